@@ -0,0 +1,450 @@
+use std::ops::Range;
+
+use crate::document::split_front_matter_with;
+use crate::parser::markdown::{
+    parse_line, parse_markdown_block_with, parse_markdown_inline_with, split_blank_lines, Markdown,
+    MarkdownInLine,
+};
+use crate::parser::options::ParserOptions;
+
+// byte range in the source, `end` is exclusive
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+// The top-level blocks of `i` with the source they came from. Lines no block parser
+// accepts are returned one by one as None, so a broken line doesn't hide the rest.
+// Blank lines between blocks are in no span.
+pub fn blocks_with_spans(i: &str, options: &ParserOptions) -> Vec<(Span, Option<Markdown>)> {
+    // front matter isn't markdown, the blocks start after it
+    let offset = match split_front_matter_with(i, options) {
+        (Some(_), rest) => i.len() - rest.len(),
+        (None, _) if i.starts_with('\u{FEFF}') => '\u{FEFF}'.len_utf8(),
+        (None, _) => 0,
+    };
+    // like parse_document, add the missing final line ending, past the end it moves nothing
+    let mut input = String::from(&i[offset..]);
+    if !input.is_empty() && !input.ends_with(['\n', '\r']) {
+        input.push('\n');
+    }
+
+    let mut blocks = Vec::new();
+    let mut rest: &str = &input;
+    loop {
+        rest = split_blank_lines(rest).1;
+        if rest.is_empty() {
+            break;
+        }
+        let start = input.len() - rest.len();
+        let (next, block) = match parse_markdown_block_with(rest, options) {
+            Ok((next, block)) => (next, Some(block)),
+            Err(_) => match parse_line(rest) {
+                Ok((next, _)) => (next, None),
+                Err(_) => ("", None),
+            },
+        };
+        let end = (input.len() - next.len()).min(i.len() - offset);
+        blocks.push((
+            Span {
+                start: offset + start,
+                end: offset + end,
+            },
+            block,
+        ));
+        rest = next;
+    }
+    blocks
+}
+
+// The inline elements in `i`, which starts at byte `offset` of the source. Container
+// markers and line endings are skipped, so this works on any span from blocks_with_spans.
+pub fn inlines_with_spans(
+    i: &str,
+    offset: usize,
+    options: &ParserOptions,
+) -> Vec<(Span, MarkdownInLine)> {
+    let mut inlines = Vec::new();
+    let mut rest = i;
+    while let Some(c) = rest.chars().next() {
+        let start = offset + i.len() - rest.len();
+        match parse_markdown_inline_with(rest, options) {
+            Ok((next, inline)) => {
+                let end = offset + i.len() - next.len();
+                inlines.push((Span { start, end }, inline));
+                rest = next;
+            }
+            Err(_) => rest = &rest[c.len_utf8()..],
+        }
+    }
+    inlines
+}
+
+// why an image's alt text doesn't describe it
+#[derive(Clone, Debug, PartialEq)]
+pub enum AltTextIssue {
+    // ![](url), fine for decorative images but those should be marked as such
+    Empty,
+    // ![image](url) or ![IMG_0042.png](url), the alt text is the matched placeholder
+    Placeholder(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MissingAltText {
+    pub span: Span,
+    pub url: String,
+    pub issue: AltTextIssue,
+}
+
+// compared ignoring case and surrounding whitespace
+const PLACEHOLDER_ALTS: &[&str] = &[
+    "alt",
+    "alt text",
+    "image",
+    "img",
+    "photo",
+    "pic",
+    "picture",
+    "placeholder",
+    "screenshot",
+    "todo",
+    "untitled",
+];
+
+const IMAGE_EXTENSIONS: &[&str] = &[".png", ".jpg", ".jpeg", ".gif", ".svg", ".webp", ".bmp"];
+
+fn alt_text_issue(alt: &str) -> Option<AltTextIssue> {
+    let alt = alt.trim();
+    if alt.is_empty() {
+        return Some(AltTextIssue::Empty);
+    }
+    let lower = alt.to_lowercase();
+    let placeholder = PLACEHOLDER_ALTS.contains(&lower.as_str())
+        || IMAGE_EXTENSIONS.iter().any(|ext| lower.ends_with(ext));
+    if placeholder {
+        return Some(AltTextIssue::Placeholder(alt.to_string()));
+    }
+    None
+}
+
+// Images in `i` whose alt text is empty or a placeholder, for accessibility checks.
+// Spans are byte offsets into `i`.
+pub fn missing_alt_text(i: &str, options: &ParserOptions) -> Vec<MissingAltText> {
+    let mut missing = Vec::new();
+    for (span, block) in blocks_with_spans(i, options) {
+        // Scanning the source also finds image syntax inside code nested in a container,
+        // so only keep what the block parser actually turned into images.
+        let mut images = match &block {
+            Some(block) => block.inlines(),
+            None => continue,
+        }
+        .into_iter()
+        .filter(|inline| matches!(inline, MarkdownInLine::Image(_, _, _)))
+        .peekable();
+        if images.peek().is_none() {
+            continue;
+        }
+
+        for (span, inline) in inlines_with_spans(&i[span.start..span.end], span.start, options) {
+            // images inside a link or a span come after it in the block's inlines
+            let mut from = span.start;
+            for nested in inline.nested() {
+                if images.peek() != Some(&nested) {
+                    continue;
+                }
+                images.next();
+                if let MarkdownInLine::Image(alt, url, _) = nested {
+                    let span = match std::ptr::eq(nested, &inline) {
+                        true => span,
+                        false => nested_span(i, from..span.end, nested, options),
+                    };
+                    from = span.end;
+                    if let Some(issue) = alt_text_issue(alt) {
+                        missing.push(MissingAltText {
+                            span,
+                            url: url.clone(),
+                            issue,
+                        });
+                    }
+                }
+            }
+        }
+    }
+    missing
+}
+
+// The span of `image`, which is somewhere inside the element at `within`: the first `![`
+// from where an image equal to it parses. All of `within` if there is none.
+fn nested_span(
+    i: &str,
+    within: Range<usize>,
+    image: &MarkdownInLine,
+    options: &ParserOptions,
+) -> Span {
+    let source = &i[within.clone()];
+    source
+        .match_indices("![")
+        .find_map(
+            |(n, _)| match parse_markdown_inline_with(&source[n..], options) {
+                Ok((rest, found)) if found == *image => Some(Span {
+                    start: within.start + n,
+                    end: within.end - rest.len(),
+                }),
+                _ => None,
+            },
+        )
+        .unwrap_or(Span {
+            start: within.start,
+            end: within.end,
+        })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Ltr,
+    Rtl,
+}
+
+// Hebrew, Arabic, Syriac, Thaana, NKo and friends, plus their presentation forms
+fn is_rtl(c: char) -> bool {
+    matches!(c, '\u{0590}'..='\u{08FF}' | '\u{FB1D}'..='\u{FDFF}' | '\u{FE70}'..='\u{FEFC}'
+        | '\u{10800}'..='\u{10FFF}' | '\u{1E800}'..='\u{1EFFF}')
+}
+
+// The direction of the first strongly directional character in `text`, the same rule
+// as dir="auto" in HTML. None if there is none, e.g. only digits and punctuation.
+pub fn text_direction(text: &str) -> Option<Direction> {
+    text.chars().find_map(|c| {
+        if is_rtl(c) {
+            Some(Direction::Rtl)
+        } else if c.is_alphabetic() {
+            Some(Direction::Ltr)
+        } else {
+            None
+        }
+    })
+}
+
+// the direction of the text in `block`, code and math are ignored
+pub fn block_direction(block: &Markdown) -> Option<Direction> {
+    block.inlines().into_iter().find_map(|inline| match inline {
+        // spans and links are looked at through the elements inside them, which come next
+        MarkdownInLine::InlineCode(_, _)
+        | MarkdownInLine::Math(_)
+        | MarkdownInLine::HardBreak
+        | MarkdownInLine::SoftBreak
+        | MarkdownInLine::FootnoteRef(_)
+        | MarkdownInLine::InlineFootnote(_)
+        | MarkdownInLine::Html(_)
+        | MarkdownInLine::Emoji(_)
+        | MarkdownInLine::Link(_, _, _)
+        | MarkdownInLine::Bold(_)
+        | MarkdownInLine::Italic(_)
+        | MarkdownInLine::Strikethrough(_)
+        | MarkdownInLine::Highlight(_)
+        | MarkdownInLine::Subscript(_)
+        | MarkdownInLine::Superscript(_) => None,
+        MarkdownInLine::Image(text, _, _) | MarkdownInLine::Plain(text) => text_direction(text),
+        MarkdownInLine::WikiLink { target, alias } => {
+            text_direction(alias.as_ref().unwrap_or(target))
+        }
+    })
+}
+
+// the prose in `block` for the analyses above, code and math are left out
+fn block_text(block: &Markdown) -> String {
+    let mut text = String::new();
+    for inline in block.inlines() {
+        match inline {
+            MarkdownInLine::InlineCode(_, _)
+            | MarkdownInLine::Math(_)
+            | MarkdownInLine::FootnoteRef(_)
+            | MarkdownInLine::InlineFootnote(_)
+            | MarkdownInLine::Html(_)
+            | MarkdownInLine::Emoji(_)
+            | MarkdownInLine::Link(_, _, _)
+            | MarkdownInLine::Bold(_)
+            | MarkdownInLine::Italic(_)
+            | MarkdownInLine::Strikethrough(_)
+            | MarkdownInLine::Highlight(_)
+            | MarkdownInLine::Subscript(_)
+            | MarkdownInLine::Superscript(_) => {}
+            MarkdownInLine::HardBreak | MarkdownInLine::SoftBreak => text.push('\n'),
+            MarkdownInLine::Image(s, _, _)
+            | MarkdownInLine::Plain(s)
+            | MarkdownInLine::WikiLink { alias: Some(s), .. }
+            | MarkdownInLine::WikiLink {
+                target: s,
+                alias: None,
+            } => {
+                text.push_str(s);
+                text.push(' ');
+            }
+        }
+    }
+    text
+}
+
+// Guesses the natural language of a piece of text as a BCP 47 code like "de".
+// Implement this to plug in a real detector, ScriptDetector only knows scripts.
+pub trait LanguageDetector {
+    fn detect(&self, text: &str) -> Option<String>;
+}
+
+// Detects the languages that are the only common one written in their script, so Greek
+// is "el" but Latin or Cyrillic text can't be told apart and gives None.
+pub struct ScriptDetector;
+
+impl LanguageDetector for ScriptDetector {
+    fn detect(&self, text: &str) -> Option<String> {
+        // the first letter decides, a script shared by many languages decides nothing
+        let language = match text.chars().find(|c| c.is_alphabetic())? {
+            '\u{0370}'..='\u{03FF}' => "el",
+            '\u{0530}'..='\u{058F}' => "hy",
+            '\u{0590}'..='\u{05FF}' => "he",
+            '\u{0E00}'..='\u{0E7F}' => "th",
+            '\u{10A0}'..='\u{10FF}' => "ka",
+            '\u{3040}'..='\u{30FF}' => "ja",
+            '\u{1100}'..='\u{11FF}' | '\u{AC00}'..='\u{D7AF}' => "ko",
+            _ => return None,
+        };
+        Some(language.to_string())
+    }
+}
+
+// The language of every block in `blocks`, in the same order, for lang attributes.
+// Blocks without any prose, like code blocks, are None.
+pub fn block_languages(
+    blocks: &[Markdown],
+    detector: &dyn LanguageDetector,
+) -> Vec<Option<String>> {
+    blocks
+        .iter()
+        .map(|block| {
+            let text = block_text(block);
+            if text.trim().is_empty() {
+                return None;
+            }
+            detector.detect(&text)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocks_with_spans() {
+        let options = ParserOptions::default();
+        let spans: Vec<_> = blocks_with_spans("\u{FEFF}# h1\r\n[broken\n- a\n- b", &options)
+            .into_iter()
+            .map(|(span, block)| (span, block.is_some()))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                (Span { start: 3, end: 9 }, true),
+                (Span { start: 9, end: 17 }, true),
+                (Span { start: 17, end: 24 }, true),
+            ]
+        );
+        let front_matter = blocks_with_spans("---\ntitle: a\n---\n# a\n", &options);
+        assert_eq!(front_matter.len(), 1);
+        assert_eq!(front_matter[0].0, Span { start: 17, end: 21 });
+    }
+
+    #[test]
+    fn test_direction() {
+        assert_eq!(text_direction("שלום world"), Some(Direction::Rtl));
+        assert_eq!(text_direction("123. hello مرحبا"), Some(Direction::Ltr));
+        assert_eq!(text_direction("42 !"), None);
+        assert_eq!(text_direction("\u{FEFF}hello"), Some(Direction::Ltr));
+
+        let parse = |i| crate::parser::markdown::parse_markdown_block(i).unwrap().1;
+        assert_eq!(
+            block_direction(&parse("# `code` 1. مرحبا\n")),
+            Some(Direction::Rtl)
+        );
+        assert_eq!(
+            block_direction(&parse("- **bold** נ\n")),
+            Some(Direction::Ltr)
+        );
+        assert_eq!(block_direction(&parse("```\ncode\n```\n")), None);
+    }
+
+    #[test]
+    fn test_block_languages() {
+        let blocks = crate::parser::markdown::parse_markdown(
+            "# Καλημέρα\n`code` こんにちは\n\ntext\n```\nשלום\n```\n",
+        )
+        .unwrap()
+        .1;
+        assert_eq!(
+            block_languages(&blocks, &ScriptDetector),
+            vec![
+                Some(String::from("el")),
+                Some(String::from("ja")),
+                None,
+                None
+            ]
+        );
+
+        struct Always;
+        impl LanguageDetector for Always {
+            fn detect(&self, _: &str) -> Option<String> {
+                Some(String::from("en"))
+            }
+        }
+        assert_eq!(
+            block_languages(&blocks[2..], &Always),
+            vec![Some(String::from("en")), None]
+        );
+    }
+
+    #[test]
+    fn test_missing_alt_text() {
+        let source = "![](a.png) ![a cat](b.png)\n\n> - ![Image](c.png)\n```\n![](code.png)\n```\n![diagram.PNG](d.png)";
+        assert_eq!(
+            missing_alt_text(source, &ParserOptions::default()),
+            vec![
+                MissingAltText {
+                    span: Span { start: 0, end: 10 },
+                    url: String::from("a.png"),
+                    issue: AltTextIssue::Empty,
+                },
+                MissingAltText {
+                    span: Span { start: 32, end: 47 },
+                    url: String::from("c.png"),
+                    issue: AltTextIssue::Placeholder(String::from("Image")),
+                },
+                MissingAltText {
+                    span: Span { start: 70, end: 91 },
+                    url: String::from("d.png"),
+                    issue: AltTextIssue::Placeholder(String::from("diagram.PNG")),
+                },
+            ]
+        );
+        assert_eq!(&source[32..47], "![Image](c.png)");
+        assert_eq!(&source[70..91], "![diagram.PNG](d.png)");
+
+        // images inside a link or emphasis, and the ones after them
+        let empty = |start, end, url: &str| MissingAltText {
+            span: Span { start, end },
+            url: String::from(url),
+            issue: AltTextIssue::Empty,
+        };
+        assert_eq!(
+            missing_alt_text(
+                "[![](a.png)](https://x) ![](b.png)",
+                &ParserOptions::default()
+            ),
+            vec![empty(1, 11, "a.png"), empty(24, 34, "b.png")]
+        );
+        assert_eq!(
+            missing_alt_text("*![](a.png)* and ![](b.png)", &ParserOptions::default()),
+            vec![empty(1, 11, "a.png"), empty(17, 27, "b.png")]
+        );
+    }
+}
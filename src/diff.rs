@@ -0,0 +1,337 @@
+use crate::analysis::blocks_with_spans;
+use crate::parser::options::ParserOptions;
+
+// how diff_markdown marks changes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DiffStyle {
+    // {--deleted--} and {++inserted++}
+    CriticMarkup,
+    // ~~deleted~~ and **inserted**, for renderers without CriticMarkup support
+    Markdown,
+}
+
+impl DiffStyle {
+    fn marks(self, op: Op) -> (&'static str, &'static str) {
+        match (self, op) {
+            (DiffStyle::CriticMarkup, Op::Delete) => ("{--", "--}"),
+            (DiffStyle::CriticMarkup, _) => ("{++", "++}"),
+            (DiffStyle::Markdown, Op::Delete) => ("~~", "~~"),
+            (DiffStyle::Markdown, _) => ("**", "**"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+// above this many cells the diff of two blocks is not refined to words
+const MAX_TABLE: usize = 4_000_000;
+
+// Longest common subsequence edit script turning `old` into `new`, or None if the
+// table would be bigger than MAX_TABLE.
+fn edit_script<T: PartialEq>(old: &[T], new: &[T]) -> Option<Vec<Op>> {
+    let (n, m) = (old.len(), new.len());
+    if (n + 1).saturating_mul(m + 1) > MAX_TABLE {
+        return None;
+    }
+    // lengths[i][j]: lcs of old[i..] and new[j..]
+    let mut lengths = vec![0u32; (n + 1) * (m + 1)];
+    let at = |i: usize, j: usize| i * (m + 1) + j;
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[at(i, j)] = if old[i] == new[j] {
+                lengths[at(i + 1, j + 1)] + 1
+            } else {
+                lengths[at(i + 1, j)].max(lengths[at(i, j + 1)])
+            };
+        }
+    }
+
+    let mut ops = Vec::with_capacity(n + m);
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && old[i] == new[j] {
+            ops.push(Op::Equal);
+            i += 1;
+            j += 1;
+        } else if j == m || (i < n && lengths[at(i + 1, j)] >= lengths[at(i, j + 1)]) {
+            ops.push(Op::Delete);
+            i += 1;
+        } else {
+            ops.push(Op::Insert);
+            j += 1;
+        }
+    }
+    Some(ops)
+}
+
+// Length of the markers `line` starts with: indentation, quote markers, a list marker
+// with its task box and a heading marker. Marks go after them so the block survives.
+fn block_prefix(line: &str) -> usize {
+    let mut rest = line.trim_start_matches([' ', '\t']);
+    let mut listed = false;
+    loop {
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let (after, list) = if let Some(after) = rest.strip_prefix('>') {
+            rest = after.strip_prefix(' ').unwrap_or(after);
+            continue;
+        } else if rest.starts_with(['-', '*', '+']) {
+            (&rest[1..], true)
+        } else if digits > 0 && rest[digits..].starts_with(['.', ')']) {
+            (&rest[digits + 1..], true)
+        } else if rest.starts_with('#') {
+            (rest.trim_start_matches('#'), false)
+        } else if listed
+            && ["[ ]", "[x]", "[X]"]
+                .iter()
+                .any(|task| rest.starts_with(task))
+        {
+            (&rest[3..], false)
+        } else {
+            break;
+        };
+        if !after.starts_with([' ', '\t']) {
+            break;
+        }
+        listed = list;
+        rest = after.trim_start_matches([' ', '\t']);
+    }
+    line.len() - rest.len()
+}
+
+// Appends `text` marked as deleted or inserted, one mark per line so none spans a line
+// break. Block markers stay outside the marks and code fences aren't marked at all.
+fn push_marked(out: &mut String, text: &str, op: Op, style: DiffStyle) {
+    let (open, close) = style.marks(op);
+    for (n, line) in text.split('\n').enumerate() {
+        if n > 0 {
+            out.push('\n');
+        }
+        let at_line_start = n > 0 || out.is_empty() || out.ends_with('\n');
+        let (prefix, line) = line.split_at(if at_line_start { block_prefix(line) } else { 0 });
+        out.push_str(prefix);
+        let content = line.trim_end_matches('\r');
+        let fence = at_line_start
+            && ["```", "~~~"]
+                .iter()
+                .any(|fence| content.starts_with(fence));
+        if content.trim().is_empty() || fence {
+            out.push_str(line);
+            continue;
+        }
+        // marks hug the words so `{++ a ++}` doesn't happen
+        let start = content.len() - content.trim_start().len();
+        let end = content.trim_end().len();
+        out.push_str(&content[..start]);
+        out.push_str(open);
+        out.push_str(&content[start..end]);
+        out.push_str(close);
+        out.push_str(&line[end..]);
+    }
+}
+
+// words and the whitespace between them
+fn tokens(text: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut space = None;
+    for (n, c) in text.char_indices() {
+        let is_space = c.is_whitespace();
+        if space.is_some_and(|space| space != is_space) {
+            tokens.push(&text[start..n]);
+            start = n;
+        }
+        space = Some(is_space);
+    }
+    if start < text.len() {
+        tokens.push(&text[start..]);
+    }
+    tokens
+}
+
+// whether at least half of the words of two blocks are the same
+fn similar(old: &str, new: &str) -> bool {
+    let words = |text| {
+        tokens(text)
+            .into_iter()
+            .filter(|t| !t.trim().is_empty())
+            .collect::<Vec<_>>()
+    };
+    let (old, new) = (words(old), words(new));
+    let common = match edit_script(&old, &new) {
+        Some(ops) => ops.iter().filter(|&&op| op == Op::Equal).count(),
+        None => return false,
+    };
+    // 2 * common / total words >= 1 / 2
+    common > 0 && 4 * common >= old.len() + new.len()
+}
+
+// the word level diff of two versions of a block
+fn diff_words(out: &mut String, old: &str, new: &str, style: DiffStyle) {
+    let (old_tokens, new_tokens) = (tokens(old), tokens(new));
+    let ops = match edit_script(&old_tokens, &new_tokens) {
+        Some(ops) => ops,
+        None => {
+            push_marked(out, old, Op::Delete, style);
+            push_marked(out, new, Op::Insert, style);
+            return;
+        }
+    };
+
+    // collect runs so a changed phrase gets one mark instead of one per word
+    let (mut i, mut j) = (0, 0);
+    let mut k = 0;
+    while k < ops.len() {
+        if ops[k] == Op::Equal {
+            out.push_str(old_tokens[i]);
+            i += 1;
+            j += 1;
+            k += 1;
+            continue;
+        }
+        let (mut deleted, mut inserted) = (String::new(), String::new());
+        while k < ops.len() && ops[k] != Op::Equal {
+            if ops[k] == Op::Delete {
+                deleted.push_str(old_tokens[i]);
+                i += 1;
+            } else {
+                inserted.push_str(new_tokens[j]);
+                j += 1;
+            }
+            k += 1;
+        }
+        // changed whitespace alone is shown as it is now
+        if !deleted.trim().is_empty() {
+            push_marked(out, &deleted, Op::Delete, style);
+        }
+        push_marked(out, &inserted, Op::Insert, style);
+    }
+}
+
+// A single markdown document showing how `new` differs from `old`. Blocks are matched
+// first, blocks changed in place are diffed word by word.
+pub fn diff_markdown(old: &str, new: &str, style: DiffStyle) -> String {
+    let options = ParserOptions::default();
+    // blank lines between blocks are kept as blocks of their own
+    let block_sources = |i: &'_ str| -> Vec<String> {
+        let mut sources = Vec::new();
+        let mut last = 0;
+        for (span, _) in blocks_with_spans(i, &options) {
+            if last < span.start {
+                sources.push(i[last..span.start].to_string());
+            }
+            sources.push(i[span.start..span.end].to_string());
+            last = span.end;
+        }
+        if last < i.len() {
+            sources.push(i[last..].to_string());
+        }
+        sources
+    };
+    let (old_blocks, new_blocks) = (block_sources(old), block_sources(new));
+    let ops = edit_script(&old_blocks, &new_blocks).unwrap_or_else(|| {
+        let mut ops = vec![Op::Delete; old_blocks.len()];
+        ops.extend(vec![Op::Insert; new_blocks.len()]);
+        ops
+    });
+
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    let mut k = 0;
+    while k < ops.len() {
+        if ops[k] == Op::Equal {
+            out.push_str(&new_blocks[j]);
+            i += 1;
+            j += 1;
+            k += 1;
+            continue;
+        }
+        let (first_old, first_new) = (i, j);
+        while k < ops.len() && ops[k] != Op::Equal {
+            match ops[k] {
+                Op::Delete => i += 1,
+                _ => j += 1,
+            }
+            k += 1;
+        }
+        // a removed block that resembles a later added one was edited in place,
+        // everything else was removed or added as a whole
+        let (deleted, inserted) = (&old_blocks[first_old..i], &new_blocks[first_new..j]);
+        let mut next = 0;
+        for old in deleted {
+            match inserted[next..].iter().position(|new| similar(old, new)) {
+                Some(pos) => {
+                    for new in &inserted[next..next + pos] {
+                        push_marked(&mut out, new, Op::Insert, style);
+                    }
+                    diff_words(&mut out, old, &inserted[next + pos], style);
+                    next += pos + 1;
+                }
+                None => push_marked(&mut out, old, Op::Delete, style),
+            }
+        }
+        for new in &inserted[next..] {
+            push_marked(&mut out, new, Op::Insert, style);
+        }
+    }
+    if !new.is_empty() && !new.ends_with(['\n', '\r']) && out.ends_with('\n') {
+        out.pop();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_markdown() {
+        let old = "# Title\nThe quick brown fox jumps.\nremoved line\n- a\n";
+        let new = "# Title\nThe slow brown fox jumps.\n- a\n- b\nadded\n";
+        assert_eq!(
+            diff_markdown(old, new, DiffStyle::CriticMarkup),
+            "# Title\nThe {--quick--}{++slow++} brown fox jumps.\n{--removed line--}\n- a\n- {++b++}\n{++added++}\n"
+        );
+        assert_eq!(
+            diff_markdown("a b c\n", "a c d", DiffStyle::Markdown),
+            "a ~~b~~ c **d**"
+        );
+        assert_eq!(diff_markdown(old, old, DiffStyle::CriticMarkup), old);
+    }
+
+    #[test]
+    fn test_diff_block_markers() {
+        let diff = |old, new| diff_markdown(old, new, DiffStyle::CriticMarkup);
+        assert_eq!(diff("# A\n", "para\n"), "# {--A--}\n{++para++}\n");
+        assert_eq!(diff("a\n", "a\n\n## B c\n"), "a\n\n## {++B c++}\n");
+        assert_eq!(
+            diff("a\n", "a\n\n- [ ] b\n  1. c\n"),
+            "a\n\n- [ ] {++b++}\n  1. {++c++}\n"
+        );
+        assert_eq!(diff("a\n\n> - b\n", "a\n"), "a\n\n> - {--b--}\n");
+        assert_eq!(
+            diff("a\n", "a\n\n```rust\nx\n```\n"),
+            "a\n\n```rust\n{++x++}\n```\n"
+        );
+        assert_eq!(
+            diff("a\n\n~~~\nx\n~~~\n", "a\n"),
+            "a\n\n~~~\n{--x--}\n~~~\n"
+        );
+        // emphasis and hashtags aren't block markers
+        assert_eq!(diff("a\n", "a\n\n**b** #c\n"), "a\n\n{++**b** #c++}\n");
+    }
+
+    #[test]
+    fn test_edit_script() {
+        use Op::*;
+        assert_eq!(
+            edit_script(&[1, 2, 3], &[1, 3, 4]),
+            Some(vec![Equal, Delete, Equal, Insert])
+        );
+        assert_eq!(edit_script::<u8>(&[], &[]), Some(vec![]));
+    }
+}
@@ -0,0 +1,683 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::str;
+
+use crate::parser::emoji::emoji;
+use crate::parser::markdown::{
+    self, plain_text, InlineKind, Markdown, MarkdownInLine, MarkdownText, ParseError,
+};
+use crate::parser::options::ParserOptions;
+
+// a parsed document, with what tools around the parser need to know about it
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Document {
+    pub front_matter: Option<FrontMatter>,
+    pub blocks: Vec<Markdown>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FrontMatterFormat {
+    // between --- lines, the last one may be ...
+    Yaml,
+    // between +++ lines, like Hugo and Zola
+    Toml,
+    // `Key: value` lines up to the first blank line, like MultiMarkdown.
+    // Only with ParserOptions::metadata_block.
+    MultiMarkdown,
+}
+
+// ---
+// title: Hello
+// ---
+// at the very start of a document, or a MultiMarkdown metadata block
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrontMatter {
+    pub format: FrontMatterFormat,
+    // what is between the delimiter lines
+    pub raw: String,
+    // the top-level `key: value` (or `key = value`) pairs, None if there is more than that.
+    // MultiMarkdown keys are lowercase without spaces, `Base Header Level` is
+    // `baseheaderlevel`.
+    pub fields: Option<BTreeMap<String, String>>,
+}
+
+impl FrontMatter {
+    pub fn parse(format: FrontMatterFormat, raw: &str) -> Self {
+        let fields = match format {
+            FrontMatterFormat::Yaml => parse_fields(raw, ':'),
+            FrontMatterFormat::Toml => parse_fields(raw, '='),
+            FrontMatterFormat::MultiMarkdown => Some(parse_metadata(raw)),
+        };
+        FrontMatter {
+            format,
+            raw: raw.to_string(),
+            fields,
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields.as_ref()?.get(key).map(String::as_str)
+    }
+
+    // Adds the fields of `defaults` this front matter doesn't set, its own values win.
+    // Front matter that is kept raw only (or defaults that are) can't be merged and is
+    // left as it is.
+    pub fn apply_defaults(&mut self, defaults: &FrontMatter) {
+        if let (Some(fields), Some(defaults)) = (self.fields.as_mut(), defaults.fields.as_ref()) {
+            for (key, value) in defaults {
+                fields.entry(key.clone()).or_insert_with(|| value.clone());
+            }
+        }
+    }
+}
+
+// flat `key: value` YAML or `key = value` TOML, quotes around values are removed
+fn parse_fields(raw: &str, separator: char) -> Option<BTreeMap<String, String>> {
+    let mut fields = BTreeMap::new();
+    for line in raw.lines() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = line.split_once(separator)?;
+        let value = value.trim();
+        // lists, nested YAML and TOML [tables] stay raw
+        if key.is_empty() || key.starts_with([' ', '\t', '-', '[']) || value.is_empty() {
+            return None;
+        }
+        let unquoted = ['"', '\'']
+            .iter()
+            .find_map(|&q| value.strip_prefix(q)?.strip_suffix(q));
+        fields.insert(
+            key.trim_end().to_string(),
+            unquoted.unwrap_or(value).to_string(),
+        );
+    }
+    Some(fields)
+}
+
+// MultiMarkdown metadata, an indented line continues the value before it on a new line
+fn parse_metadata(raw: &str) -> BTreeMap<String, String> {
+    let mut fields: BTreeMap<String, String> = BTreeMap::new();
+    let mut key = String::new();
+    for line in raw.lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some(value) = fields.get_mut(&key) {
+                if !value.is_empty() {
+                    value.push('\n');
+                }
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            key = name
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .collect::<String>()
+                .to_lowercase();
+            fields.insert(key.clone(), value.trim().to_string());
+        }
+    }
+    fields
+}
+
+// Title: My Document
+// Author: Ann
+fn is_metadata_line(line: &str) -> bool {
+    let key = |c: char| c.is_alphanumeric() || matches!(c, ' ' | '\t' | '-' | '_');
+    match line.split_once(':') {
+        Some((name, _)) => {
+            !name.is_empty() && !name.starts_with([' ', '\t']) && name.chars().all(key)
+        }
+        None => false,
+    }
+}
+
+// Splits a MultiMarkdown metadata block off `i`, its lines and what follows the blank
+// line ending it. A leading byte order mark is skipped.
+fn split_metadata_block(i: &str) -> Option<(&str, &str)> {
+    let source = i.strip_prefix('\u{FEFF}').unwrap_or(i);
+    let mut end = 0;
+    for line in source.split_inclusive('\n') {
+        if line.trim().is_empty() {
+            return (end > 0).then(|| (&source[..end], &source[end + line.len()..]));
+        }
+        let continued = end > 0 && line.starts_with([' ', '\t']);
+        if !continued && !is_metadata_line(line) {
+            return None;
+        }
+        end += line.len();
+    }
+    (end > 0).then_some((source, ""))
+}
+
+// Splits the front matter off `i`: what is between a first line of `---` and the next
+// line of `---` or `...`, or between two lines of `+++`, and what follows the closing
+// line. A leading byte order mark is skipped.
+pub fn split_front_matter(i: &str) -> (Option<(FrontMatterFormat, &str)>, &str) {
+    let source = i.strip_prefix('\u{FEFF}').unwrap_or(i);
+    let mut lines = source.split_inclusive('\n');
+    let (format, start) = match lines.next() {
+        Some(first) if first.trim_end() == "---" => (FrontMatterFormat::Yaml, first.len()),
+        Some(first) if first.trim_end() == "+++" => (FrontMatterFormat::Toml, first.len()),
+        _ => return (None, i),
+    };
+    let mut end = start;
+    for line in lines {
+        let closing = match format {
+            FrontMatterFormat::Yaml => matches!(line.trim_end(), "---" | "..."),
+            _ => line.trim_end() == "+++",
+        };
+        if closing {
+            return (
+                Some((format, &source[start..end])),
+                &source[end + line.len()..],
+            );
+        }
+        end += line.len();
+    }
+    (None, i)
+}
+
+// Same as split_front_matter, but with ParserOptions::metadata_block a MultiMarkdown
+// metadata block counts as front matter too
+pub fn split_front_matter_with<'a>(
+    i: &'a str,
+    options: &ParserOptions,
+) -> (Option<(FrontMatterFormat, &'a str)>, &'a str) {
+    match split_front_matter(i) {
+        (None, _) if options.metadata_block => match split_metadata_block(i) {
+            Some((raw, rest)) => (Some((FrontMatterFormat::MultiMarkdown, raw)), rest),
+            None => (None, i),
+        },
+        split => split,
+    }
+}
+
+// error offsets of parsing what follows front matter of `len` bytes
+fn shift_error(error: ParseError, len: usize) -> ParseError {
+    match error {
+        ParseError::Unparsed(offset) => ParseError::Unparsed(offset + len),
+        ParseError::InvalidUtf8(offset) => ParseError::InvalidUtf8(offset + len),
+        error => error,
+    }
+}
+
+// one heading of Document::outline
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutlineEntry {
+    pub level: usize,
+    pub text: String,
+}
+
+impl Document {
+    pub fn parse(i: &str, options: &ParserOptions) -> Result<Self, ParseError> {
+        let (front_matter, rest) = split_front_matter_with(i, options);
+        Ok(Document {
+            front_matter: front_matter.map(|(format, raw)| FrontMatter::parse(format, raw)),
+            blocks: markdown::parse(rest, options)
+                .map_err(|e| shift_error(e, i.len() - rest.len()))?,
+        })
+    }
+
+    pub fn parse_bytes(i: &[u8], options: &ParserOptions) -> Result<Self, ParseError> {
+        // front matter has to be in the valid UTF-8 the input starts with
+        let valid = match str::from_utf8(i) {
+            Ok(text) => text,
+            Err(e) => str::from_utf8(&i[..e.valid_up_to()]).unwrap_or_default(),
+        };
+        let (front_matter, rest) = split_front_matter_with(valid, options);
+        let len = valid.len() - rest.len();
+        Ok(Document {
+            front_matter: front_matter.map(|(format, raw)| FrontMatter::parse(format, raw)),
+            blocks: markdown::parse_bytes(&i[len..], options).map_err(|e| shift_error(e, len))?,
+        })
+    }
+
+    // the top-level headings in document order
+    pub fn outline(&self) -> Vec<OutlineEntry> {
+        self.blocks
+            .iter()
+            .filter_map(|block| match block {
+                Markdown::Heading(level, text) => Some(OutlineEntry {
+                    level: *level,
+                    text: plain_text(text).trim().to_string(),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // the destination of every link, including links nested in lists and quotes
+    pub fn links(&self) -> Vec<&str> {
+        self.blocks
+            .iter()
+            .flat_map(Markdown::inlines)
+            .filter_map(|inline| match inline {
+                MarkdownInLine::Link(_, url, _) => Some(url.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // The labels of the footnotes referenced with [^label], each once in the order of its
+    // first reference, which is the order they are usually numbered in. Labels without
+    // a Markdown::FootnoteDefinition are included too.
+    pub fn footnote_refs(&self) -> Vec<&str> {
+        let mut labels = Vec::new();
+        for inline in self.blocks.iter().flat_map(Markdown::inlines) {
+            if let MarkdownInLine::FootnoteRef(label) = inline {
+                if !labels.contains(&label.as_str()) {
+                    labels.push(label.as_str());
+                }
+            }
+        }
+        labels
+    }
+
+    // The document as a reply quote, preceded by `attribution` ("Ann wrote:") if given.
+    // Quotes already in it end up nested one level deeper.
+    pub fn as_quote(&self, attribution: Option<&str>) -> Document {
+        self.quote_blocks(0..self.blocks.len(), attribution)
+    }
+
+    // like as_quote, for a selection of the top-level blocks
+    pub fn quote_blocks(&self, blocks: Range<usize>, attribution: Option<&str>) -> Document {
+        let mut quoted = Vec::new();
+        if let Some(attribution) = attribution {
+            quoted.push(Markdown::Paragraph(vec![MarkdownInLine::Plain(
+                attribution.to_string(),
+            )]));
+        }
+        quoted.push(Markdown::Quote {
+            body: self.blocks[blocks].to_vec(),
+            cite: None,
+        });
+        Document {
+            front_matter: None,
+            blocks: quoted,
+        }
+    }
+
+    // Turns every inline element of one of the `kinds` into plain text, for simpler
+    // variants like SMS: spans and links keep their text without any markup inside it (a
+    // link without text its url, a wiki link its alias or target), images their tag, hard
+    // and soft breaks become a space, emoji their character if it is known and HTML tags
+    // disappear.
+    pub fn strip_styles(&mut self, kinds: &[InlineKind]) {
+        for block in &mut self.blocks {
+            block.for_each_inline_mut(&mut |inline| {
+                if !kinds.contains(&inline.inline_kind()) {
+                    return;
+                }
+                let text = match inline {
+                    MarkdownInLine::Link(text, url, _) if text.is_empty() => std::mem::take(url),
+                    MarkdownInLine::Image(tag, _, _) => std::mem::take(tag),
+                    MarkdownInLine::InlineCode(text, _)
+                    | MarkdownInLine::Math(text)
+                    | MarkdownInLine::Plain(text) => std::mem::take(text),
+                    MarkdownInLine::Link(text, _, _)
+                    | MarkdownInLine::Bold(text)
+                    | MarkdownInLine::Italic(text)
+                    | MarkdownInLine::Strikethrough(text)
+                    | MarkdownInLine::Highlight(text)
+                    | MarkdownInLine::Subscript(text)
+                    | MarkdownInLine::Superscript(text) => plain_text(text),
+                    MarkdownInLine::WikiLink { target, alias } => {
+                        alias.take().unwrap_or_else(|| std::mem::take(target))
+                    }
+                    MarkdownInLine::HardBreak | MarkdownInLine::SoftBreak => String::from(" "),
+                    MarkdownInLine::FootnoteRef(label) => format!("[{}]", label),
+                    MarkdownInLine::InlineFootnote(text) => format!("({})", plain_text(text)),
+                    MarkdownInLine::Html(_) => String::new(),
+                    MarkdownInLine::Emoji(name) => match emoji(name) {
+                        Some(emoji) => emoji.to_string(),
+                        None => format!(":{}:", name),
+                    },
+                };
+                *inline = MarkdownInLine::Plain(text);
+            });
+        }
+    }
+
+    // Turns every paragraph that is just an image, maybe with a caption of plain and
+    // italic text on the line below it, into a Markdown::Figure, for <figure> output.
+    pub fn make_figures(&mut self) {
+        for block in &mut self.blocks {
+            block.for_each_block_mut(&mut |block| {
+                if let Markdown::Paragraph(text) = block {
+                    if let Some(figure) = figure(text) {
+                        *block = figure;
+                    }
+                }
+            });
+        }
+    }
+}
+
+// the figure `text` of a paragraph is, see Document::make_figures
+fn figure(text: &MarkdownText) -> Option<Markdown> {
+    let (image, rest) = match text.split_first()? {
+        (image @ MarkdownInLine::Image(_, _, _), rest) => (image, rest),
+        _ => return None,
+    };
+    let mut caption = rest.to_vec();
+    // the line break before the caption, spaces may end the image's line
+    if let Some(MarkdownInLine::Plain(spaces)) = caption.first() {
+        if !spaces.trim_start_matches([' ', '\t']).is_empty() {
+            return None;
+        }
+        caption.remove(0);
+    }
+    match caption.first() {
+        None => {}
+        Some(MarkdownInLine::HardBreak | MarkdownInLine::SoftBreak) => {
+            caption.remove(0);
+        }
+        Some(_) => return None,
+    }
+    let one_line = caption
+        .iter()
+        .all(|inline| matches!(inline, MarkdownInLine::Plain(_) | MarkdownInLine::Italic(_)));
+    if !one_line {
+        return None;
+    }
+    Some(Markdown::Figure {
+        image: image.clone(),
+        caption,
+    })
+}
+
+// `source` with every line quoted for a reply, "> " in front of text and ">" on blank
+// lines, so quotes in it become "> > ". Parsing the result gives the same blocks as
+// parsing `source` and calling Document::as_quote.
+pub fn quote_source(source: &str, attribution: Option<&str>) -> String {
+    let mut quoted = String::new();
+    if let Some(attribution) = attribution {
+        quoted.push_str(attribution);
+        quoted.push('\n');
+    }
+    for line in source.lines() {
+        if line.is_empty() {
+            quoted.push_str(">\n");
+        } else {
+            quoted.push_str("> ");
+            quoted.push_str(line);
+            quoted.push('\n');
+        }
+    }
+    quoted
+}
+
+// Lowercase words joined by `-`, for urls and heading anchors: "Hello, World!" is
+// "hello-world". Letters of any script are kept.
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for word in text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+    {
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.extend(word.chars().flat_map(char::to_lowercase));
+    }
+    slug
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outline_and_links() {
+        let document = Document::parse(
+            "# Guide\n[a](a.md)\n## **Setup**\n> - [b](../b.md#x)\n```\n[c](c.md)\n```\n",
+            &ParserOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            document.outline(),
+            vec![
+                OutlineEntry {
+                    level: 1,
+                    text: String::from("Guide")
+                },
+                OutlineEntry {
+                    level: 2,
+                    text: String::from("Setup")
+                },
+            ]
+        );
+        assert_eq!(document.links(), vec!["a.md", "../b.md#x"]);
+
+        let document = Document::parse(
+            "a[^b] c^[d [^e]]\n- [^b] [^f]\n\n[^b]: note\n",
+            &ParserOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(document.footnote_refs(), vec!["b", "e", "f"]);
+    }
+
+    #[test]
+    fn test_as_quote() {
+        let options = ParserOptions::default();
+        let source = "# Hi\n\n> earlier\n- a\n";
+        let document = Document::parse(source, &options).unwrap();
+        let quoted = document.as_quote(Some("Ann wrote:"));
+        assert_eq!(
+            quoted.blocks[0],
+            Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("Ann wrote:"))])
+        );
+        assert_eq!(
+            quoted.blocks[1],
+            Markdown::Quote {
+                body: document.blocks.clone(),
+                cite: None
+            }
+        );
+
+        let text = quote_source(source, Some("Ann wrote:"));
+        assert_eq!(text, "Ann wrote:\n> # Hi\n>\n> > earlier\n> - a\n");
+        assert_eq!(Document::parse(&text, &options).unwrap(), quoted);
+
+        assert_eq!(
+            document.quote_blocks(2..3, None).blocks,
+            vec![Markdown::Quote {
+                body: vec![document.blocks[2].clone()],
+                cite: None
+            }]
+        );
+    }
+
+    #[test]
+    fn test_make_figures() {
+        let mut document = Document::parse(
+            "![cat](cat.png)\n*A cat*, asleep\n\n> ![dog](dog.png)  \n\n![a](a.png) and text\n\n![b](b.png)\n**bold**\n",
+            &ParserOptions::default(),
+        )
+        .unwrap();
+        document.make_figures();
+        let image =
+            |alt: &str, url: &str| MarkdownInLine::Image(alt.to_string(), url.to_string(), None);
+        assert_eq!(
+            document.blocks[..2],
+            [
+                Markdown::Figure {
+                    image: image("cat", "cat.png"),
+                    caption: vec![
+                        MarkdownInLine::Italic(vec![MarkdownInLine::Plain(String::from("A cat"))]),
+                        MarkdownInLine::Plain(String::from(", asleep")),
+                    ],
+                },
+                Markdown::Quote {
+                    body: vec![Markdown::Figure {
+                        image: image("dog", "dog.png"),
+                        caption: vec![]
+                    }],
+                    cite: None
+                },
+            ]
+        );
+        // text beside the image or a bold caption stay paragraphs
+        assert!(document.blocks[2..]
+            .iter()
+            .all(|block| matches!(block, Markdown::Paragraph(_))));
+
+        let hard_breaks = ParserOptions {
+            hard_breaks: true,
+            ..ParserOptions::default()
+        };
+        let mut document = Document::parse("![a](a.png)\ncaption\n", &hard_breaks).unwrap();
+        document.make_figures();
+        assert_eq!(
+            document.blocks,
+            vec![Markdown::Figure {
+                image: image("a", "a.png"),
+                caption: vec![MarkdownInLine::Plain(String::from("caption"))],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_strip_styles() {
+        let mut document = Document::parse(
+            "**a** *b* `c` ![alt](x.png) [](y.md)\n> - [d](z.md)\n",
+            &ParserOptions::default(),
+        )
+        .unwrap();
+        let original = document.clone();
+        document.strip_styles(&[]);
+        assert_eq!(document, original);
+
+        document.strip_styles(&[InlineKind::Bold, InlineKind::Image, InlineKind::Link]);
+        let plain = |text: &str| MarkdownInLine::Plain(text.to_string());
+        assert_eq!(
+            document.blocks[0],
+            Markdown::Paragraph(vec![
+                plain("a"),
+                plain(" "),
+                MarkdownInLine::Italic(vec![MarkdownInLine::Plain(String::from("b"))]),
+                plain(" "),
+                MarkdownInLine::InlineCode(String::from("c"), None),
+                plain(" "),
+                plain("alt"),
+                plain(" "),
+                plain("y.md"),
+            ])
+        );
+        assert_eq!(document.blocks[1].inlines(), vec![&plain("d")]);
+    }
+
+    #[test]
+    fn test_front_matter() {
+        let options = ParserOptions::default();
+        let source = "---\ntitle: \"Hello: world\"\ndate: 2024-01-02\n---\n# Hello\n";
+        let document = Document::parse(source, &options).unwrap();
+        let front_matter = document.front_matter.unwrap();
+        assert_eq!(
+            front_matter.raw,
+            "title: \"Hello: world\"\ndate: 2024-01-02\n"
+        );
+        assert_eq!(front_matter.get("title"), Some("Hello: world"));
+        assert_eq!(front_matter.get("date"), Some("2024-01-02"));
+        assert_eq!(
+            document.blocks,
+            vec![Markdown::Heading(
+                1,
+                vec![MarkdownInLine::Plain(String::from("Hello"))]
+            )]
+        );
+
+        // nested YAML is kept raw only
+        let nested = FrontMatter::parse(FrontMatterFormat::Yaml, "tags:\n  - a\n");
+        assert_eq!(nested.fields, None);
+        assert_eq!(
+            split_front_matter("\u{FEFF}---\r\n...\r\nrest"),
+            (Some((FrontMatterFormat::Yaml, "")), "rest")
+        );
+        // without a closing line or not on the first line it is no front matter
+        assert_eq!(split_front_matter("---\na: b\n"), (None, "---\na: b\n"));
+        assert_eq!(
+            Document::parse("a\n---\n", &options).unwrap().front_matter,
+            None
+        );
+        assert_eq!(
+            Document::parse_bytes(
+                b"---\na: b\n---\n\xFF",
+                &ParserOptions {
+                    invalid_utf8: crate::parser::options::InvalidUtf8::Error,
+                    ..ParserOptions::default()
+                }
+            ),
+            Err(ParseError::InvalidUtf8(13))
+        );
+    }
+
+    #[test]
+    fn test_toml_front_matter() {
+        let document = Document::parse(
+            "+++\ntitle = \"Hugo\"\ndraft = false\n+++\n---\n",
+            &ParserOptions::default(),
+        )
+        .unwrap();
+        let front_matter = document.front_matter.unwrap();
+        assert_eq!(front_matter.format, FrontMatterFormat::Toml);
+        assert_eq!(front_matter.get("title"), Some("Hugo"));
+        assert_eq!(front_matter.get("draft"), Some("false"));
+        assert_eq!(document.blocks, vec![Markdown::Rule]);
+
+        let table = FrontMatter::parse(FrontMatterFormat::Toml, "[extra]\nx = 1\n");
+        assert_eq!(table.fields, None);
+        // --- doesn't close +++
+        assert_eq!(split_front_matter("+++\na = 1\n---\n").0, None);
+    }
+
+    #[test]
+    fn test_multimarkdown_metadata() {
+        let options = ParserOptions {
+            metadata_block: true,
+            ..ParserOptions::default()
+        };
+        let source = "Title: My Doc\nBase Header Level: 2\nAuthor: Ann\n    Bob\n\n# Hello\n";
+        let document = Document::parse(source, &options).unwrap();
+        let front_matter = document.front_matter.unwrap();
+        assert_eq!(front_matter.format, FrontMatterFormat::MultiMarkdown);
+        assert_eq!(
+            front_matter.raw,
+            "Title: My Doc\nBase Header Level: 2\nAuthor: Ann\n    Bob\n"
+        );
+        assert_eq!(front_matter.get("title"), Some("My Doc"));
+        assert_eq!(front_matter.get("baseheaderlevel"), Some("2"));
+        assert_eq!(front_matter.get("author"), Some("Ann\nBob"));
+        assert_eq!(
+            document.blocks,
+            vec![Markdown::Heading(
+                1,
+                vec![MarkdownInLine::Plain(String::from("Hello"))]
+            )]
+        );
+
+        // only when asked for, and only if every line of the first block is metadata
+        assert_eq!(
+            Document::parse(source, &ParserOptions::default())
+                .unwrap()
+                .front_matter,
+            None
+        );
+        assert_eq!(
+            split_front_matter_with("Note: hi\n# no\n", &options).0,
+            None
+        );
+        assert_eq!(split_front_matter_with("\nTitle: a\n", &options).0, None);
+        assert_eq!(
+            split_front_matter_with("---\na: b\n---\nc: d\n", &options),
+            (Some((FrontMatterFormat::Yaml, "a: b\n")), "c: d\n")
+        );
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Hello, World!"), "hello-world");
+        assert_eq!(slugify("  Über  uns "), "über-uns");
+        assert_eq!(slugify("--"), "");
+    }
+}
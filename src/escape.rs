@@ -0,0 +1,111 @@
+use std::fmt;
+
+// the markdown flavour escaped text goes into
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dialect {
+    CommonMark,
+    // Telegram's MarkdownV2, which rejects messages with any of its characters unescaped
+    Telegram,
+    Discord,
+}
+
+impl Dialect {
+    fn special(self) -> &'static str {
+        match self {
+            Dialect::CommonMark => "\\`*_{}[]()<>#+-.!|~$=^:&\"",
+            Dialect::Telegram => "\\_*[]()~`>#+-=|{}.!",
+            Dialect::Discord => "\\*_~`|>#-[]()",
+        }
+    }
+}
+
+// `text` with backslashes in front of every character that could mean something in
+// `dialect`, so it shows up literally wherever it is put in a markdown document
+pub fn escape_markdown(text: &str, dialect: Dialect) -> String {
+    let special = dialect.special();
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if special.contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// Displays a value escaped with escape_markdown, see markdown_format!
+pub struct Escaped<T>(pub T, pub Dialect);
+
+impl<T: fmt::Display> fmt::Display for Escaped<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&escape_markdown(&self.0.to_string(), self.1))
+    }
+}
+
+// format! for markdown templates: the template is used as it is, every argument is
+// escaped for the dialect first.
+//     markdown_format!(Dialect::Telegram, "*{}* joined", name)
+#[macro_export]
+macro_rules! markdown_format {
+    ($dialect:expr, $template:literal $(, $arg:expr)* $(,)?) => {
+        format!($template $(, $crate::escape::Escaped(&$arg, $dialect))*)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::markdown::{parse, parse_markdown_text_with, Markdown, MarkdownInLine};
+    use crate::parser::options::ParserOptions;
+
+    #[test]
+    fn test_escape_markdown() {
+        assert_eq!(
+            escape_markdown("*a_b* [c](d)", Dialect::CommonMark),
+            "\\*a\\_b\\* \\[c\\]\\(d\\)"
+        );
+        assert_eq!(
+            escape_markdown("1 + 1 = 2.", Dialect::Telegram),
+            "1 \\+ 1 \\= 2\\."
+        );
+        assert_eq!(
+            escape_markdown("||spoiler|| ok.", Dialect::Discord),
+            "\\|\\|spoiler\\|\\| ok."
+        );
+        assert_eq!(
+            markdown_format!(Dialect::Telegram, "*{}* has {} points", "a_b", 1.5),
+            "*a\\_b* has 1\\.5 points"
+        );
+    }
+
+    #[test]
+    fn test_escaped_text_parses_back() {
+        let text = "*a_b* [c](d) `e` ~~f~~ C:\\dir <g> #1 $x$ ==h== ~s~ x^2^ :tada: [[w|v]] ^[n] &amp; \"q\" https://a.b www.c.d";
+        let escaped = format!("{}\n", escape_markdown(text, Dialect::CommonMark));
+        let everything = ParserOptions {
+            inline_code_language: true,
+            autolinks: true,
+            emoji: true,
+            highlight: true,
+            sub_superscript: true,
+            wiki_links: true,
+            ..ParserOptions::default()
+        };
+        for options in [ParserOptions::default(), everything] {
+            assert_eq!(
+                parse_markdown_text_with(&escaped, &options),
+                Ok(("", vec![MarkdownInLine::Plain(String::from(text))]))
+            );
+        }
+        // a setext underline
+        let escaped = format!("a\n{}\n", escape_markdown("===", Dialect::CommonMark));
+        assert_eq!(
+            parse(&escaped, &ParserOptions::default()),
+            Ok(vec![Markdown::Paragraph(vec![
+                MarkdownInLine::Plain(String::from("a")),
+                MarkdownInLine::SoftBreak,
+                MarkdownInLine::Plain(String::from("===")),
+            ])])
+        );
+    }
+}
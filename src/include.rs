@@ -0,0 +1,380 @@
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::document::Document;
+use crate::parser::markdown::{Markdown, ParseError};
+use crate::parser::options::ParserOptions;
+
+#[derive(Debug)]
+pub enum IncludeError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, ParseError),
+    // files including each other, the first one again at the end
+    Cycle(Vec<PathBuf>),
+    // a file outside the root given to resolve_includes_within
+    OutsideRoot(PathBuf),
+}
+
+impl fmt::Display for IncludeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IncludeError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            IncludeError::Parse(path, e) => write!(f, "{}: {}", path.display(), e),
+            IncludeError::Cycle(paths) => {
+                let paths: Vec<_> = paths
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect();
+                write!(f, "include cycle: {}", paths.join(" -> "))
+            }
+            IncludeError::OutsideRoot(path) => {
+                write!(f, "{}: outside the include root", path.display())
+            }
+        }
+    }
+}
+
+impl error::Error for IncludeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            IncludeError::Io(_, e) => Some(e),
+            IncludeError::Parse(_, e) => Some(e),
+            IncludeError::Cycle(_) | IncludeError::OutsideRoot(_) => None,
+        }
+    }
+}
+
+// what resolving the includes of one document has used up so far
+struct Resolver<'a> {
+    // the files being included, outermost first, as (path, canonical path)
+    stack: Vec<(PathBuf, PathBuf)>,
+    // canonical, every included file has to be below it
+    root: Option<PathBuf>,
+    // the bytes of every included file and the nodes of the whole document, which
+    // options.limits limit like those of a single document
+    bytes: usize,
+    nodes: usize,
+    options: &'a ParserOptions,
+}
+
+impl Document {
+    // Replaces every Include block with the blocks of the file it names, relative to the
+    // file that includes it. `path` is where this document was read from. Included files
+    // are parsed with `options` and their includes resolved too, their front matter is
+    // dropped. `options.limits` apply to all of them together, a file included twice
+    // counts twice.
+    // Any file the process can read may be included, resolve the includes of documents
+    // that are not trusted with resolve_includes_within.
+    pub fn resolve_includes(
+        &mut self,
+        path: &Path,
+        options: &ParserOptions,
+    ) -> Result<(), IncludeError> {
+        resolve(self, path, None, options)
+    }
+
+    // Same as resolve_includes, but a file outside `root`, after following symlinks and
+    // `..`, is an IncludeError::OutsideRoot
+    pub fn resolve_includes_within(
+        &mut self,
+        path: &Path,
+        root: &Path,
+        options: &ParserOptions,
+    ) -> Result<(), IncludeError> {
+        let root = fs::canonicalize(root).map_err(|e| IncludeError::Io(root.to_path_buf(), e))?;
+        resolve(self, path, Some(root), options)
+    }
+}
+
+fn resolve(
+    document: &mut Document,
+    path: &Path,
+    root: Option<PathBuf>,
+    options: &ParserOptions,
+) -> Result<(), IncludeError> {
+    #[cfg(feature = "tracing")]
+    let span = tracing::debug_span!(
+        "resolve_includes",
+        path = %path.display(),
+        bytes = tracing::field::Empty,
+        nodes = tracing::field::Empty,
+    );
+    #[cfg(feature = "tracing")]
+    let _enter = span.enter();
+
+    let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut resolver = Resolver {
+        stack: vec![(path.to_path_buf(), canonical)],
+        root,
+        bytes: 0,
+        nodes: document.blocks.iter().map(Markdown::node_count).sum(),
+        options,
+    };
+    let result = resolve_blocks(&mut document.blocks, &mut resolver);
+
+    #[cfg(feature = "tracing")]
+    match &result {
+        Ok(()) => {
+            span.record("bytes", resolver.bytes);
+            span.record("nodes", resolver.nodes);
+        }
+        Err(error) => tracing::debug!(%error, "resolving includes failed"),
+    }
+
+    result
+}
+
+fn resolve_blocks(blocks: &mut Vec<Markdown>, resolver: &mut Resolver) -> Result<(), IncludeError> {
+    let mut n = 0;
+    while n < blocks.len() {
+        let target = match &mut blocks[n] {
+            Markdown::Include(target) => target.clone(),
+            block => {
+                for children in children_mut(block) {
+                    resolve_blocks(children, resolver)?;
+                }
+                n += 1;
+                continue;
+            }
+        };
+        let stack = &resolver.stack;
+        let dir = stack
+            .last()
+            .and_then(|(path, _)| path.parent())
+            .unwrap_or(Path::new(""));
+        let included = load(&dir.join(target), resolver)?;
+        let len = included.len();
+        blocks.splice(n..n + 1, included);
+        n += len;
+    }
+    Ok(())
+}
+
+// the blocks of the file at `path` with its includes resolved
+fn load(path: &Path, resolver: &mut Resolver) -> Result<Vec<Markdown>, IncludeError> {
+    #[cfg(feature = "tracing")]
+    let _enter = tracing::debug_span!("include", path = %path.display()).entered();
+
+    let io_error = |e| IncludeError::Io(path.to_path_buf(), e);
+    let parse_error = |e| IncludeError::Parse(path.to_path_buf(), e);
+    let canonical = fs::canonicalize(path).map_err(io_error)?;
+    if let Some(n) = resolver
+        .stack
+        .iter()
+        .position(|(_, outer)| *outer == canonical)
+    {
+        let mut cycle: Vec<_> = resolver.stack[n..]
+            .iter()
+            .map(|(path, _)| path.clone())
+            .collect();
+        cycle.push(path.to_path_buf());
+        return Err(IncludeError::Cycle(cycle));
+    }
+    if resolver
+        .root
+        .as_ref()
+        .is_some_and(|root| !canonical.starts_with(root))
+    {
+        return Err(IncludeError::OutsideRoot(path.to_path_buf()));
+    }
+    let limits = &resolver.options.limits;
+    if let Some(limit) = limits.max_depth {
+        if resolver.stack.len() >= limit {
+            return Err(parse_error(ParseError::TooDeep(limit)));
+        }
+    }
+
+    let bytes = fs::read(path).map_err(io_error)?;
+    resolver.bytes += bytes.len();
+    if let Some(limit) = limits.max_input_size {
+        if resolver.bytes > limit {
+            return Err(parse_error(ParseError::InputTooLarge(
+                resolver.bytes,
+                limit,
+            )));
+        }
+    }
+    let mut document = Document::parse_bytes(&bytes, resolver.options).map_err(parse_error)?;
+    resolver.nodes += document
+        .blocks
+        .iter()
+        .map(Markdown::node_count)
+        .sum::<usize>();
+    if let Some(limit) = limits.max_nodes {
+        if resolver.nodes > limit {
+            return Err(parse_error(ParseError::TooManyNodes(limit)));
+        }
+    }
+
+    resolver.stack.push((path.to_path_buf(), canonical));
+    let resolved = resolve_blocks(&mut document.blocks, resolver);
+    resolver.stack.pop();
+    resolved.map(|_| document.blocks)
+}
+
+// the block lists directly inside `block`
+fn children_mut(block: &mut Markdown) -> Vec<&mut Vec<Markdown>> {
+    match block {
+        Markdown::OrderedList(items, _) | Markdown::UnorderedList(items, _) => {
+            items.iter_mut().map(|item| &mut item.blocks).collect()
+        }
+        Markdown::Quote { body: children, .. }
+        | Markdown::FootnoteDefinition(_, children)
+        | Markdown::Container(_, _, children)
+        | Markdown::Admonition { body: children, .. }
+        | Markdown::Directive {
+            body: Some(children),
+            ..
+        } => vec![children],
+        Markdown::BlockTable(table) => table.cells_mut().collect(),
+        Markdown::Heading(_, _)
+        | Markdown::Paragraph(_)
+        | Markdown::Figure { .. }
+        | Markdown::CodeBlock(_, _)
+        | Markdown::Rule
+        | Markdown::TocMarker
+        | Markdown::Include(_)
+        | Markdown::Html(_)
+        | Markdown::Comment(_)
+        | Markdown::MathBlock(_)
+        | Markdown::Table(_)
+        | Markdown::DefinitionList(_)
+        | Markdown::Directive { body: None, .. } => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::markdown::MarkdownInLine;
+    use crate::parser::options::Limits;
+
+    #[test]
+    fn test_resolve_includes() {
+        let root = std::env::temp_dir().join(format!("doubledown-include-{}", std::process::id()));
+        let write = |path: &str, text: &str| {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        };
+        write(
+            "book.md",
+            "# Book\n!include(parts/one.md)\n> {{#include parts/two.md}}\n",
+        );
+        write(
+            "parts/one.md",
+            "---\ntitle: one\n---\none\n{{#include two.md}}\n",
+        );
+        write("parts/two.md", "two\n");
+        write("loop.md", "!include(again.md)\n");
+        write("again.md", "!include(loop.md)\n");
+
+        let options = ParserOptions {
+            includes: true,
+            ..ParserOptions::default()
+        };
+        let load = |path: &str| {
+            let path = root.join(path);
+            let mut document =
+                Document::parse(&fs::read_to_string(&path).unwrap(), &options).unwrap();
+            document
+                .resolve_includes(&path, &options)
+                .map(|_| document.blocks)
+        };
+        let book = load("book.md");
+        let cycle = load("loop.md");
+        let missing = load("parts/two.md").and_then(|_| {
+            let mut document = Document::parse("!include(gone.md)\n", &options).unwrap();
+            document.resolve_includes(&root.join("book.md"), &options)
+        });
+        fs::remove_dir_all(&root).unwrap();
+
+        let text = |s: &str| Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from(s))]);
+        assert_eq!(
+            book.unwrap(),
+            vec![
+                Markdown::Heading(1, vec![MarkdownInLine::Plain(String::from("Book"))]),
+                text("one"),
+                text("two"),
+                Markdown::Quote {
+                    body: vec![text("two")],
+                    cite: None
+                },
+            ]
+        );
+        match cycle {
+            Err(IncludeError::Cycle(paths)) => assert_eq!(
+                paths,
+                vec![
+                    root.join("loop.md"),
+                    root.join("again.md"),
+                    root.join("loop.md")
+                ]
+            ),
+            other => panic!("expected a cycle, got {:?}", other),
+        }
+        assert!(matches!(missing, Err(IncludeError::Io(path, _)) if path == root.join("gone.md")));
+    }
+
+    #[test]
+    fn test_include_limits() {
+        let root =
+            std::env::temp_dir().join(format!("doubledown-include-limits-{}", std::process::id()));
+        let write = |path: &str, text: &str| {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        };
+        // every file includes the next one twice, 2^20 copies of the last one
+        for n in 0..20 {
+            write(
+                &format!("docs/{}.md", n),
+                &format!("!include({0}.md)\n!include({0}.md)\n", n + 1),
+            );
+        }
+        write("docs/20.md", "text\n");
+        write("docs/escape.md", "!include(../secret.md)\n");
+        write("secret.md", "secret\n");
+
+        let resolve = |path: &str, within: &str, limits: Limits| {
+            let options = ParserOptions {
+                includes: true,
+                limits,
+                ..ParserOptions::default()
+            };
+            let path = root.join(path);
+            let mut document =
+                Document::parse(&fs::read_to_string(&path).unwrap(), &options).unwrap();
+            document.resolve_includes_within(&path, &root.join(within), &options)
+        };
+        let nodes = Limits {
+            max_nodes: Some(1000),
+            ..Limits::default()
+        };
+        let bytes = Limits {
+            max_input_size: Some(1000),
+            ..Limits::default()
+        };
+        let too_many = resolve("docs/0.md", "", nodes);
+        let too_large = resolve("docs/0.md", "", bytes);
+        let escape = resolve("docs/escape.md", "docs", Limits::default());
+        let allowed = resolve("docs/escape.md", "", Limits::default());
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(matches!(
+            too_many,
+            Err(IncludeError::Parse(_, ParseError::TooManyNodes(1000)))
+        ));
+        assert!(matches!(
+            too_large,
+            Err(IncludeError::Parse(_, ParseError::InputTooLarge(_, 1000)))
+        ));
+        assert!(
+            matches!(escape, Err(IncludeError::OutsideRoot(path)) if path.ends_with("secret.md"))
+        );
+        assert!(allowed.is_ok());
+    }
+}
@@ -1 +1,10 @@
-mod parser;
\ No newline at end of file
+pub mod analysis;
+pub mod diff;
+pub mod document;
+pub mod escape;
+pub mod include;
+pub mod lint;
+pub mod parser;
+pub mod project;
+pub mod redact;
+pub mod smart;
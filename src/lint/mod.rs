@@ -0,0 +1,336 @@
+pub mod rules;
+
+use std::collections::BTreeMap;
+
+use crate::analysis::{blocks_with_spans, Span};
+use crate::parser::markdown::Markdown;
+use crate::parser::options::ParserOptions;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    // the rule doesn't run
+    Off,
+    Info,
+    Warning,
+    Error,
+}
+
+// replace the source in `span` with `text`, an empty span inserts
+#[derive(Clone, Debug, PartialEq)]
+pub struct Edit {
+    pub span: Span,
+    pub text: String,
+}
+
+// something a rule found, the linter adds which rule and how severe
+#[derive(Clone, Debug, PartialEq)]
+pub struct Finding {
+    pub span: Span,
+    pub message: String,
+    // edits that resolve the finding, applied together or not at all
+    pub fix: Option<Vec<Edit>>,
+}
+
+impl Finding {
+    pub fn new(span: Span, message: impl Into<String>) -> Self {
+        Finding {
+            span,
+            message: message.into(),
+            fix: None,
+        }
+    }
+
+    pub fn with_fix(mut self, fix: Vec<Edit>) -> Self {
+        self.fix = Some(fix);
+        self
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub rule: &'static str,
+    pub severity: Severity,
+    pub span: Span,
+    pub message: String,
+    pub fix: Option<Vec<Edit>>,
+}
+
+// what rules get to look at, the source is parsed once for all of them
+pub struct LintContext<'a> {
+    pub source: &'a str,
+    // top-level blocks, None for lines no block parser accepted
+    pub blocks: Vec<(Span, Option<Markdown>)>,
+    pub options: &'a ParserOptions,
+}
+
+impl<'a> LintContext<'a> {
+    pub fn new(source: &'a str, options: &'a ParserOptions) -> Self {
+        LintContext {
+            source,
+            blocks: blocks_with_spans(source, options),
+            options,
+        }
+    }
+
+    // every line with the offset it starts at, without its line ending
+    pub fn lines(&self) -> impl Iterator<Item = (usize, &'a str)> {
+        let source = self.source;
+        let mut start = 0;
+        std::iter::from_fn(move || {
+            if start >= source.len() {
+                return None;
+            }
+            let rest = &source[start..];
+            let len = rest.find(['\n', '\r']).unwrap_or(rest.len());
+            let ending = if rest[len..].starts_with("\r\n") {
+                2
+            } else {
+                (len < rest.len()) as usize
+            };
+            let line = (start, &rest[..len]);
+            start += len + ending;
+            Some(line)
+        })
+    }
+
+    // whether `offset` is inside a top-level fenced code block
+    pub fn in_code_block(&self, offset: usize) -> bool {
+        self.blocks.iter().any(|(span, block)| {
+            matches!(block, Some(Markdown::CodeBlock(_, _)))
+                && span.start <= offset
+                && offset < span.end
+        })
+    }
+}
+
+pub trait Rule {
+    // kebab-case, used to configure the rule
+    fn name(&self) -> &'static str;
+
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn check(&self, cx: &LintContext) -> Vec<Finding>;
+}
+
+pub struct Linter {
+    rules: Vec<Box<dyn Rule>>,
+    severities: BTreeMap<String, Severity>,
+    pub options: ParserOptions,
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        let mut linter = Linter::new();
+        for rule in rules::builtin() {
+            linter.add_rule(rule);
+        }
+        linter
+    }
+}
+
+impl Linter {
+    // a linter without any rules, Linter::default() has the built-in ones
+    pub fn new() -> Self {
+        Linter {
+            rules: Vec::new(),
+            severities: BTreeMap::new(),
+            options: ParserOptions::default(),
+        }
+    }
+
+    pub fn add_rule(&mut self, rule: Box<dyn Rule>) {
+        self.rules.push(rule);
+    }
+
+    pub fn rule_names(&self) -> Vec<&'static str> {
+        self.rules.iter().map(|rule| rule.name()).collect()
+    }
+
+    // overrides the default severity of the rule called `name`, Severity::Off disables it
+    pub fn set_severity(&mut self, name: &str, severity: Severity) {
+        self.severities.insert(name.to_string(), severity);
+    }
+
+    pub fn severity(&self, rule: &dyn Rule) -> Severity {
+        self.severities
+            .get(rule.name())
+            .copied()
+            .unwrap_or_else(|| rule.default_severity())
+    }
+
+    // diagnostics of all enabled rules, ordered by where they start
+    pub fn lint(&self, source: &str) -> Vec<Diagnostic> {
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!(
+            "lint",
+            bytes = source.len(),
+            diagnostics = tracing::field::Empty
+        );
+        #[cfg(feature = "tracing")]
+        let _enter = span.enter();
+
+        let cx = LintContext::new(source, &self.options);
+        let mut diagnostics = Vec::new();
+        for rule in &self.rules {
+            let severity = self.severity(rule.as_ref());
+            if severity == Severity::Off {
+                continue;
+            }
+            diagnostics.extend(rule.check(&cx).into_iter().map(|finding| Diagnostic {
+                rule: rule.name(),
+                severity,
+                span: finding.span,
+                message: finding.message,
+                fix: finding.fix,
+            }));
+        }
+        diagnostics.sort_by_key(|d| (d.span.start, d.span.end));
+
+        #[cfg(feature = "tracing")]
+        span.record("diagnostics", diagnostics.len());
+
+        diagnostics
+    }
+
+    // `source` with every fix of the enabled rules applied. Fixes that overlap an
+    // earlier one are left for the next round, which lints the fixed source again.
+    pub fn fix(&self, source: &str) -> String {
+        #[cfg(feature = "tracing")]
+        let _enter = tracing::debug_span!("fix", bytes = source.len()).entered();
+
+        let mut source = source.to_string();
+        for _round in 0..MAX_FIX_ROUNDS {
+            let (fixed, applied) = apply_fixes(&source, &self.lint(&source));
+            #[cfg(feature = "tracing")]
+            tracing::debug!(round = _round, applied, "applied fixes");
+            if applied == 0 {
+                break;
+            }
+            source = fixed;
+        }
+        source
+    }
+}
+
+// rounds of Linter::fix before giving up on fixes that keep producing new findings
+const MAX_FIX_ROUNDS: usize = 10;
+
+// Applies the fixes of `diagnostics` to `source`, skipping any that overlap a fix
+// already taken. Returns the new source and how many fixes were applied.
+pub fn apply_fixes(source: &str, diagnostics: &[Diagnostic]) -> (String, usize) {
+    let mut fixes: Vec<&Vec<Edit>> = diagnostics.iter().filter_map(|d| d.fix.as_ref()).collect();
+    fixes.sort_by_key(|edits| edits.iter().map(|edit| edit.span.start).min());
+
+    let mut edits: Vec<&Edit> = Vec::new();
+    let mut applied = 0;
+    for fix in fixes {
+        let overlaps = fix.iter().any(|edit| {
+            edits.iter().any(|taken| {
+                // two inserts at the same offset overlap too, their order would be arbitrary
+                (edit.span.start < taken.span.end && taken.span.start < edit.span.end)
+                    || edit.span.start == taken.span.start
+            })
+        });
+        if !overlaps {
+            edits.extend(fix);
+            applied += 1;
+        }
+    }
+
+    edits.sort_by_key(|edit| (edit.span.start, edit.span.end));
+    let mut fixed = String::with_capacity(source.len());
+    let mut last = 0;
+    for edit in edits {
+        fixed.push_str(&source[last..edit.span.start]);
+        fixed.push_str(&edit.text);
+        last = edit.span.end;
+    }
+    fixed.push_str(&source[last..]);
+    (fixed, applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lines() {
+        let options = ParserOptions::default();
+        let cx = LintContext::new("a\r\nb\rc\n\nd", &options);
+        assert_eq!(
+            cx.lines().collect::<Vec<_>>(),
+            vec![(0, "a"), (3, "b"), (5, "c"), (7, ""), (8, "d")]
+        );
+    }
+
+    #[test]
+    fn test_linter_severities() {
+        let mut linter = Linter::default();
+        let source = "# a \n\nb\tc";
+        let rules: Vec<_> = linter
+            .lint(source)
+            .iter()
+            .map(|d| (d.rule, d.severity))
+            .collect();
+        assert_eq!(
+            rules,
+            vec![
+                ("trailing-whitespace", Severity::Warning),
+                ("hard-tabs", Severity::Warning),
+                ("final-newline", Severity::Warning),
+            ]
+        );
+
+        linter.set_severity("hard-tabs", Severity::Off);
+        linter.set_severity("final-newline", Severity::Error);
+        let rules: Vec<_> = linter
+            .lint(source)
+            .iter()
+            .map(|d| (d.rule, d.severity))
+            .collect();
+        assert_eq!(
+            rules,
+            vec![
+                ("trailing-whitespace", Severity::Warning),
+                ("final-newline", Severity::Error),
+            ]
+        );
+        assert!(Linter::new().lint(source).is_empty());
+    }
+
+    #[test]
+    fn test_fix() {
+        let linter = Linter::default();
+        assert_eq!(
+            linter.fix("# a \nb\tc\t\nsee https://example.com"),
+            "# a\nb    c\nsee <https://example.com>\n"
+        );
+        for source in ["- a\t \t\n", "see https://example.com and http://a.b.\n"] {
+            assert!(linter.lint(&linter.fix(source)).is_empty(), "{:?}", source);
+        }
+
+        // the second edit overlaps the first and is skipped
+        let edit = |start, end, text: &str| Edit {
+            span: Span { start, end },
+            text: text.to_string(),
+        };
+        let diagnostic = |fix| Diagnostic {
+            rule: "test",
+            severity: Severity::Warning,
+            span: Span { start: 0, end: 0 },
+            message: String::new(),
+            fix: Some(fix),
+        };
+        let diagnostics = vec![
+            diagnostic(vec![edit(0, 2, "x")]),
+            diagnostic(vec![edit(1, 3, "y")]),
+            diagnostic(vec![edit(3, 3, "!"), edit(4, 4, "?")]),
+        ];
+        assert_eq!(
+            apply_fixes("abcd", &diagnostics),
+            (String::from("xc!d?"), 2)
+        );
+    }
+}
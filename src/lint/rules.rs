@@ -0,0 +1,491 @@
+use std::collections::BTreeMap;
+
+use super::{Edit, Finding, LintContext, Rule};
+use crate::analysis::{inlines_with_spans, Span};
+use crate::parser::markdown::{plain_text, Markdown, MarkdownInLine, MarkdownText};
+
+// all built-in rules with their default settings
+pub fn builtin() -> Vec<Box<dyn Rule>> {
+    vec![
+        Box::new(TrailingWhitespace),
+        Box::new(HardTabs),
+        Box::new(LongLines::default()),
+        Box::new(BareUrls),
+        Box::new(FinalNewline),
+        Box::new(DuplicateHeadings),
+        Box::new(LinkText::default()),
+        Box::new(HeadingIncrement),
+        Box::new(SingleH1),
+        Box::new(EmptyHeadings),
+    ]
+}
+
+// the top-level headings with their level, spans cover the line without its ending
+fn headings<'a>(cx: &'a LintContext) -> Vec<(Span, usize, &'a MarkdownText)> {
+    let mut headings = Vec::new();
+    for (span, block) in &cx.blocks {
+        if let Some(Markdown::Heading(level, text)) = block {
+            let line = cx.source[span.start..span.end].trim_end_matches(['\n', '\r']);
+            headings.push((
+                Span {
+                    start: span.start,
+                    end: span.start + line.len(),
+                },
+                *level,
+                text,
+            ));
+        }
+    }
+    headings
+}
+
+// spaces or tabs at the end of a line, except the two spaces of a line break
+pub struct TrailingWhitespace;
+
+impl Rule for TrailingWhitespace {
+    fn name(&self) -> &'static str {
+        "trailing-whitespace"
+    }
+
+    fn check(&self, cx: &LintContext) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for (start, line) in cx.lines() {
+            let content = line.trim_end_matches([' ', '\t']);
+            let trailing = &line[content.len()..];
+            if trailing.is_empty() || (trailing == "  " && !content.trim().is_empty()) {
+                continue;
+            }
+            let span = Span {
+                start: start + content.len(),
+                end: start + line.len(),
+            };
+            let fix = vec![Edit {
+                span,
+                text: String::new(),
+            }];
+            findings.push(Finding::new(span, "trailing whitespace").with_fix(fix));
+        }
+        findings
+    }
+}
+
+// tabs outside of code blocks, they are rendered with different widths everywhere.
+// The fix replaces each one with TAB_WIDTH spaces.
+pub struct HardTabs;
+
+const TAB_WIDTH: usize = 4;
+
+impl Rule for HardTabs {
+    fn name(&self) -> &'static str {
+        "hard-tabs"
+    }
+
+    fn check(&self, cx: &LintContext) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for (start, line) in cx.lines() {
+            if cx.in_code_block(start) {
+                continue;
+            }
+            let mut rest = line;
+            while let Some(tab) = rest.find('\t') {
+                let len = rest[tab..].bytes().take_while(|&b| b == b'\t').count();
+                let offset = start + line.len() - rest.len() + tab;
+                let span = Span {
+                    start: offset,
+                    end: offset + len,
+                };
+                let fix = vec![Edit {
+                    span,
+                    text: " ".repeat(TAB_WIDTH * len),
+                }];
+                findings.push(Finding::new(span, "hard tab").with_fix(fix));
+                rest = &rest[tab + len..];
+            }
+        }
+        findings
+    }
+}
+
+// Lines longer than `max` characters outside of code blocks. A line that only goes over
+// the limit with a single word, like a long url, can't be wrapped and is allowed.
+pub struct LongLines {
+    pub max: usize,
+}
+
+impl Default for LongLines {
+    fn default() -> Self {
+        LongLines { max: 80 }
+    }
+}
+
+impl Rule for LongLines {
+    fn name(&self) -> &'static str {
+        "long-lines"
+    }
+
+    fn check(&self, cx: &LintContext) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for (start, line) in cx.lines() {
+            let over = match line.char_indices().nth(self.max) {
+                Some((over, _)) => over,
+                None => continue,
+            };
+            if cx.in_code_block(start) || !line[over..].contains(char::is_whitespace) {
+                continue;
+            }
+            let message = format!(
+                "line is {} characters long, the limit is {}",
+                line.chars().count(),
+                self.max
+            );
+            findings.push(Finding::new(
+                Span {
+                    start: start + over,
+                    end: start + line.len(),
+                },
+                message,
+            ));
+        }
+        findings
+    }
+}
+
+// urls in plain text, they should be written as [text](url) or <url>
+pub struct BareUrls;
+
+impl Rule for BareUrls {
+    fn name(&self) -> &'static str {
+        "bare-urls"
+    }
+
+    fn check(&self, cx: &LintContext) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for (span, block) in &cx.blocks {
+            if matches!(block, None | Some(Markdown::CodeBlock(_, _))) {
+                continue;
+            }
+            let source = &cx.source[span.start..span.end];
+            for (span, inline) in inlines_with_spans(source, span.start, cx.options) {
+                if let MarkdownInLine::Plain(_) = inline {
+                    find_urls(&cx.source[span.start..span.end], span.start, &mut findings);
+                }
+            }
+        }
+        findings
+    }
+}
+
+fn find_urls(text: &str, offset: usize, findings: &mut Vec<Finding>) {
+    let mut rest = text;
+    while let Some(start) = ["http://", "https://"]
+        .iter()
+        .filter_map(|s| rest.find(s))
+        .min()
+    {
+        let url = &rest[start..];
+        let len = url
+            .find(|c: char| c.is_whitespace() || "<>\"'".contains(c))
+            .unwrap_or(url.len());
+        // a sentence ending right after the url
+        let url = url[..len].trim_end_matches(['.', ',', ';', ':', '!', '?', ')']);
+        let begin = offset + text.len() - rest.len() + start;
+        let span = Span {
+            start: begin,
+            end: begin + url.len(),
+        };
+        let fix = vec![Edit {
+            span,
+            text: format!("<{}>", url),
+        }];
+        findings.push(Finding::new(span, format!("bare url {}", url)).with_fix(fix));
+        rest = &rest[start + len..];
+    }
+}
+
+// the last line should end with a line ending, for tools that work line by line
+pub struct FinalNewline;
+
+impl Rule for FinalNewline {
+    fn name(&self) -> &'static str {
+        "final-newline"
+    }
+
+    fn check(&self, cx: &LintContext) -> Vec<Finding> {
+        let len = cx.source.len();
+        if cx.source.is_empty() || cx.source.ends_with(['\n', '\r']) {
+            return vec![];
+        }
+        let span = Span {
+            start: len,
+            end: len,
+        };
+        let fix = vec![Edit {
+            span,
+            text: String::from("\n"),
+        }];
+        vec![Finding::new(span, "missing newline at end of file").with_fix(fix)]
+    }
+}
+
+// Headings with the same text, which makes their anchors and the outline ambiguous.
+pub struct DuplicateHeadings;
+
+impl Rule for DuplicateHeadings {
+    fn name(&self) -> &'static str {
+        "duplicate-headings"
+    }
+
+    fn check(&self, cx: &LintContext) -> Vec<Finding> {
+        let mut seen = BTreeMap::new();
+        let mut findings = Vec::new();
+        for (span, _, text) in headings(cx) {
+            let text = plain_text(text).trim().to_string();
+            match seen.get(&text) {
+                Some(first) => findings.push(Finding::new(
+                    span,
+                    format!(
+                        "duplicate heading \"{}\", first used at byte {}",
+                        text, first
+                    ),
+                )),
+                None => {
+                    seen.insert(text, span.start);
+                }
+            }
+        }
+        findings
+    }
+}
+
+// Links whose text says nothing about where they go, like "click here" or the url itself.
+// Screen reader users often skim a page's links out of context, where these are useless.
+pub struct LinkText {
+    // compared ignoring case, surrounding whitespace and trailing punctuation
+    pub phrases: Vec<String>,
+    // also flag links whose text is a url
+    pub urls: bool,
+}
+
+impl Default for LinkText {
+    fn default() -> Self {
+        let phrases = [
+            "click here",
+            "here",
+            "link",
+            "more",
+            "read more",
+            "this",
+            "this link",
+        ];
+        LinkText {
+            phrases: phrases.iter().map(|phrase| phrase.to_string()).collect(),
+            urls: true,
+        }
+    }
+}
+
+impl LinkText {
+    fn problem(&self, text: &str) -> Option<String> {
+        let text = text.trim().trim_end_matches(['.', ',', ';', ':', '!', '?']);
+        let lower = text.to_lowercase();
+        if self
+            .phrases
+            .iter()
+            .any(|phrase| phrase.to_lowercase() == lower)
+        {
+            return Some(format!(
+                "link text \"{}\" doesn't describe its target",
+                text
+            ));
+        }
+        let is_url = ["http://", "https://", "www."]
+            .iter()
+            .any(|s| lower.starts_with(s));
+        if self.urls && is_url {
+            return Some(String::from(
+                "link text is a url, describe its target instead",
+            ));
+        }
+        None
+    }
+}
+
+impl Rule for LinkText {
+    fn name(&self) -> &'static str {
+        "link-text"
+    }
+
+    fn check(&self, cx: &LintContext) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for (span, block) in &cx.blocks {
+            if matches!(block, None | Some(Markdown::CodeBlock(_, _))) {
+                continue;
+            }
+            let source = &cx.source[span.start..span.end];
+            for (span, inline) in inlines_with_spans(source, span.start, cx.options) {
+                // an autolink like <https://x.y> shows its url on purpose
+                if cx.source[span.start..].starts_with('<') {
+                    continue;
+                }
+                if let MarkdownInLine::Link(text, _, _) = inline {
+                    if let Some(message) = self.problem(&plain_text(&text)) {
+                        findings.push(Finding::new(span, message));
+                    }
+                }
+            }
+        }
+        findings
+    }
+}
+
+// A heading more than one level below the one before it, like ## followed by ####.
+// Skipped levels break the outline assistive technology builds from the headings.
+pub struct HeadingIncrement;
+
+impl Rule for HeadingIncrement {
+    fn name(&self) -> &'static str {
+        "heading-increment"
+    }
+
+    fn check(&self, cx: &LintContext) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        let mut previous = None;
+        for (span, level, _) in headings(cx) {
+            if let Some(previous) = previous.filter(|&previous| level > previous + 1) {
+                findings.push(Finding::new(
+                    span,
+                    format!(
+                        "heading level {} follows level {}, expected at most {}",
+                        level,
+                        previous,
+                        previous + 1
+                    ),
+                ));
+            }
+            previous = Some(level);
+        }
+        findings
+    }
+}
+
+// more than one level 1 heading, the document should have a single title
+pub struct SingleH1;
+
+impl Rule for SingleH1 {
+    fn name(&self) -> &'static str {
+        "single-h1"
+    }
+
+    fn check(&self, cx: &LintContext) -> Vec<Finding> {
+        headings(cx)
+            .into_iter()
+            .filter(|&(_, level, _)| level == 1)
+            .skip(1)
+            .map(|(span, _, _)| Finding::new(span, "more than one level 1 heading"))
+            .collect()
+    }
+}
+
+// headings without any text
+pub struct EmptyHeadings;
+
+impl Rule for EmptyHeadings {
+    fn name(&self) -> &'static str {
+        "empty-headings"
+    }
+
+    fn check(&self, cx: &LintContext) -> Vec<Finding> {
+        headings(cx)
+            .into_iter()
+            .filter(|(_, _, text)| plain_text(text).trim().is_empty())
+            .map(|(span, _, _)| Finding::new(span, "empty heading"))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::options::ParserOptions;
+
+    fn check(rule: &dyn Rule, source: &str) -> Vec<(usize, usize)> {
+        let options = ParserOptions::default();
+        let cx = LintContext::new(source, &options);
+        rule.check(&cx)
+            .into_iter()
+            .map(|finding| (finding.span.start, finding.span.end))
+            .collect()
+    }
+
+    #[test]
+    fn test_whitespace_rules() {
+        assert_eq!(
+            check(&TrailingWhitespace, "a \nb  \nc\t\n  \n"),
+            vec![(1, 2), (8, 9), (10, 12)]
+        );
+        assert_eq!(
+            check(&HardTabs, "a\tb\t\tc\n```\n\tcode\n```\n"),
+            vec![(1, 2), (3, 5)]
+        );
+        assert_eq!(check(&FinalNewline, "a\nb"), vec![(3, 3)]);
+        assert!(check(&FinalNewline, "a\r\n").is_empty());
+        assert!(check(&FinalNewline, "").is_empty());
+    }
+
+    #[test]
+    fn test_long_lines() {
+        let rule = LongLines { max: 10 };
+        assert_eq!(
+            check(&rule, "short\nthis line is too long\n"),
+            vec![(16, 27)]
+        );
+        // nothing to wrap
+        assert!(check(&rule, "see https://example.com/a/long/path\n").is_empty());
+        assert!(check(&rule, "```\nthis line is too long\n```\n").is_empty());
+    }
+
+    #[test]
+    fn test_bare_urls() {
+        let source = "see https://example.com.\n[ok](https://a.b) `http://code`\n- http://x.y\n```\nhttp://z\n```\n";
+        assert_eq!(check(&BareUrls, source), vec![(4, 23), (59, 69)]);
+        assert_eq!(&source[4..23], "https://example.com");
+        assert_eq!(&source[59..69], "http://x.y");
+    }
+
+    #[test]
+    fn test_link_text() {
+        let source = "[Click here](a) [the docs](b) [this.](c)\n- [https://x.y](https://x.y)\n";
+        assert_eq!(
+            check(&LinkText::default(), source),
+            vec![(0, 15), (30, 40), (43, 69)]
+        );
+        assert!(check(&LinkText::default(), "see <https://x.y>\n").is_empty());
+
+        let rule = LinkText {
+            phrases: vec![String::from("The Docs")],
+            urls: false,
+        };
+        assert_eq!(check(&rule, source), vec![(16, 29)]);
+    }
+
+    #[test]
+    fn test_heading_structure() {
+        let source = "# Title\n### Skipped\n## Back\n# Second\n##  \n";
+        assert_eq!(check(&HeadingIncrement, source), vec![(8, 19)]);
+        assert_eq!(check(&SingleH1, source), vec![(28, 36)]);
+        assert_eq!(check(&EmptyHeadings, source), vec![(37, 41)]);
+        assert_eq!(check(&HeadingIncrement, "## a\n#### b\n"), vec![(5, 11)]);
+        assert!(check(&SingleH1, "## a\n### b\n").is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_headings() {
+        assert_eq!(
+            check(
+                &DuplicateHeadings,
+                "# Intro\n## Usage\n## **Intro**\ntext\n# Usage\n"
+            ),
+            vec![(17, 29), (35, 42)]
+        );
+    }
+}
@@ -1,752 +1,5586 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt;
+use std::path::PathBuf;
+use std::str;
+use std::time::{Duration, Instant};
+
 use nom::{
     branch::alt,
-    bytes::complete::{is_not, tag, take, take_while1},
-    character::is_digit,
-    combinator::{map, not, opt},
+    bytes::complete::{is_not, tag, take_while1, take_while_m_n},
+    character::{complete::anychar, is_digit},
+    combinator::{map, not, opt, peek, verify},
     error::ErrorKind,
-    multi::{many0, many1},
-    sequence::{delimited, pair, preceded, terminated, tuple},
-    Err::Error,
+    multi::many1,
+    sequence::{delimited, pair, preceded, terminated},
+    Err::{Error, Failure},
     IResult,
 };
 
+use super::emoji::emoji;
+use super::options::{HeadingOverflow, InvalidUtf8, ParserOptions};
+
 pub type MarkdownText = Vec<MarkdownInLine>;
+// (key, value) pairs, flags have no value
+pub type Attributes = Vec<(String, Option<String>)>;
+#[derive(Clone, Debug, PartialEq)]
+pub struct ListItem {
+    // Some for task list items, starting with [ ] or [x]
+    pub checked: Option<bool>,
+    // only with ParserOptions::tasks, for task list items
+    pub task: Option<Task>,
+    pub blocks: Vec<Markdown>,
+}
+
+impl ListItem {
+    pub fn new(blocks: Vec<Markdown>) -> Self {
+        ListItem {
+            checked: None,
+            task: None,
+            blocks,
+        }
+    }
+}
+
+// how a list was written
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ListInfo {
+    // items are separated by blank lines or contain some, CommonMark renders their
+    // content in <p> then
+    pub loose: bool,
+    // the bullet of an unordered list, or `.` or `)` after the numbers of an ordered one
+    pub marker: char,
+    // the number of the first item of an ordered list, for <ol start="3">
+    pub start: Option<u64>,
+}
+
+impl ListInfo {
+    // a tight ordered list
+    pub fn ordered(start: u64, delimiter: char) -> Self {
+        ListInfo {
+            loose: false,
+            marker: delimiter,
+            start: Some(start),
+        }
+    }
+}
+
+// a tight `-` list
+impl Default for ListInfo {
+    fn default() -> Self {
+        ListInfo {
+            loose: false,
+            marker: '-',
+            start: None,
+        }
+    }
+}
+
+// - [ ] (A) water the plants due:2024-01-01
+// The metadata is removed from the item's text.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Task {
+    // (A) to (Z), todo.txt style
+    pub priority: Option<char>,
+    // YYYY-MM-DD from `due:2024-01-01` or `📅 2024-01-01`
+    pub due: Option<String>,
+}
+
+// ```rust,ignore {linenos=true, hl_lines="2-4"}
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FenceInfo {
+    pub language: Option<String>,
+    // in order: flags after the language like `ignore` have no value, the key=value
+    // pairs in braces do. Quotes around values are removed.
+    pub attributes: Attributes,
+}
+
+impl FenceInfo {
+    // info with just a language
+    pub fn new(language: &str) -> Self {
+        FenceInfo {
+            language: Some(language.to_string()),
+            attributes: vec![],
+        }
+    }
+
+    pub fn attribute(&self, key: &str) -> Option<&str> {
+        self.attributes.iter().find(|(k, _)| k == key).and_then(|(_, v)| v.as_deref())
+    }
+
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.attributes.iter().any(|(k, _)| k == flag)
+    }
+}
+
+// how a table column is aligned, from the colons of its delimiter row
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Alignment {
+    // ---
+    #[default]
+    None,
+    // :---
+    Left,
+    // :---:
+    Center,
+    // ---:
+    Right,
+}
+
+// | a | b |
+// |---|--:|
+// | c | d |
+// Every row has as many cells as the header.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Table {
+    pub alignments: Vec<Alignment>,
+    pub header: Vec<MarkdownText>,
+    pub rows: Vec<Vec<MarkdownText>>,
+    // from a `Table: caption` or `[caption]` line right before or after the table
+    pub caption: Option<MarkdownText>,
+}
+
+impl Table {
+    // the header followed by the rows
+    pub fn cells(&self) -> impl Iterator<Item = &MarkdownText> {
+        self.header.iter().chain(self.rows.iter().flatten())
+    }
+}
+
+// A table whose cells hold blocks, from Pandoc's grid and multiline tables.
+// Every row has as many cells as there are alignments.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockTable {
+    pub alignments: Vec<Alignment>,
+    // None for a table without a header
+    pub header: Option<Vec<Vec<Markdown>>>,
+    pub rows: Vec<Vec<Vec<Markdown>>>,
+    pub caption: Option<MarkdownText>,
+}
+
+impl BlockTable {
+    // the header followed by the rows
+    pub fn cells(&self) -> impl Iterator<Item = &Vec<Markdown>> {
+        self.header.iter().flatten().chain(self.rows.iter().flatten())
+    }
 
+    pub fn cells_mut(&mut self) -> impl Iterator<Item = &mut Vec<Markdown>> {
+        self.header.iter_mut().flatten().chain(self.rows.iter_mut().flatten())
+    }
+}
 
-// TODO: Right now lists can not be nested and will be parsed as plain text in quotes
-//  Add something similar to Markdown::List(Vec<Markdown>) maybe? So maybe this can work:
-//  Md::List(vec![
-//      Md::Heading(1, vec![MIL::Plain("Food")])
-//      Md::List(vec![
-//          Md::Text(vec![MIL::Plain("text")],
-//          Md::Text(vec![MIL::Bold("bold"), MIL::Plain("text")],
-//      ]),
-//      Md::Text(vec![Mil::Plain("hope this works")])
-//  ])
-//  for:
-//  - Food
-//      - text
-//      - **bold** text
-//  - hope this works
-
-// TODO:  After that make quote nested?
 #[derive(Clone, Debug, PartialEq)]
 pub enum Markdown {
     // (num of #, text)
     Heading(usize, MarkdownText),
-    OrderedList(Vec<MarkdownText>),
-    UnorderedList(Vec<MarkdownText>),
-    Quote(Vec<MarkdownText>),
-    CodeBlock(String, Option<String>),
-    Text(MarkdownText),
+    OrderedList(Vec<ListItem>, ListInfo),
+    UnorderedList(Vec<ListItem>, ListInfo),
+    // > text
+    // > — Author
+    // a last line starting with `— ` or `-- ` is the attribution, without the dash
+    Quote {
+        body: Vec<Markdown>,
+        cite: Option<MarkdownText>,
+    },
+    CodeBlock(String, FenceInfo),
+    Table(Table),
+    BlockTable(BlockTable),
+    // ---, *** or ___
+    Rule,
+    // [TOC] or [[_TOC_]] alone on a line, where a table of contents goes
+    TocMarker,
+    // !include(path) or {{#include path}} alone on a line, only with
+    // ParserOptions::includes. Document::resolve_includes splices the file in.
+    Include(PathBuf),
+    // raw HTML, passed through or dropped by renderers
+    Html(String),
+    // the text of an <!-- --> comment on lines of its own, renderers should skip it
+    Comment(String),
+    // (term, definitions) pairs
+    DefinitionList(Vec<(MarkdownText, Vec<MarkdownText>)>),
+    // (label, content) of [^label]: content
+    FootnoteDefinition(String, Vec<Markdown>),
+    // > [!WARNING] title
+    // > body
+    // a quote starting with a GitHub or Obsidian callout marker, `kind` in uppercase
+    Admonition {
+        kind: String,
+        title: MarkdownText,
+        body: Vec<Markdown>,
+    },
+    // the TeX between $$ lines, never parsed as markdown, MarkdownInLine::Math in text
+    MathBlock(String),
+    // (name, attributes, content) of ::: name, attributes like FenceInfo::attributes
+    Container(String, Attributes, Vec<Markdown>),
+    // ::name[label]{key=value} on a line of its own, or :::name[label]{key=value} up to
+    // a ::: line, only with ParserOptions::directives. The container form has a body.
+    Directive {
+        name: String,
+        label: MarkdownText,
+        attrs: Attributes,
+        body: Option<Vec<Markdown>>,
+    },
+    // consecutive lines of text, joined by a SoftBreak or a HardBreak
+    Paragraph(MarkdownText),
+    // an image alone in a paragraph with its caption from the line below, never parsed
+    // but made from paragraphs by Document::make_figures. `image` is an Image.
+    Figure {
+        image: MarkdownInLine,
+        caption: MarkdownText,
+    },
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum MarkdownInLine {
-    // (tag, url)
-    Link(String, String),
-    // (tag, url)
-    Image(String, String),
+    // (text, url, title), text and url may be empty for [](url) or [text]()
+    Link(MarkdownText, String, Option<String>),
+    // (tag, url, title), tag and url may be empty for ![](url) or ![tag]()
+    Image(String, String, Option<String>),
     // (code, language)
     InlineCode(String, Option<String>),
-    Bold(String),
-    Italic(String),
+    Bold(MarkdownText),
+    Italic(MarkdownText),
+    // ~~text~~
+    Strikethrough(MarkdownText),
+    // ==text==, see ParserOptions::highlight
+    Highlight(MarkdownText),
+    // H~2~O and x^2^, see ParserOptions::sub_superscript
+    Subscript(MarkdownText),
+    Superscript(MarkdownText),
     Plain(String),
+    // a line break kept in the output, see ParserOptions::hard_breaks
+    HardBreak,
+    // the newline between two lines of a paragraph, a space or a line break is up to
+    // the renderer
+    SoftBreak,
+    // [^label], the label of a Markdown::FootnoteDefinition
+    FootnoteRef(String),
+    // ^[a note written where it is referenced]
+    InlineFootnote(MarkdownText),
+    // one raw tag or comment like <kbd>, </kbd> or <!-- note -->, as written
+    Html(String),
+    // the name in :name:, see ParserOptions::emoji and emoji::emoji for the character
+    Emoji(String),
+    // the TeX in $x^2$, never parsed as markdown like a Markdown::MathBlock
+    Math(String),
+    // [[target]] or [[target|alias]], see ParserOptions::wiki_links
+    WikiLink { target: String, alias: Option<String> },
+}
+
+// the variants of MarkdownInLine without their content, see MarkdownInLine::inline_kind
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InlineKind {
+    Link,
+    Image,
+    InlineCode,
+    Bold,
+    Italic,
+    Strikethrough,
+    Highlight,
+    Subscript,
+    Superscript,
+    Plain,
+    HardBreak,
+    SoftBreak,
+    FootnoteRef,
+    InlineFootnote,
+    Html,
+    Emoji,
+    Math,
+    WikiLink,
+}
+
+// text between `open` and `close`, which may be empty
+fn parse_enclosed<'a>(i: &'a str, open: &str, close: &str) -> IResult<&'a str, &'a str> {
+    delimited(tag(open), map(opt(is_not(close)), |s| s.unwrap_or("")), tag(close))(i)
 }
 
 // [text](url)
-pub fn parse_link(i: &str) -> IResult<&str, (&str, &str)> {
-    pair(
-        delimited(tag("["), is_not("]"), tag("]")),
-        delimited(tag("("), is_not(")"), tag(")")),
+// text and url may be empty: [](url), [text]()
+pub fn parse_link(i: &str) -> IResult<&str, (&str, &str, Option<&str>)> {
+    map(
+        pair(parse_link_text, parse_destination),
+        |(text, (url, title))| (text, url, title),
     )(i)
 }
 
+// The text between `open` and the `close` matching it, which may hold balanced pairs
+// of both. Escaped ones don't count.
+fn parse_balanced(i: &str, open: u8, close: u8) -> IResult<&str, &str> {
+    if i.as_bytes().first() != Some(&open) {
+        return Err(Error((i, ErrorKind::Tag)));
+    }
+    let rest = &i[1..];
+    let bytes = rest.as_bytes();
+    let (mut depth, mut n) = (0, 0);
+    while n < bytes.len() {
+        match bytes[n] {
+            b'\\' => n += 1,
+            b if b == open => depth += 1,
+            b if b == close && depth == 0 => return Ok((&rest[n + 1..], &rest[..n])),
+            b if b == close => depth -= 1,
+            _ => {}
+        }
+        n += 1;
+    }
+    Err(Error((&rest[rest.len()..], ErrorKind::Tag)))
+}
+
+// [text] where the text may hold balanced brackets, so [![alt](a.png)](b) is an image
+// linking to b
+fn parse_link_text(i: &str) -> IResult<&str, &str> {
+    parse_balanced(i, b'[', b']')
+}
+
+// [[Page Name]] or [[Page Name|alias]] as (target, alias), the target can't be empty
+pub fn parse_wiki_link(i: &str) -> IResult<&str, (&str, Option<&str>)> {
+    let (rest, inner) = delimited(tag("[["), is_not("]\r\n"), tag("]]"))(i)?;
+    let (target, alias) = match inner.split_once('|') {
+        Some((target, alias)) => (target, Some(alias)),
+        None => (inner, None),
+    };
+    if target.is_empty() {
+        return Err(Error((i, ErrorKind::Verify)));
+    }
+    Ok((rest, (target, alias)))
+}
+
+// [^label] in text
+pub fn parse_footnote_ref(i: &str) -> IResult<&str, &str> {
+    delimited(tag("[^"), is_not(" \t\r\n]"), tag("]"))(i)
+}
+
+// ^[note], the note may hold balanced brackets
+pub fn parse_inline_footnote(i: &str) -> IResult<&str, &str> {
+    preceded(tag("^"), parse_link_text)(i)
+}
+
 // ![text](url / path)
-fn parse_image(i: &str) -> IResult<&str, (&str, &str)> {
-    pair(
-        delimited(tag("!["), is_not("]"), tag("]")),
-        delimited(tag("("), is_not(")"), tag(")")),
+// alt text and path may be empty: ![](image.png)
+fn parse_image(i: &str) -> IResult<&str, (&str, &str, Option<&str>)> {
+    map(
+        pair(|i| parse_enclosed(i, "![", "]"), parse_destination),
+        |(text, (url, title))| (text, url, title),
     )(i)
 }
 
+// (url) or (url "title"), the title may also be in single quotes or parentheses and
+// hold escaped ones. Parentheses in the url have to be balanced, like in
+// https://en.wikipedia.org/wiki/Rust_(programming_language). A url in angle brackets may
+// hold spaces: (<my file.md>). Anything else after the url isn't a link.
+fn parse_destination(i: &str) -> IResult<&str, (&str, Option<&str>)> {
+    let (rest, inner) = parse_balanced(i, b'(', b')')?;
+    let inner = inner.trim_matches([' ', '\t']);
+    let (url, after) = match inner.strip_prefix('<') {
+        Some(bracketed) => match bracketed.find('>') {
+            Some(end) => (&bracketed[..end], &bracketed[end + 1..]),
+            None => return Err(Error((i, ErrorKind::Verify))),
+        },
+        None => inner.split_at(inner.find([' ', '\t']).unwrap_or(inner.len())),
+    };
+    let after = after.trim_start_matches([' ', '\t']);
+    if after.is_empty() {
+        return Ok((rest, (url, None)));
+    }
+    let close = match after.as_bytes()[0] {
+        b'"' => '"',
+        b'\'' => '\'',
+        b'(' => ')',
+        _ => return Err(Error((i, ErrorKind::Verify))),
+    };
+    let body = &after[1..];
+    let mut chars = body.char_indices();
+    while let Some((n, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            c if c == close && n + 1 == body.len() => return Ok((rest, (url, Some(&body[..n])))),
+            c if c == close => break,
+            _ => {}
+        }
+    }
+    Err(Error((i, ErrorKind::Verify)))
+}
+
+// A run of backticks and the code up to the next run just as long, so ``a ` b`` can
+// hold a backtick. One space is stripped from both ends if both have one: `` `a` ``.
+fn parse_code_span(i: &str) -> IResult<&str, &str> {
+    let run = i.bytes().take_while(|&b| b == b'`').count();
+    if run == 0 {
+        return Err(Error((i, ErrorKind::Tag)));
+    }
+    let rest = &i[run..];
+    if rest.is_empty() {
+        return Err(Error((rest, ErrorKind::IsNot)));
+    }
+    let bytes = rest.as_bytes();
+    let mut n = 0;
+    while n < bytes.len() {
+        let len = bytes[n..].iter().take_while(|&&b| b == b'`').count();
+        if len == run {
+            let code = &rest[..n];
+            let padded = code.starts_with(' ') && code.ends_with(' ');
+            let code = if padded && !code.trim_matches(' ').is_empty() {
+                &code[1..code.len() - 1]
+            } else {
+                code
+            };
+            return Ok((&rest[n + run..], code));
+        }
+        n += len.max(1);
+    }
+    Err(Error((&rest[rest.len()..], ErrorKind::Tag)))
+}
+
+// `code`
+pub fn parse_inline_code(i: &str) -> IResult<&str, &str> {
+    parse_code_span(i)
+}
+
 // `code`language  (whitespace is the separator for the next)
+// only used with ParserOptions::inline_code_language
 pub fn parse_inline(i: &str) -> IResult<&str, (&str, Option<&str>)> {
     pair(
-        delimited(tag("`"), is_not("`"), tag("`")), // code
+        parse_code_span, // code
         opt(delimited(tag(""), is_not(" \t\r\n"), not(is_not(" \t\r\n")))),                      // language
     )(i)
 }
 
+// ASCII punctuation plus the Unicode punctuation blocks, including full-width forms
+fn is_punctuation(c: char) -> bool {
+    c.is_ascii_punctuation()
+        || matches!(c,
+            '\u{00A1}'..='\u{00BF}'
+            | '\u{2010}'..='\u{2027}'
+            | '\u{2030}'..='\u{205E}'
+            | '\u{2E00}'..='\u{2E7F}'
+            | '\u{3001}'..='\u{3003}'
+            | '\u{3008}'..='\u{3011}'
+            | '\u{3014}'..='\u{301F}'
+            | '\u{FE10}'..='\u{FE19}'
+            | '\u{FE30}'..='\u{FE6B}'
+            | '\u{FF01}'..='\u{FF0F}'
+            | '\u{FF1A}'..='\u{FF20}'
+            | '\u{FF3B}'..='\u{FF40}'
+            | '\u{FF5B}'..='\u{FF65}')
+}
+
+// Han, kana, hangul and full-width forms. These scripts don't put spaces
+// between words, so they count as a word boundary next to emphasis delimiters.
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{1100}'..='\u{11FF}'
+        | '\u{2E80}'..='\u{A4CF}'
+        | '\u{A960}'..='\u{A97F}'
+        | '\u{AC00}'..='\u{D7FF}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{FE30}'..='\u{FE4F}'
+        | '\u{FF00}'..='\u{FFEF}'
+        | '\u{20000}'..='\u{3FFFF}')
+}
+
+// Whether a delimiter run between `before` and `after` can close emphasis: it must not
+// follow whitespace, and if it follows punctuation something other than a letter has
+// to come next, so `*(a)*b` is literal while `*a*b` and `**テスト。**です` are not.
+fn is_right_flanking(before: Option<char>, after: Option<char>) -> bool {
+    let boundary = |c: char| c.is_whitespace() || is_punctuation(c) || is_cjk(c);
+    match (before, after) {
+        (None, _) => false,
+        (Some(c), _) if c.is_whitespace() => false,
+        (Some(c), _) if !is_punctuation(c) || is_cjk(c) => true,
+        (Some(_), None) => true,
+        (Some(_), Some(c)) => boundary(c),
+    }
+}
+
+// Byte offset of the first run of `mark` on this line that is between `min` and `max`
+// long and right flanking, so it can close an emphasis span. A `_` run also has to end
+// the word, snake_case names stay text.
+// Runs with another length are skipped as a whole and stay part of the content, so is
+// inline math, *$a*b$* is italic math.
+fn find_closing_run(i: &str, mark: u8, min: usize, max: usize) -> Option<usize> {
+    let bytes = i.as_bytes();
+    let mut n = 0;
+    while n < bytes.len() {
+        match bytes[n] {
+            b'\n' | b'\r' => return None,
+            b'$' => match parse_inline_math(&i[n..]) {
+                Ok((rest, _)) => n = i.len() - rest.len(),
+                Err(_) => n += 1,
+            },
+            b if b == mark => {
+                let run = bytes[n..].iter().take_while(|&&b| b == mark).count();
+                let after = i[n + run..].chars().next();
+                let flanking = is_right_flanking(i[..n].chars().next_back(), after);
+                let intraword = mark == b'_' && after.is_some_and(char::is_alphanumeric);
+                if flanking && !intraword && run >= min && run <= max {
+                    return Some(n);
+                }
+                n += run;
+            }
+            _ => n += 1,
+        }
+    }
+    None
+}
+
+// `delimiter` followed by content that may not start with the delimiter character or
+// whitespace, closed by the first fitting run found by find_closing_run
+fn parse_emphasis<'a>(i: &'a str, delimiter: &str, max: usize) -> IResult<&'a str, &'a str> {
+    let mark = delimiter.as_bytes()[0];
+    let (rest, _) = tag(delimiter)(i)?;
+    if rest.is_empty() || rest.starts_with(|c: char| c == mark as char || c.is_whitespace()) {
+        return Err(Error((rest, ErrorKind::IsNot)));
+    }
+    match find_closing_run(rest, mark, delimiter.len(), max) {
+        Some(end) => Ok((&rest[end + delimiter.len()..], &rest[..end])),
+        None => Err(Error((&rest[rest.len()..], ErrorKind::Tag))),
+    }
+}
+
 // **text**
+// single `*` are allowed inside, a longer closing run like `**text***`
+// only uses the first two and leaves the rest as text
 pub fn parse_bold(i: &str) -> IResult<&str, &str> {
-    delimited(tag("**"), is_not("**"), tag("**"))(i)
+    parse_emphasis(i, "**", usize::MAX)
 }
 
 // *text*
 pub fn parse_italic(i: &str) -> IResult<&str, &str> {
-    delimited(tag("*"), is_not("*"), tag("*"))(i)
+    parse_emphasis(i, "*", 1)
+}
+
+// ***text*** is italic around bold like in CommonMark. ***a** b* and ***a* b** open
+// both spans at once too, the inner one closes first.
+fn parse_strong_emphasis<'a>(
+    i: &'a str,
+    delimiter: &str,
+    options: &ParserOptions,
+    depth: Option<usize>,
+) -> IResult<&'a str, MarkdownInLine> {
+    // the depth left for what is in the span `levels` below this one
+    let inner = |levels| depth.map(|depth: usize| depth.saturating_sub(levels));
+    if let Ok((rest, content)) = parse_emphasis(i, delimiter, usize::MAX) {
+        let bold = MarkdownInLine::Bold(parse_span(content, options, inner(2))?);
+        return Ok((rest, MarkdownInLine::Italic(vec![bold])));
+    }
+    if !i.starts_with(delimiter) {
+        return Err(Error((i, ErrorKind::Tag)));
+    }
+    let mark = delimiter.as_bytes()[0];
+    for outer in [1, 2] {
+        let rest = &i[outer..];
+        if let Some(end) = find_closing_run(rest, mark, outer, outer) {
+            let text = parse_span(&rest[..end], options, inner(1))?;
+            match (outer, text.first()) {
+                (1, Some(MarkdownInLine::Bold(_))) => {
+                    return Ok((&rest[end + outer..], MarkdownInLine::Italic(text)))
+                }
+                (2, Some(MarkdownInLine::Italic(_))) => {
+                    return Ok((&rest[end + outer..], MarkdownInLine::Bold(text)))
+                }
+                _ => {}
+            }
+        }
+    }
+    Err(Error((i, ErrorKind::Tag)))
+}
+
+// $x^2$, the TeX is kept as written. Like in pandoc the opening $ can't come before a
+// space and the closing one can't follow a space or come before a digit, so prices
+// like $5 and $10 stay text. An escaped \$ or $$ doesn't close the math.
+pub fn parse_inline_math(i: &str) -> IResult<&str, &str> {
+    let (rest, _) = tag("$")(i)?;
+    if rest.is_empty() || rest.starts_with(|c: char| c == '$' || c.is_whitespace()) {
+        return Err(Error((rest, ErrorKind::IsNot)));
+    }
+    let bytes = rest.as_bytes();
+    let mut n = 0;
+    while n < bytes.len() {
+        match bytes[n] {
+            b'\n' | b'\r' => break,
+            b'\\' => n += 2,
+            b'$' => {
+                let run = bytes[n..].iter().take_while(|&&b| b == b'$').count();
+                let digit = bytes.get(n + 1).is_some_and(u8::is_ascii_digit);
+                if run == 1 && !bytes[n - 1].is_ascii_whitespace() && !digit {
+                    return Ok((&rest[n + 1..], &rest[..n]));
+                }
+                n += run;
+            }
+            _ => n += 1,
+        }
+    }
+    Err(Error((&rest[rest.len()..], ErrorKind::Tag)))
+}
+
+// ~~text~~
+pub fn parse_strikethrough(i: &str) -> IResult<&str, &str> {
+    parse_emphasis(i, "~~", 2)
+}
+
+// ==text==
+pub fn parse_highlight(i: &str) -> IResult<&str, &str> {
+    parse_emphasis(i, "==", 2)
+}
+
+// ~text~ or ^text^ for `mark` like in pandoc, the text can't hold spaces so ~/a and ~/b
+// stay apart. ~~ opens strikethrough instead.
+fn parse_script(i: &str, mark: char) -> IResult<&str, &str> {
+    let rest = match i.strip_prefix(mark) {
+        Some(rest) => rest,
+        None => return Err(Error((i, ErrorKind::Tag))),
+    };
+    let len = rest.find(|c: char| c == mark || c.is_whitespace()).unwrap_or(rest.len());
+    match rest[len..].strip_prefix(mark) {
+        Some(after) if len > 0 => Ok((after, &rest[..len])),
+        _ => Err(Error((&rest[len..], ErrorKind::Tag))),
+    }
+}
+
+// H~2~O
+pub fn parse_subscript(i: &str) -> IResult<&str, &str> {
+    parse_script(i, '~')
+}
+
+// x^2^
+pub fn parse_superscript(i: &str) -> IResult<&str, &str> {
+    parse_script(i, '^')
+}
+
+// length of the leading run of text that can not start any other inline element
+fn plain_len(i: &str, options: &ParserOptions) -> usize {
+    let bytes = i.as_bytes();
+    bytes
+        .iter()
+        .enumerate()
+        .find(|&(n, &b)| match b {
+            b'*' | b'~' | b'`' | b'[' | b'<' | b'$' | b'\n' | b'\r' => true,
+            b'!' => bytes.get(n + 1) == Some(&b'['),
+            b'^' => options.sub_superscript || bytes.get(n + 1) == Some(&b'['),
+            b'\\' => bytes.get(n + 1).is_some_and(u8::is_ascii_punctuation),
+            b'=' if options.highlight => bytes.get(n + 1) == Some(&b'='),
+            // inside a word `_` can't open emphasis
+            b'_' => !i[..n].chars().next_back().is_some_and(char::is_alphanumeric),
+            // neither can a `:` in 10:30
+            b':' if options.emoji => !i[..n].chars().next_back().is_some_and(char::is_alphanumeric),
+            _ => false,
+        })
+        .map_or(bytes.len(), |(n, _)| n)
+}
+
+// scheme:rest, the scheme 2 to 32 letters, digits, `+`, `.` or `-` starting with a letter
+fn is_absolute_uri(s: &str) -> bool {
+    let scheme = s.split(':').next().unwrap_or("");
+    scheme.len() < s.len()
+        && (2..=32).contains(&scheme.len())
+        && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+.-".contains(c))
+}
+
+fn is_email(s: &str) -> bool {
+    let label = |label: &str| {
+        (1..=63).contains(&label.len())
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+    };
+    match s.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && local.chars().all(|c| c.is_ascii_alphanumeric() || ".!#$%&'*+/=?^_`{|}~-".contains(c))
+                && domain.split('.').all(label)
+        }
+        None => false,
+    }
+}
+
+// <https://example.com> or <user@example.com> as (text, destination), an email
+// address gets a mailto: destination
+pub fn parse_autolink(i: &str) -> IResult<&str, (&str, String)> {
+    let (rest, target) = delimited(tag("<"), is_not("<> \t\r\n"), tag(">"))(i)?;
+    if is_absolute_uri(target) {
+        Ok((rest, (target, target.to_string())))
+    } else if is_email(target) {
+        Ok((rest, (target, format!("mailto:{}", target))))
+    } else {
+        Err(Error((i, ErrorKind::Verify)))
+    }
+}
+
+// Length of the url `i` starts with, see ParserOptions::autolinks. It goes up to the
+// next whitespace or `<`, without trailing punctuation or a `)` that closes nothing
+// in the url, so "(see https://a.b/c_(d))." ends before the last `)`.
+fn bare_url_len(i: &str) -> Option<usize> {
+    let prefix = ["https://", "http://", "www."].iter().find(|prefix| i.starts_with(*prefix))?;
+    let mut url = &i[..i.find(|c: char| c.is_whitespace() || c == '<').unwrap_or(i.len())];
+    loop {
+        let unbalanced = url.ends_with(')') && url.matches(')').count() > url.matches('(').count();
+        match url.strip_suffix(|c| "?!.,:;*_~'\"".contains(c)) {
+            Some(shorter) => url = shorter,
+            None if unbalanced => url = &url[..url.len() - 1],
+            None => break,
+        }
+    }
+    let domain = url.get(prefix.len()..)?.split(['/', '?', '#']).next().unwrap_or("");
+    let host = |c: char| c.is_alphanumeric() || "-._".contains(c);
+    (!domain.is_empty() && domain.chars().all(host)).then_some(url.len())
+}
+
+// plain text with every url in it turned into a Link, see ParserOptions::autolinks
+fn parse_plain_with_urls<'a>(
+    i: &'a str,
+    options: &ParserOptions,
+) -> IResult<&'a str, MarkdownInLine> {
+    if let Some(len) = bare_url_len(i) {
+        let url = &i[..len];
+        let destination = match url.starts_with("www.") {
+            true => format!("http://{}", url),
+            false => url.to_string(),
+        };
+        let text = vec![MarkdownInLine::Plain(url.to_string())];
+        return Ok((&i[len..], allowed_link(text, &destination, None, options)));
+    }
+    let (_, text) = parse_plain_with(i, options)?;
+    // a url starts a word, or follows an opening parenthesis or emphasis delimiter
+    let end = text
+        .char_indices()
+        .skip(1)
+        .find(|&(n, c)| {
+            (c == 'h' || c == 'w')
+                && text[..n].ends_with(|c: char| c.is_whitespace() || "(*_~".contains(c))
+                && bare_url_len(&i[n..]).is_some()
+        })
+        .map_or(text.len(), |(n, _)| n);
+    Ok((&i[end..], MarkdownInLine::Plain(i[..end].to_string())))
+}
+
+// One HTML tag, closing tag or comment in text: <span class="x">, </span>, <br/>.
+// Quoted attribute values may hold `>`.
+pub fn parse_inline_html(i: &str) -> IResult<&str, &str> {
+    if let Some(body) = i.strip_prefix("<!--") {
+        return match body.find("-->") {
+            Some(end) => Ok((&body[end + 3..], &i[..4 + end + 3])),
+            None => Err(Error((body, ErrorKind::TakeUntil))),
+        };
+    }
+    if !is_html_start(i) {
+        return Err(Error((i, ErrorKind::Verify)));
+    }
+    let mut quote = None;
+    for (n, c) in i.char_indices().skip(1) {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '>') => return Ok((&i[n + 1..], &i[..n + 1])),
+            (None, '<') => break,
+            _ => {}
+        }
+    }
+    Err(Error((i, ErrorKind::Tag)))
+}
+
+// \* or any other escaped ASCII punctuation character, which is taken literally
+pub fn parse_escape(i: &str) -> IResult<&str, char> {
+    preceded(tag("\\"), verify(anychar, char::is_ascii_punctuation))(i)
+}
+
+// :name: with ParserOptions::emoji, the name made of lowercase letters, digits, `_`,
+// `+` and `-` like GitHub's :+1: or :white_check_mark:
+pub fn parse_emoji(i: &str) -> IResult<&str, &str> {
+    let name = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || "_+-".contains(c);
+    delimited(tag(":"), take_while1(name), tag(":"))(i)
 }
 
-// match against all special tags and then join each array
+// everything up to the next special tag
 pub fn parse_plain(i: &str) -> IResult<&str, String> {
-    map(
-        many1(preceded(
-            not(alt((tag("*"), tag("`"), tag("["), tag("!["), tag("\n")))),
-            take(1u8),
-        )),
-        |vec| vec.join(""),
-    )(i)
+    parse_plain_with(i, &ParserOptions::default())
+}
+
+pub fn parse_plain_with<'a>(i: &'a str, options: &ParserOptions) -> IResult<&'a str, String> {
+    match plain_len(i, options) {
+        0 if i.is_empty() => Err(Error((i, ErrorKind::Eof))),
+        0 => Err(Error((i, ErrorKind::Not))),
+        len => Ok((&i[len..], i[..len].to_string())),
+    }
 }
 
 pub fn parse_markdown_inline(i: &str) -> IResult<&str, MarkdownInLine> {
-    alt((
-        map(parse_plain, |s| MarkdownInLine::Plain(s.to_string())),
-        map(parse_bold, |s| MarkdownInLine::Bold(s.to_string())),
-        map(parse_italic, |s| MarkdownInLine::Italic(s.to_string())),
-        map(parse_inline, |(code, language)| {
+    parse_markdown_inline_with(i, &ParserOptions::default())
+}
+
+// A Link to `url`, or only its text if `options` doesn't allow the scheme of `url`, so
+// a blocked link can't be mistaken for an empty [text]().
+fn allowed_link(text: MarkdownText, url: &str, title: Option<&str>, options: &ParserOptions) -> MarkdownInLine {
+    match options.is_allowed_url(url) {
+        true => MarkdownInLine::Link(text, url.to_string(), title.map(String::from)),
+        false => MarkdownInLine::Plain(plain_text(&text)),
+    }
+}
+
+// `parser`, with the inline elements of what it matched wrapped in `wrap`
+fn parse_wrapped<'a, 'o>(
+    parser: fn(&'a str) -> IResult<&'a str, &'a str>,
+    wrap: fn(MarkdownText) -> MarkdownInLine,
+    options: &'o ParserOptions,
+    depth: Option<usize>,
+) -> impl Fn(&'a str) -> IResult<&'a str, MarkdownInLine> + 'o
+where
+    'a: 'o,
+{
+    move |i| {
+        let (rest, content) = parser(i)?;
+        Ok((rest, wrap(parse_span(content, options, depth)?)))
+    }
+}
+
+// The inline elements are limited to the depth left after the block they are in, so
+// deeply nested spans fail like deeply nested blocks do.
+pub fn parse_markdown_inline_with<'a>(
+    i: &'a str,
+    options: &ParserOptions,
+) -> IResult<&'a str, MarkdownInLine> {
+    let depth = options.limits.max_depth.map(|depth| depth.saturating_sub(1));
+    parse_inline_with(i, options, depth)
+}
+
+// dispatch on the first byte so only the parsers that can match are tried. `depth` is how
+// many levels the inline element may take up, the ones in a span get one fewer.
+fn parse_inline_with<'a>(
+    i: &'a str,
+    options: &ParserOptions,
+    depth: Option<usize>,
+) -> IResult<&'a str, MarkdownInLine> {
+    let inner = depth.map(|depth| depth.saturating_sub(1));
+    let span = |parser: fn(&'a str) -> IResult<&'a str, &'a str>, wrap| {
+        parse_wrapped(parser, wrap, options, inner)
+    };
+    match i.as_bytes().first() {
+        None | Some(b'\n') | Some(b'\r') => Err(Error((i, ErrorKind::Tag))),
+        Some(b'*') => alt((
+            |i| parse_strong_emphasis(i, "***", options, depth),
+            span(parse_bold, MarkdownInLine::Bold),
+            span(parse_italic, MarkdownInLine::Italic),
+            // a run that opens nothing is literal text
+            map(take_while1(|c| c == '*'), |s: &str| MarkdownInLine::Plain(s.to_string())),
+        ))(i),
+        Some(b'_') => alt((
+            |i| parse_strong_emphasis(i, "___", options, depth),
+            span(|i| parse_emphasis(i, "__", usize::MAX), MarkdownInLine::Bold),
+            span(|i| parse_emphasis(i, "_", 1), MarkdownInLine::Italic),
+            map(take_while1(|c| c == '_'), |s: &str| MarkdownInLine::Plain(s.to_string())),
+        ))(i),
+        Some(b'<') => alt((
+            map(parse_autolink, |(text, url)| {
+                allowed_link(vec![MarkdownInLine::Plain(text.to_string())], &url, None, options)
+            }),
+            map(parse_inline_html, |html| MarkdownInLine::Html(html.to_string())),
+            map(tag("<"), |s: &str| MarkdownInLine::Plain(s.to_string())),
+        ))(i),
+        Some(b'\\') => alt((
+            map(parse_escape, |c| MarkdownInLine::Plain(c.to_string())),
+            map(|i| parse_plain_with(i, options), MarkdownInLine::Plain),
+        ))(i),
+        Some(b'~') if options.sub_superscript => alt((
+            span(parse_strikethrough, MarkdownInLine::Strikethrough),
+            span(parse_subscript, MarkdownInLine::Subscript),
+            map(take_while1(|c| c == '~'), |s: &str| MarkdownInLine::Plain(s.to_string())),
+        ))(i),
+        Some(b'~') => alt((
+            span(parse_strikethrough, MarkdownInLine::Strikethrough),
+            map(take_while1(|c| c == '~'), |s: &str| MarkdownInLine::Plain(s.to_string())),
+        ))(i),
+        Some(b'`') if options.inline_code_language => map(parse_inline, |(code, language)| {
             MarkdownInLine::InlineCode(code.to_string(), language.map(String::from))
-        }),
-        map(parse_image, |(tag, url)| {
-            MarkdownInLine::Image(tag.to_string(), url.to_string())
-        }),
-        map(parse_link, |(tag, url)| {
-            MarkdownInLine::Link(tag.to_string(), url.to_string())
-        }),
-    ))(i)
+        })(i),
+        Some(b'`') => map(parse_inline_code, |code| {
+            MarkdownInLine::InlineCode(code.to_string(), None)
+        })(i),
+        // a blocked image is left as its tag, like a blocked link
+        Some(b'!') if i.starts_with("![") => map(parse_image, |(tag, url, title)| match options.is_allowed_url(url) {
+            true => MarkdownInLine::Image(tag.to_string(), url.to_string(), title.map(String::from)),
+            false => MarkdownInLine::Plain(tag.to_string()),
+        })(i),
+        Some(b'[') => alt((
+            map(parse_footnote_ref, |label| MarkdownInLine::FootnoteRef(label.to_string())),
+            |i| match options.wiki_links {
+                true => map(parse_wiki_link, |(target, alias)| MarkdownInLine::WikiLink {
+                    target: target.to_string(),
+                    alias: alias.map(String::from),
+                })(i),
+                false => Err(Error((i, ErrorKind::Tag))),
+            },
+            |i| {
+                let (rest, (tag, url, title)) = parse_link(i)?;
+                let text = parse_label(tag, options, inner)?;
+                Ok((rest, allowed_link(text, url, title, options)))
+            },
+        ))(i),
+        Some(b'^') if i.starts_with("^[") => alt((
+            span(parse_inline_footnote, MarkdownInLine::InlineFootnote),
+            map(tag("^"), |s: &str| MarkdownInLine::Plain(s.to_string())),
+        ))(i),
+        Some(b'^') if options.sub_superscript => alt((
+            span(parse_superscript, MarkdownInLine::Superscript),
+            map(take_while1(|c| c == '^'), |s: &str| MarkdownInLine::Plain(s.to_string())),
+        ))(i),
+        Some(b'=') if options.highlight => alt((
+            span(parse_highlight, MarkdownInLine::Highlight),
+            map(take_while1(|c| c == '='), |s: &str| MarkdownInLine::Plain(s.to_string())),
+        ))(i),
+        Some(b'$') => alt((
+            map(parse_inline_math, |math| MarkdownInLine::Math(math.to_string())),
+            map(take_while1(|c| c == '$'), |s: &str| MarkdownInLine::Plain(s.to_string())),
+        ))(i),
+        Some(b':') if options.emoji => alt((
+            map(parse_emoji, |name| MarkdownInLine::Emoji(name.to_string())),
+            map(tag(":"), |s: &str| MarkdownInLine::Plain(s.to_string())),
+        ))(i),
+        Some(_) if options.autolinks => parse_plain_with_urls(i, options),
+        Some(_) => map(|i| parse_plain_with(i, options), MarkdownInLine::Plain)(i),
+    }
+}
+
+// \n, \r\n or a lone \r
+pub fn parse_line_ending(i: &str) -> IResult<&str, &str> {
+    alt((tag("\n"), tag("\r\n"), tag("\r")))(i)
+}
+
+// appends `inline`, merging it into the last element if both are plain text
+fn push_inline(text: &mut MarkdownText, inline: MarkdownInLine) {
+    if let (Some(MarkdownInLine::Plain(last)), MarkdownInLine::Plain(s)) = (text.last_mut(), &inline) {
+        last.push_str(s);
+        return;
+    }
+    text.push(inline);
+}
+
+// The text of a link. Links can't be nested, an autolink or url in it is just text.
+fn parse_label<'a>(
+    i: &'a str,
+    options: &ParserOptions,
+    depth: Option<usize>,
+) -> Result<MarkdownText, nom::Err<(&'a str, ErrorKind)>> {
+    let mut text = Vec::new();
+    for inline in parse_span(i, options, depth)? {
+        match inline {
+            MarkdownInLine::Link(label, _, _) => {
+                label.into_iter().for_each(|inline| push_inline(&mut text, inline))
+            }
+            inline => push_inline(&mut text, inline),
+        }
+    }
+    Ok(text)
+}
+
+// The inline elements in the content of an emphasis span, which is a part of one line.
+// A Failure once `depth` has run out, more spans nested in it would exhaust the stack.
+fn parse_span<'a>(
+    i: &'a str,
+    options: &ParserOptions,
+    depth: Option<usize>,
+) -> Result<MarkdownText, nom::Err<(&'a str, ErrorKind)>> {
+    if depth == Some(0) {
+        return Err(Failure((i, ErrorKind::TooLarge)));
+    }
+    let mut text = Vec::new();
+    let mut rest = i;
+    while let Some(c) = rest.chars().next() {
+        match parse_inline_with(rest, options, depth) {
+            Ok((next, inline)) => {
+                push_inline(&mut text, inline);
+                rest = next;
+            }
+            Err(Failure(e)) => return Err(Failure(e)),
+            // an unmatched [ or ` is just text
+            Err(_) => {
+                push_inline(&mut text, MarkdownInLine::Plain(c.to_string()));
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    Ok(text)
 }
 
 pub fn parse_markdown_text(i: &str) -> IResult<&str, MarkdownText> {
-    terminated(many0(parse_markdown_inline), tag("\n"))(i)
+    parse_markdown_text_with(i, &ParserOptions::default())
+}
+
+pub fn parse_markdown_text_with<'a>(
+    i: &'a str,
+    options: &ParserOptions,
+) -> IResult<&'a str, MarkdownText> {
+    let mut text = Vec::new();
+    let mut rest = i;
+    loop {
+        match parse_markdown_inline_with(rest, options) {
+            Ok((next, inline)) => {
+                push_inline(&mut text, inline);
+                rest = next;
+            }
+            Err(Failure(e)) => return Err(Failure(e)),
+            // an unmatched [ or ` is just text, like in a span
+            Err(_) => match rest.chars().next() {
+                Some(c) if c != '\n' && c != '\r' => {
+                    push_inline(&mut text, MarkdownInLine::Plain(c.to_string()));
+                    rest = &rest[c.len_utf8()..];
+                }
+                _ => break,
+            },
+        }
+    }
+    let (rest, _) = parse_line_ending(rest)?;
+    Ok((rest, text))
+}
+
+// the space or tab separating a block marker from its content, a tab counts
+// as whatever is left to the next tab stop so it is consumed as one unit
+pub fn parse_marker_space(i: &str) -> IResult<&str, &str> {
+    alt((tag(" "), tag("\t")))(i)
 }
 
 pub fn parse_header_tag(i: &str) -> IResult<&str, usize> {
     map(
-        terminated(take_while1(|c| c == '#'), tag(" ")),
+        terminated(take_while1(|c| c == '#'), parse_marker_space),
         |s: &str| s.len(),
     )(i)
 }
 
 pub fn parse_header(i: &str) -> IResult<&str, (usize, MarkdownText)> {
-    tuple((parse_header_tag, parse_markdown_text))(i)
+    parse_header_with(i, &ParserOptions::default())
+}
+
+pub fn parse_header_with<'a>(
+    i: &'a str,
+    options: &ParserOptions,
+) -> IResult<&'a str, (usize, MarkdownText)> {
+    let (rest, level) = parse_header_tag(i)?;
+    let level = match options.heading_overflow {
+        _ if level <= 6 => level,
+        HeadingOverflow::Clamp => 6,
+        HeadingOverflow::Keep => level,
+        HeadingOverflow::Text => return Err(Error((i, ErrorKind::TooLarge))),
+    };
+    let (rest, mut text) = parse_markdown_text_with(rest, options)?;
+    strip_closing_hashes(&mut text);
+    Ok((rest, (level, text)))
+}
+
+// `## Title ##` -> "Title", the closing run of #s needs a space before it
+fn strip_closing_hashes(text: &mut MarkdownText) {
+    let alone = text.len() == 1;
+    if let Some(MarkdownInLine::Plain(last)) = text.last_mut() {
+        let trimmed = last.trim_end();
+        let open = trimmed.trim_end_matches('#');
+        if open.len() == trimmed.len() || !(open.ends_with([' ', '\t']) || (open.is_empty() && alone)) {
+            return;
+        }
+        let len = open.trim_end().len();
+        last.truncate(len);
+        if last.is_empty() {
+            text.pop();
+        }
+    }
+}
+
+// one line including its line ending
+pub fn parse_line(i: &str) -> IResult<&str, &str> {
+    let len = i.find(['\n', '\r']).unwrap_or(i.len());
+    let (rest, _) = parse_line_ending(&i[len..])?;
+    Ok((rest, &i[..i.len() - rest.len()]))
+}
+
+fn is_blank(line: &str) -> bool {
+    line.trim().is_empty()
+}
+
+// width of `i` in columns, tabs advance to the next multiple of 4
+fn columns(i: &str) -> usize {
+    i.chars()
+        .fold(0, |col, c| if c == '\t' { col + 4 - col % 4 } else { col + 1 })
+}
+
+// `line` without its first `indent` columns of whitespace, None if it is indented less.
+// A tab that reaches past `indent` leaves the rest of its width as spaces.
+fn strip_columns(line: &str, indent: usize) -> Option<Cow<'_, str>> {
+    let mut col = 0;
+    for (n, c) in line.char_indices() {
+        if col >= indent {
+            return Some(Cow::Borrowed(&line[n..]));
+        }
+        match c {
+            ' ' => col += 1,
+            '\t' => {
+                col += 4 - col % 4;
+                if col > indent {
+                    let spaces = " ".repeat(col - indent);
+                    return Some(Cow::Owned(spaces + &line[n + 1..]));
+                }
+            }
+            _ => return None,
+        }
+    }
+    Some(Cow::Borrowed(&line[line.len()..]))
+}
+
+// blocks and whether blank lines separate any two of them
+type Blocks = (Vec<Markdown>, bool);
+
+// A Failure once the `nodes` parsed so far are more than the limit. Blocks and list
+// items are counted as they are parsed, so a large block fails before all of it is built.
+fn check_node_count<'a>(
+    i: &'a str,
+    nodes: usize,
+    options: &ParserOptions,
+) -> Result<(), nom::Err<(&'a str, ErrorKind)>> {
+    match options.limits.max_nodes {
+        Some(limit) if nodes > limit => Err(Failure((i, ErrorKind::Count))),
+        _ => Ok(()),
+    }
+}
+
+// Parses the content of a container, with its prefix already removed, as blocks.
+// `levels` is how much deeper than the container its blocks are, which is taken from
+// the depth budget so deeply nested input fails before it can exhaust the stack.
+fn parse_nested<'a>(
+    i: &'a str,
+    content: &str,
+    levels: usize,
+    options: &ParserOptions,
+) -> Result<Blocks, nom::Err<(&'a str, ErrorKind)>> {
+    let mut nested = options.clone();
+    if let Some(depth) = options.limits.max_depth {
+        if depth <= levels {
+            return Err(Failure((i, ErrorKind::TooLarge)));
+        }
+        nested.limits.max_depth = Some(depth - levels);
+    }
+
+    if is_blank(content) {
+        return Ok((vec![], false));
+    }
+    match parse_blocks_with(content, &nested) {
+        Ok(("", blocks)) => Ok(blocks),
+        Err(Failure((_, kind))) => Err(Failure((i, kind))),
+        Ok(_) | Err(_) => Err(Error((i, ErrorKind::Many1))),
+    }
+}
+
+// The rest of the line at `i` plus every following line indented by `indent` columns
+// or more with that indentation removed, and blank lines between those.
+fn parse_indented_content(i: &str, indent: usize) -> IResult<&str, String> {
+    let (mut rest, first) = parse_line(i)?;
+    let mut content = String::from(first);
+
+    loop {
+        let mut next = rest;
+        let mut blanks = 0;
+        while let Ok((after, line)) = parse_line(next) {
+            if !is_blank(line) {
+                break;
+            }
+            next = after;
+            blanks += 1;
+        }
+        let continuation = parse_line(next)
+            .ok()
+            .and_then(|(after, line)| strip_columns(line, indent).map(|line| (after, line)));
+        match continuation {
+            Some((after, line)) => {
+                content.push_str(&"\n".repeat(blanks));
+                content.push_str(&line);
+                rest = after;
+            }
+            None => break,
+        }
+    }
+    Ok((rest, content))
+}
+
+// The item starting at `i`, whose marker ends at `rest`. Its content is the rest of the
+// first line plus every following line indented past the marker, and blank lines
+// between those, so fenced code and nested lists can live inside the item.
+// Also tells whether blank lines separate the item's blocks.
+fn parse_list_item<'a>(
+    i: &'a str,
+    rest: &'a str,
+    options: &ParserOptions,
+) -> IResult<&'a str, (ListItem, bool)> {
+    let indent = columns(&i[..i.len() - rest.len()]);
+    let (rest, mut content) = parse_indented_content(rest, indent)?;
+
+    let (mut checked, mut task) = (None, None);
+    if let Some((is_checked, text)) = split_task_box(&content) {
+        checked = Some(is_checked);
+        content = if options.tasks {
+            let (meta, text) = split_task_metadata(text);
+            task = Some(meta);
+            text
+        } else {
+            text.to_string()
+        };
+    }
+
+    let (blocks, separated) = parse_nested(i, &content, 2, options)?;
+    Ok((rest, (ListItem { checked, task, blocks }, separated)))
+}
+
+// "[x] text" -> (true, "text")
+fn split_task_box(content: &str) -> Option<(bool, &str)> {
+    let checked = match content.get(..3)? {
+        "[ ]" => false,
+        "[x]" | "[X]" => true,
+        _ => return None,
+    };
+    let text = &content[3..];
+    match text.chars().next() {
+        Some(' ') | Some('\t') => Some((checked, &text[1..])),
+        Some('\n') | Some('\r') => Some((checked, text)),
+        _ => None,
+    }
+}
+
+fn is_date(s: &str) -> bool {
+    let b = s.as_bytes();
+    b.len() == 10
+        && b.iter().enumerate().all(|(n, &c)| if n == 4 || n == 7 { c == b'-' } else { c.is_ascii_digit() })
+}
+
+// the last word of `text` and what comes before it, trimmed
+fn split_last_word(text: &str) -> (&str, &str) {
+    match text.rsplit_once(char::is_whitespace) {
+        Some((rest, last)) => (rest.trim_end(), last),
+        None => ("", text),
+    }
+}
+
+// A leading or trailing priority and trailing due dates of the first line of a task,
+// with the text that remains. Everything after the first line is left alone.
+fn split_task_metadata(content: &str) -> (Task, String) {
+    let len = content.find(['\n', '\r']).unwrap_or(content.len());
+    let mut text = content[..len].trim();
+    let mut task = Task::default();
+    let priority = |word: &str| match word.as_bytes() {
+        [b'(', c @ b'A'..=b'Z', b')'] => Some(*c as char),
+        _ => None,
+    };
+
+    let (first, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    if let Some(first) = priority(first) {
+        task.priority = Some(first);
+        text = rest.trim_start();
+    }
+    loop {
+        let (rest, last) = split_last_word(text);
+        let (before, marker) = split_last_word(rest);
+        if marker == "📅" && is_date(last) {
+            task.due.get_or_insert_with(|| last.to_string());
+            text = before;
+        } else if last.strip_prefix("due:").is_some_and(is_date) {
+            task.due.get_or_insert_with(|| last[4..].to_string());
+            text = rest;
+        } else if task.priority.is_none() && priority(last).is_some() {
+            task.priority = priority(last);
+            text = rest;
+        } else {
+            break;
+        }
+    }
+    (task, text.to_string() + &content[len..])
+}
+
+// One or more items, each starting with what `marker` accepts. Blank lines between
+// them are part of the list, those and blank lines between the blocks of an item
+// make it loose.
+fn parse_list_items<'a, O>(
+    i: &'a str,
+    mut info: ListInfo,
+    marker: impl Fn(&'a str) -> IResult<&'a str, O>,
+    options: &ParserOptions,
+) -> IResult<&'a str, (Vec<ListItem>, ListInfo)> {
+    let item = |i| {
+        let (rest, _) = marker(i)?;
+        parse_list_item(i, rest, options)
+    };
+    let item_nodes =
+        |item: &ListItem| 1 + item.blocks.iter().map(Markdown::node_count).sum::<usize>();
+    let (mut rest, (first, separated)) = item(i)?;
+    let mut nodes = item_nodes(&first);
+    check_node_count(i, nodes, options)?;
+    let mut items = vec![first];
+    info.loose |= separated;
+    loop {
+        let (blank, next) = split_blank_lines(rest);
+        match item(next) {
+            Ok((after, (found, separated))) => {
+                nodes += item_nodes(&found);
+                check_node_count(i, nodes, options)?;
+                info.loose |= separated || !blank.is_empty();
+                items.push(found);
+                rest = after;
+            }
+            Err(Failure(e)) => return Err(Failure(e)),
+            // blank lines after the last item are not part of the list
+            Err(_) => break,
+        }
+    }
+    Ok((rest, (items, info)))
+}
+
+// Up to 3 spaces before a list marker. A sub-list indented by 4 spaces or a tab
+// keeps some of that after its parent's content column is removed.
+fn parse_list_indent(i: &str) -> IResult<&str, &str> {
+    take_while_m_n(0, 3, |c| c == ' ')(i)
 }
 
+// -, * or + with the space after it, returns the bullet
 pub fn parse_unordered_list_tag(i: &str) -> IResult<&str, &str> {
-    terminated(tag("-"), tag(" "))(i)
+    terminated(alt((tag("-"), tag("*"), tag("+"))), parse_marker_space)(i)
+}
+
+pub fn parse_unordered_list_element(i: &str) -> IResult<&str, ListItem> {
+    parse_unordered_list_element_with(i, &ParserOptions::default())
+}
+
+pub fn parse_unordered_list_element_with<'a>(
+    i: &'a str,
+    options: &ParserOptions,
+) -> IResult<&'a str, ListItem> {
+    let (rest, _) = preceded(parse_list_indent, parse_unordered_list_tag)(i)?;
+    map(|rest| parse_list_item(i, rest, options), |(item, _)| item)(rest)
+}
+
+pub fn parse_unordered_list(i: &str) -> IResult<&str, (Vec<ListItem>, ListInfo)> {
+    parse_unordered_list_with(i, &ParserOptions::default())
+}
+
+pub fn parse_unordered_list_with<'a>(
+    i: &'a str,
+    options: &ParserOptions,
+) -> IResult<&'a str, (Vec<ListItem>, ListInfo)> {
+    let tag = |i| preceded(parse_list_indent, parse_unordered_list_tag)(i);
+    let (_, bullet) = tag(i)?;
+    let info = ListInfo {
+        marker: bullet.as_bytes()[0] as char,
+        ..ListInfo::default()
+    };
+    // a rule after the last item or another bullet ends the list
+    let marker = |i| preceded(not(parse_thematic_break), verify(tag, |b: &str| b == bullet))(i);
+    parse_list_items(i, info, marker, options)
+}
+
+// 1. or 1) with the space after it, returns the number and the delimiter.
+// Like CommonMark at most 9 digits, so the number always fits.
+pub fn parse_ordered_list_tag(i: &str) -> IResult<&str, (&str, char)> {
+    terminated(
+        pair(
+            take_while_m_n(1, 9, |d| is_digit(d as u8)),
+            map(alt((tag("."), tag(")"))), |d: &str| d.as_bytes()[0] as char),
+        ),
+        parse_marker_space,
+    )(i)
+}
+
+pub fn parse_ordered_list_element(i: &str) -> IResult<&str, ListItem> {
+    parse_ordered_list_element_with(i, &ParserOptions::default())
+}
+
+pub fn parse_ordered_list_element_with<'a>(
+    i: &'a str,
+    options: &ParserOptions,
+) -> IResult<&'a str, ListItem> {
+    let (rest, _) = preceded(parse_list_indent, parse_ordered_list_tag)(i)?;
+    map(|rest| parse_list_item(i, rest, options), |(item, _)| item)(rest)
 }
 
-pub fn parse_unordered_list_element(i: &str) -> IResult<&str, MarkdownText> {
-    preceded(parse_unordered_list_tag, parse_markdown_text)(i)
+pub fn parse_ordered_list(i: &str) -> IResult<&str, (Vec<ListItem>, ListInfo)> {
+    parse_ordered_list_with(i, &ParserOptions::default())
 }
 
-pub fn parse_unordered_list(i: &str) -> IResult<&str, Vec<MarkdownText>> {
-    many1(parse_unordered_list_element)(i)
+pub fn parse_ordered_list_with<'a>(
+    i: &'a str,
+    options: &ParserOptions,
+) -> IResult<&'a str, (Vec<ListItem>, ListInfo)> {
+    let tag = |i| preceded(parse_list_indent, parse_ordered_list_tag)(i);
+    let (_, (start, delimiter)) = tag(i)?;
+    let start = start.bytes().fold(0, |n, d| n * 10 + u64::from(d - b'0'));
+    // another delimiter starts a new list
+    let marker = |i| verify(tag, |(_, d)| *d == delimiter)(i);
+    parse_list_items(i, ListInfo::ordered(start, delimiter), marker, options)
 }
 
-pub fn parse_ordered_list_tag(i: &str) -> IResult<&str, &str> {
+// [^label]: with the space after it, returns the label
+pub fn parse_footnote_label(i: &str) -> IResult<&str, &str> {
     terminated(
-        terminated(take_while1(|d| is_digit(d as u8)), tag(".")),
-        tag(" "),
+        delimited(tag("[^"), is_not(" \t\r\n]"), tag("]:")),
+        alt((parse_marker_space, peek(parse_line_ending))),
     )(i)
 }
 
-pub fn parse_ordered_list_element(i: &str) -> IResult<&str, MarkdownText> {
-    preceded(parse_ordered_list_tag, parse_markdown_text)(i)
+pub fn parse_footnote_definition(i: &str) -> IResult<&str, (String, Vec<Markdown>)> {
+    parse_footnote_definition_with(i, &ParserOptions::default())
 }
 
-pub fn parse_ordered_list(i: &str) -> IResult<&str, Vec<MarkdownText>> {
-    many1(parse_ordered_list_element)(i)
+// [^1]: the footnote
+//     continued by lines indented by 4 columns
+pub fn parse_footnote_definition_with<'a>(
+    i: &'a str,
+    options: &ParserOptions,
+) -> IResult<&'a str, (String, Vec<Markdown>)> {
+    let (rest, label) = parse_footnote_label(i)?;
+    let (rest, content) = parse_indented_content(rest, 4)?;
+    let (blocks, _) = parse_nested(i, &content, 1, options)?;
+    Ok((rest, (label.to_string(), blocks)))
 }
 
 // > text
 pub fn parse_quote_tag(i: &str) -> IResult<&str, &str> {
-    terminated(tag(">"), tag(" "))(i)
+    terminated(tag(">"), parse_marker_space)(i)
+}
+
+// "> ", or a bare ">" before a nested ">" or the end of the line
+fn parse_quote_prefix(i: &str) -> IResult<&str, &str> {
+    alt((
+        parse_quote_tag,
+        terminated(tag(">"), peek(alt((tag(">"), parse_line_ending)))),
+    ))(i)
 }
 
-pub fn parse_quote_line(i: &str) -> IResult<&str, MarkdownText> {
-    preceded(parse_quote_tag, parse_markdown_text)(i)
+// the content of one quote line without its marker, including the line ending
+pub fn parse_quote_line(i: &str) -> IResult<&str, &str> {
+    preceded(parse_quote_prefix, parse_line)(i)
 }
 
 // > #text
 // > this is a quote
 // > - list in quote
 // > - list in quote
-pub fn parse_quote(i: &str) -> IResult<&str, Vec<MarkdownText>> {
-    many1(parse_quote_line)(i)
+// > — Author
+// the lines are parsed as blocks once their markers are removed, returns (body, cite)
+pub fn parse_quote(i: &str) -> IResult<&str, (Vec<Markdown>, Option<MarkdownText>)> {
+    parse_quote_with(i, &ParserOptions::default())
 }
 
-// ``` lang\n
-//  text
-// ```
-//
+pub fn parse_quote_with<'a>(
+    i: &'a str,
+    options: &ParserOptions,
+) -> IResult<&'a str, (Vec<Markdown>, Option<MarkdownText>)> {
+    let (rest, lines) = many1(parse_quote_line)(i)?;
+    let cite = lines.split_last().and_then(|(last, body)| {
+        let cite = last.strip_prefix("— ").or_else(|| last.strip_prefix("-- "))?;
+        Some((cite, body))
+    });
+    // the last line is only a cite below at least one block of the quote
+    if let Some((cite, body)) = cite {
+        let (blocks, _) = parse_nested(i, &body.concat(), 1, options)?;
+        if !blocks.is_empty() {
+            return Ok((rest, (blocks, Some(parse_table_cell(cite.trim(), options)))));
+        }
+    }
+    let (blocks, _) = parse_nested(i, &lines.concat(), 1, options)?;
+    Ok((rest, (blocks, None)))
+}
+
+// [!NOTE], maybe followed by the + or - of a foldable Obsidian callout
+fn parse_admonition_marker(i: &str) -> IResult<&str, &str> {
+    terminated(
+        delimited(tag("[!"), take_while1(|c: char| c.is_ascii_alphabetic()), tag("]")),
+        opt(alt((tag("+"), tag("-")))),
+    )(i)
+}
+
+pub fn parse_admonition(i: &str) -> IResult<&str, (String, MarkdownText, Vec<Markdown>)> {
+    parse_admonition_with(i, &ParserOptions::default())
+}
+
+// A quote whose first line is an admonition marker and an optional title, returns
+// (kind, title, body).
+pub fn parse_admonition_with<'a>(
+    i: &'a str,
+    options: &ParserOptions,
+) -> IResult<&'a str, (String, MarkdownText, Vec<Markdown>)> {
+    let (rest, lines) = many1(parse_quote_line)(i)?;
+    let (title, kind) =
+        parse_admonition_marker(lines[0]).map_err(|_| Error((i, ErrorKind::Verify)))?;
+    let (_, title) = parse_markdown_text_with(title.trim_start_matches([' ', '\t']), options)?;
+    let (body, _) = parse_nested(i, &lines[1..].concat(), 1, options)?;
+    Ok((rest, (kind.to_ascii_uppercase(), title, body)))
+}
+
+// $$
+// \frac{a_1}{b^*}
+// $$
+// or on one line, $$ x^2 $$. The TeX may start right after the opening $$ and end right
+// before the closing one. Without a closing $$ it isn't a math block.
+pub fn parse_math_block(i: &str) -> IResult<&str, String> {
+    let (mut rest, line) = parse_line(i)?;
+    let first = line.trim_end().strip_prefix("$$").ok_or(Error((i, ErrorKind::Tag)))?;
+    if let Some(math) = first.strip_suffix("$$") {
+        return Ok((rest, math.trim().to_string()));
+    }
+    let mut math = String::from(first.trim_start());
+    if !math.is_empty() {
+        math.push('\n');
+    }
+    while let Ok((next, line)) = parse_line(rest) {
+        let line = line.trim_end();
+        if let Some(last) = line.strip_suffix("$$") {
+            math.push_str(last);
+            return Ok((next, math.trim_end().to_string()));
+        }
+        math.push_str(line);
+        math.push('\n');
+        rest = next;
+    }
+    Err(Error((i, ErrorKind::TakeUntil)))
+}
+
+// the number of colons of a ::: line and what follows them
+fn split_container_fence(line: &str) -> Option<(usize, &str)> {
+    let colons = line.bytes().take_while(|&b| b == b':').count();
+    if colons < 3 {
+        return None;
+    }
+    Some((colons, line[colons..].trim()))
+}
+
+pub fn parse_container(i: &str) -> IResult<&str, (String, Attributes, Vec<Markdown>)> {
+    parse_container_with(i, &ParserOptions::default())
+}
+
+// ::: warning {open=true}
+// *content*
+// :::
+// The name is required, the rest of the line is read like a code fence's info string.
+// Containers nest, a longer fence like :::: can make that easier to read. The closing
+// line is made of at least as many colons as the opening one.
+pub fn parse_container_with<'a>(
+    i: &'a str,
+    options: &ParserOptions,
+) -> IResult<&'a str, (String, Attributes, Vec<Markdown>)> {
+    let (rest, line) = parse_line(i)?;
+    let (len, info) = split_container_fence(line)
+        .filter(|(_, info)| !info.is_empty())
+        .ok_or(Error((i, ErrorKind::Verify)))?;
+    let (content, rest) = split_container_content(rest, len);
+
+    let FenceInfo { language, attributes } = parse_fence_info(info);
+    let (blocks, _) = parse_nested(i, content, 1, options)?;
+    Ok((rest, (language.unwrap_or_default(), attributes, blocks)))
+}
+
+// The content of a container opened by `len` colons up to its closing line, and what
+// follows that line. Without a closing line the container runs to the end of the input.
+fn split_container_content(i: &str, len: usize) -> (&str, &str) {
+    let mut rest = i;
+    let mut open = 0;
+    while let Ok((next, line)) = parse_line(rest) {
+        match split_container_fence(line) {
+            Some((_, "")) if open > 0 => open -= 1,
+            Some((colons, "")) if colons >= len => return (&i[..i.len() - rest.len()], next),
+            Some((_, name)) if !name.is_empty() => open += 1,
+            _ => {}
+        }
+        rest = next;
+    }
+    (&i[..i.len() - rest.len()], rest)
+}
+
+// the offset of the `close` that matches an `open` already consumed before `i`
+fn find_closing(i: &str, open: char, close: char) -> Option<usize> {
+    let mut depth = 0;
+    for (n, c) in i.char_indices() {
+        if c == open {
+            depth += 1;
+        } else if c == close && depth == 0 {
+            return Some(n);
+        } else if c == close {
+            depth -= 1;
+        }
+    }
+    None
+}
+
+// (name, label, attributes) of the name[label]{attributes} after a directive's colons
+fn split_directive(line: &str) -> Option<(&str, &str, &str)> {
+    let line = line.trim_end();
+    if !line.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let len = line
+        .find(|c: char| !c.is_ascii_alphanumeric() && c != '-' && c != '_')
+        .unwrap_or(line.len());
+    let (name, mut rest) = line.split_at(len);
+    let mut delimited = |open, close| match rest.strip_prefix(open) {
+        Some(after) => {
+            let end = find_closing(after, open, close)?;
+            rest = &after[end + 1..];
+            Some(&after[..end])
+        }
+        None => Some(""),
+    };
+    let label = delimited('[', ']')?;
+    let attributes = delimited('{', '}')?;
+    rest.is_empty().then_some((name, label, attributes))
+}
+
+// key=value pairs and flags like a fence's, `#id` and `.class` are short for
+// id=id and class=class
+fn parse_directive_attributes(attributes: &str) -> Attributes {
+    parse_fence_info(&format!("{{{}}}", attributes))
+        .attributes
+        .into_iter()
+        .map(|(key, value)| match (key.as_bytes().first(), value) {
+            (Some(b'#'), None) => (String::from("id"), Some(key[1..].to_string())),
+            (Some(b'.'), None) => (String::from("class"), Some(key[1..].to_string())),
+            (_, value) => (key, value),
+        })
+        .collect()
+}
+
+pub fn parse_directive(i: &str) -> IResult<&str, Markdown> {
+    parse_directive_with(i, &ParserOptions::default())
+}
+
+// ::name[label]{key=value}
+// or
+// :::name[label]{key=value}
+// content
+// :::
+// The generic directives proposed for CommonMark, the label and attributes are optional.
+// Returns a Markdown::Directive, a leaf directive has no body.
+pub fn parse_directive_with<'a>(i: &'a str, options: &ParserOptions) -> IResult<&'a str, Markdown> {
+    let (rest, line) = parse_line(i)?;
+    let colons = line.bytes().take_while(|&b| b == b':').count();
+    let (name, label, attributes) = split_directive(&line[colons..])
+        .filter(|_| colons >= 2)
+        .ok_or(Error((i, ErrorKind::Verify)))?;
+    let label = match label.trim() {
+        "" => vec![],
+        label => parse_table_cell(label, options),
+    };
+    let (rest, body) = match colons {
+        2 => (rest, None),
+        _ => {
+            let (content, rest) = split_container_content(rest, colons);
+            let (blocks, _) = parse_nested(i, content, 1, options)?;
+            (rest, Some(blocks))
+        }
+    };
+    let (name, attrs) = (name.to_string(), parse_directive_attributes(attributes));
+    Ok((rest, Markdown::Directive { name, label, attrs, body }))
+}
+
+// up to 3 spaces before a fence
+fn parse_fence_indent(i: &str) -> IResult<&str, &str> {
+    take_while_m_n(0, 3, |c| c == ' ')(i)
+}
+
+// a run of at least `len` of the fence's `mark` at the start of a line (after up to
+// 3 spaces), followed by nothing but whitespace
+fn is_closing_fence(line: &str, mark: u8, len: usize) -> bool {
+    let line = parse_fence_indent(line).map_or(line, |(rest, _)| rest);
+    let run = line.bytes().take_while(|&b| b == mark).count();
+    run >= len && line[run..].trim().is_empty()
+}
+
+// the opening fence of a code block and its info string, without the line ending
+fn parse_opening_fence(i: &str) -> IResult<&str, (&str, &str)> {
+    let (rest, _) = parse_fence_indent(i)?;
+    let (rest, fence) = alt((
+        take_while_m_n(3, usize::MAX, |c| c == '`'),
+        take_while_m_n(3, usize::MAX, |c| c == '~'),
+    ))(rest)?;
+    let (rest, info) = map(opt(is_not("\r\n")), |info| info.unwrap_or(""))(rest)?;
+    // only backtick fences, ~~~ may have them in its info string
+    if fence.starts_with('`') && info.contains('`') {
+        return Err(Error((rest, ErrorKind::Verify)));
+    }
+    Ok((rest, (fence, info)))
+}
+
+// ``` lang\n
+//  text
+// ```
+// The info string is optional, ~~~ works the same as ```. The block is closed by a
+// fence of the same kind at least as long as the opening one, so ```` can wrap
+// content containing ```. Without a closing fence the block runs to the end of the input.
 pub fn parse_code_block(i: &str) -> IResult<&str, (&str, &str)> {
-    tuple((
-        delimited(tag("```"), is_not("\n"), tag("\n")),
-        delimited(tag(""), is_not("```"), tag("```"))
-    ))(i)
+    let (rest, (fence, info)) = parse_opening_fence(i)?;
+    let mark = fence.as_bytes()[0];
+    let content = match parse_line_ending(rest) {
+        Ok((content, _)) => content,
+        Err(_) => return Ok((rest, (info, rest))),
+    };
+
+    let mut line = content;
+    while !line.is_empty() {
+        let len = line.find(['\n', '\r']).unwrap_or(line.len());
+        if is_closing_fence(&line[..len], mark, fence.len()) {
+            let code = &content[..content.len() - line.len()];
+            return Ok((&line[len..], (info, code)));
+        }
+        line = parse_line_ending(&line[len..]).map_or(&line[len..], |(next, _)| next);
+    }
+    Ok((line, (info, content)))
+}
+
+// removes up to `indent` leading spaces from every line
+fn strip_indent(code: &str, indent: usize) -> String {
+    code.split_inclusive('\n')
+        .map(|line| {
+            let spaces = line.bytes().take(indent).take_while(|&b| b == b' ').count();
+            &line[spaces..]
+        })
+        .collect()
+}
+
+// words separated by commas or whitespace, which stay inside double quotes
+fn split_fence_words(info: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let (mut start, mut quoted) = (0, false);
+    for (n, c) in info.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' | ' ' | '\t' if !quoted => {
+                words.push(&info[start..n]);
+                start = n + 1;
+            }
+            _ => {}
+        }
+    }
+    words.push(&info[start..]);
+    words.into_iter().filter(|word| !word.is_empty()).collect()
+}
+
+// The info string of a fence: the first word is the language, unless the info
+// starts with `{`, what follows are flags and then {key=value} attributes.
+pub fn parse_fence_info(info: &str) -> FenceInfo {
+    let info = info.trim();
+    let (words, braces) = match info.find('{') {
+        Some(n) => {
+            let braces = &info[n + 1..];
+            (&info[..n], braces.strip_suffix('}').unwrap_or(braces))
+        }
+        None => (info, ""),
+    };
+    let mut words = split_fence_words(words).into_iter();
+    let mut fence = FenceInfo {
+        language: words.next().map(String::from),
+        attributes: words.map(|flag| (flag.to_string(), None)).collect(),
+    };
+    for word in split_fence_words(braces) {
+        let attribute = match word.split_once('=') {
+            Some((key, value)) => {
+                let unquoted = value.strip_prefix('"').and_then(|v| v.strip_suffix('"'));
+                let value = unquoted.unwrap_or(value);
+                (key.to_string(), Some(value.to_string()))
+            }
+            None => (word.to_string(), None),
+        };
+        fence.attributes.push(attribute);
+    }
+    fence
+}
+
+// whether the line at `i` starts with the 4 columns of whitespace of indented code
+fn is_code_indent(i: &str) -> bool {
+    let content = i.trim_start_matches([' ', '\t']);
+    columns(&i[..i.len() - content.len()]) >= 4
+}
+
+// Lines indented by 4 columns or more, with those removed, and the blank lines between
+// them. Blank lines after the last one are not part of the code.
+pub fn parse_indented_code_block(i: &str) -> IResult<&str, String> {
+    let mut code = String::new();
+    let mut rest = i;
+    loop {
+        let (blank, next) = split_blank_lines(rest);
+        let line = parse_line(next)
+            .ok()
+            .filter(|(_, line)| is_code_indent(line))
+            .and_then(|(after, line)| strip_columns(line, 4).map(|line| (after, line)));
+        match line {
+            Some((after, line)) => {
+                if !code.is_empty() {
+                    code.push_str(&"\n".repeat(blank.lines().count()));
+                }
+                code.push_str(&line);
+                rest = after;
+            }
+            None => break,
+        }
+    }
+    if code.is_empty() {
+        return Err(Error((i, ErrorKind::Verify)));
+    }
+    Ok((rest, normalize_line_endings(&code).into_owned()))
+}
+
+fn parse_code_block_markdown(i: &str) -> IResult<&str, Markdown> {
+    let indent = i.bytes().take_while(|&b| b == b' ').count();
+    let (rest, block) = map(parse_code_block, move |(info, code)| {
+        let code = normalize_line_endings(code);
+        Markdown::CodeBlock(strip_indent(&code, indent), parse_fence_info(info))
+    })(i)?;
+    // the closing fence's line ending belongs to the block
+    let rest = parse_line_ending(rest).map_or(rest, |(rest, _)| rest);
+    Ok((rest, block))
+}
+
+// Title
+// =====
+// the line under the text decides the level, `=` for 1 and `-` for 2
+pub fn parse_setext_heading(i: &str) -> IResult<&str, (usize, MarkdownText)> {
+    parse_setext_heading_with(i, &ParserOptions::default())
+}
+
+pub fn parse_setext_heading_with<'a>(
+    i: &'a str,
+    options: &ParserOptions,
+) -> IResult<&'a str, (usize, MarkdownText)> {
+    let (rest, line) = parse_line(i)?;
+    let (after, underline) = parse_line(rest)?;
+    let level = setext_level(line, underline).ok_or(Error((i, ErrorKind::Verify)))?;
+    let (_, text) = parse_markdown_text_with(line, options)?;
+    Ok((after, (level, text)))
+}
+
+// the level `underline` gives the text `line` above it, if it is a setext heading
+fn setext_level(line: &str, underline: &str) -> Option<usize> {
+    let underline = underline.trim_end();
+    let marks = underline.trim_start_matches(' ');
+    match marks.as_bytes().first() {
+        _ if is_blank(line) || underline.len() - marks.len() > 3 => None,
+        Some(b'=') if marks.bytes().all(|b| b == b'=') => Some(1),
+        Some(b'-') if marks.bytes().all(|b| b == b'-') => Some(2),
+        _ => None,
+    }
+}
+
+// : a definition
+fn parse_definition_with<'a>(i: &'a str, options: &ParserOptions) -> IResult<&'a str, MarkdownText> {
+    preceded(pair(tag(":"), parse_marker_space), |i| parse_markdown_text_with(i, options))(i)
+}
+
+// a term line followed by its definitions
+fn parse_definition_entry_with<'a>(
+    i: &'a str,
+    options: &ParserOptions,
+) -> IResult<&'a str, (MarkdownText, Vec<MarkdownText>)> {
+    let (rest, line) = parse_line(i)?;
+    if is_blank(line) || line.starts_with(':') {
+        return Err(Error((i, ErrorKind::Verify)));
+    }
+    let (_, term) = parse_markdown_text_with(line, options)?;
+    let (rest, definitions) = many1(|i| parse_definition_with(i, options))(rest)?;
+    Ok((rest, (term, definitions)))
+}
+
+// Term
+// : definition
+// : another one
+//
+// Next term
+// : its definition
+// Terms may be separated by one blank line.
+pub fn parse_definition_list(i: &str) -> IResult<&str, Vec<(MarkdownText, Vec<MarkdownText>)>> {
+    parse_definition_list_with(i, &ParserOptions::default())
+}
+
+pub fn parse_definition_list_with<'a>(
+    i: &'a str,
+    options: &ParserOptions,
+) -> IResult<&'a str, Vec<(MarkdownText, Vec<MarkdownText>)>> {
+    let (mut rest, entry) = parse_definition_entry_with(i, options)?;
+    let mut entries = vec![entry];
+    loop {
+        let next = match parse_line(rest) {
+            Ok((after, line)) if is_blank(line) => after,
+            _ => rest,
+        };
+        match parse_definition_entry_with(next, options) {
+            Ok((after, entry)) => {
+                entries.push(entry);
+                rest = after;
+            }
+            Err(_) => return Ok((rest, entries)),
+        }
+    }
+}
+
+// whether `line` starts with an HTML tag, closing tag or comment
+fn is_html_start(line: &str) -> bool {
+    let tag = match line.strip_prefix('<') {
+        Some(rest) if rest.starts_with("!--") => return true,
+        Some(rest) => rest.strip_prefix('/').unwrap_or(rest),
+        None => return false,
+    };
+    let name = tag.bytes().take_while(|b| b.is_ascii_alphanumeric() || *b == b'-').count();
+    // <https://example.com> is not a tag
+    let after = &tag[name..];
+    name > 0
+        && tag.as_bytes()[0].is_ascii_alphabetic()
+        && (after.is_empty() || after.starts_with([' ', '\t', '>', '/', '\n', '\r']))
+}
+
+// tags that start an HTML block even with text after them, CommonMark's types 1 and 6
+const HTML_BLOCK_TAGS: &[&str] = &[
+    "address", "article", "aside", "base", "basefont", "blockquote", "body", "caption", "center",
+    "col", "colgroup", "dd", "details", "dialog", "dir", "div", "dl", "dt", "fieldset",
+    "figcaption", "figure", "footer", "form", "frame", "frameset", "h1", "h2", "h3", "h4", "h5",
+    "h6", "head", "header", "hr", "html", "iframe", "legend", "li", "link", "main", "menu",
+    "menuitem", "nav", "noframes", "ol", "optgroup", "option", "p", "param", "pre", "script",
+    "search", "section", "style", "summary", "table", "tbody", "td", "textarea", "tfoot", "th",
+    "thead", "title", "tr", "track", "ul",
+];
+
+// Whether `line` starts an HTML block: a comment, a block-level tag or any other tag alone
+// on the line. That last one can't interrupt a paragraph, so <kbd> at the start of a
+// line stays inline.
+fn is_html_block_start(line: &str, interrupting: bool) -> bool {
+    if !is_html_start(line) {
+        return false;
+    }
+    if line.starts_with("<!--") {
+        return true;
+    }
+    let tag = line[1..].strip_prefix('/').unwrap_or(&line[1..]);
+    let name = &tag[..tag.bytes().take_while(|b| b.is_ascii_alphanumeric() || *b == b'-').count()];
+    if HTML_BLOCK_TAGS.iter().any(|block| block.eq_ignore_ascii_case(name)) {
+        return true;
+    }
+    !interrupting && parse_inline_html(line).is_ok_and(|(rest, _)| rest.trim().is_empty())
+}
+
+// <div class="note">
+//   *not* markdown
+// </div>
+// A line starting with an HTML comment, a block-level tag or a tag alone on it starts a
+// block that runs to the next blank line. Line endings are normalized to \n.
+pub fn parse_html_block(i: &str) -> IResult<&str, String> {
+    let first = &i[..i.find(['\n', '\r']).unwrap_or(i.len())];
+    if !is_html_block_start(first, false) {
+        return Err(Error((i, ErrorKind::Verify)));
+    }
+    let mut rest = i;
+    while let Ok((next, line)) = parse_line(rest) {
+        if is_blank(line) {
+            break;
+        }
+        rest = next;
+    }
+    // the last line may end without a line ending
+    if !rest.is_empty() && !rest.starts_with(['\n', '\r']) && parse_line(rest).is_err() {
+        rest = &rest[rest.len()..];
+    }
+    Ok((rest, normalize_line_endings(&i[..i.len() - rest.len()]).into_owned()))
+}
+
+// <!-- an editorial note,
+//      maybe spanning lines -->
+// Returns the text between the markers with line endings normalized to \n. Only
+// whitespace may follow the comment on its last line.
+pub fn parse_comment_block(i: &str) -> IResult<&str, String> {
+    let (body, _) = tag("<!--")(i)?;
+    let end = match body.find("-->") {
+        Some(end) => end,
+        None => return Err(Error((body, ErrorKind::TakeUntil))),
+    };
+    let after = &body[end + 3..];
+    // the last line may end without a line ending
+    let (rest, line) = parse_line(after).unwrap_or((&after[after.len()..], after));
+    if !is_blank(line) {
+        return Err(Error((after, ErrorKind::Verify)));
+    }
+    Ok((rest, normalize_line_endings(&body[..end]).into_owned()))
+}
+
+// [TOC], or [[_TOC_]] like GitLab, alone on a line
+pub fn parse_toc_marker(i: &str) -> IResult<&str, ()> {
+    let (rest, line) = parse_line(i)?;
+    match line.trim() {
+        "[TOC]" | "[[_TOC_]]" => Ok((rest, ())),
+        _ => Err(Error((i, ErrorKind::Verify))),
+    }
+}
+
+// the path of a !include(path) or {{#include path}} line as written
+pub fn parse_include(i: &str) -> IResult<&str, PathBuf> {
+    let (rest, line) = parse_line(i)?;
+    let line = line.trim();
+    let path = line
+        .strip_prefix("!include(")
+        .and_then(|path| path.strip_suffix(')'))
+        .or_else(|| line.strip_prefix("{{#include ").and_then(|path| path.strip_suffix("}}")))
+        .map(str::trim);
+    match path {
+        Some(path) if !path.is_empty() => Ok((rest, PathBuf::from(path))),
+        _ => Err(Error((i, ErrorKind::Verify))),
+    }
+}
+
+// A thematic break: 3 or more of the same `-`, `*` or `_` alone on a line, spaces
+// and tabs allowed between them and up to 3 spaces before.
+pub fn parse_thematic_break(i: &str) -> IResult<&str, ()> {
+    let (rest, line) = parse_line(i)?;
+    let line = line.trim_end_matches(['\n', '\r']);
+    let content = line.trim_start_matches(' ');
+    let c = match content.chars().next() {
+        Some(c @ ('-' | '*' | '_')) if line.len() - content.len() <= 3 => c,
+        _ => return Err(Error((i, ErrorKind::Verify))),
+    };
+    let marks = content.chars().filter(|&m| m == c).count();
+    if marks < 3 || !content.chars().all(|m| m == c || m == ' ' || m == '\t') {
+        return Err(Error((i, ErrorKind::Verify)));
+    }
+    Ok((rest, ()))
+}
+
+// the cells of a table row without the pipes around them, None if there is no pipe
+fn split_table_row(line: &str) -> Option<Vec<&str>> {
+    let line = line.trim();
+    if !line.contains('|') {
+        return None;
+    }
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = line.strip_suffix('|').unwrap_or(line);
+    Some(line.split('|').map(str::trim).collect())
+}
+
+// :---: and friends, at least one dash
+fn parse_table_alignment(cell: &str) -> Option<Alignment> {
+    let left = cell.starts_with(':');
+    let right = cell.len() > 1 && cell.ends_with(':');
+    let dashes = &cell[left as usize..cell.len() - right as usize];
+    if dashes.is_empty() || !dashes.bytes().all(|b| b == b'-') {
+        return None;
+    }
+    Some(match (left, right) {
+        (true, true) => Alignment::Center,
+        (true, false) => Alignment::Left,
+        (false, true) => Alignment::Right,
+        (false, false) => Alignment::None,
+    })
+}
+
+fn parse_table_cell(cell: &str, options: &ParserOptions) -> MarkdownText {
+    let line = format!("{}\n", cell);
+    match parse_markdown_text_with(&line, options) {
+        Ok((_, text)) => text,
+        // an unmatched [ or ` is just text in a cell
+        Err(_) => vec![MarkdownInLine::Plain(cell.to_string())],
+    }
+}
+
+pub fn parse_table(i: &str) -> IResult<&str, Table> {
+    parse_table_with(i, &ParserOptions::default())
+}
+
+// A header row, a delimiter row with the same number of cells and every following
+// line with a pipe in it. Rows with too many cells lose the extra ones, rows with
+// too few are filled with empty cells.
+pub fn parse_table_with<'a>(i: &'a str, options: &ParserOptions) -> IResult<&'a str, Table> {
+    let (rest, mut caption) = opt(|i| parse_table_caption(i, options))(i)?;
+    let (rest, line) = parse_line(rest)?;
+    let (mut rest, delimiter) = parse_line(rest)?;
+    let (header, alignments) =
+        split_table_header(line, delimiter).ok_or(Error((i, ErrorKind::Verify)))?;
+
+    let mut rows = Vec::new();
+    while let Ok((next, line)) = parse_line(rest) {
+        let cells = match split_table_row(line) {
+            Some(cells) => cells,
+            None => break,
+        };
+        let mut row: Vec<MarkdownText> = cells
+            .into_iter()
+            .take(header.len())
+            .map(|cell| parse_table_cell(cell, options))
+            .collect();
+        row.resize(header.len(), vec![]);
+        rows.push(row);
+        rest = next;
+    }
+    if caption.is_none() {
+        if let Ok((next, text)) = parse_table_caption(rest, options) {
+            caption = Some(text);
+            rest = next;
+        }
+    }
+    Ok((
+        rest,
+        Table {
+            alignments,
+            header: header.into_iter().map(|cell| parse_table_cell(cell, options)).collect(),
+            rows,
+            caption,
+        },
+    ))
+}
+
+// the header cells and the alignments of a table whose first two lines are these
+fn split_table_header<'a>(line: &'a str, delimiter: &str) -> Option<(Vec<&'a str>, Vec<Alignment>)> {
+    let header = split_table_row(line)?;
+    let alignments = split_table_row(delimiter)
+        .and_then(|cells| cells.into_iter().map(parse_table_alignment).collect::<Option<Vec<_>>>())
+        .filter(|alignments| alignments.len() == header.len())?;
+    Some((header, alignments))
+}
+
+// Table: caption, the Pandoc way, or [caption], the MultiMarkdown way
+fn parse_table_caption<'a>(i: &'a str, options: &ParserOptions) -> IResult<&'a str, MarkdownText> {
+    let (rest, line) = parse_line(i)?;
+    let caption = split_table_caption(line).ok_or(Error((i, ErrorKind::Verify)))?;
+    Ok((rest, parse_table_cell(caption, options)))
+}
+
+fn split_table_caption(line: &str) -> Option<&str> {
+    let line = line.trim();
+    line.strip_prefix("Table:")
+        .or_else(|| line.strip_prefix('[').and_then(|caption| caption.strip_suffix(']')))
+        .map(str::trim)
+        .filter(|caption| !caption.is_empty())
+}
+
+pub fn parse_block_table(i: &str) -> IResult<&str, BlockTable> {
+    parse_block_table_with(i, &ParserOptions::default())
+}
+
+// a grid or multiline table, with a caption line before or after it like a pipe table
+pub fn parse_block_table_with<'a>(
+    i: &'a str,
+    options: &ParserOptions,
+) -> IResult<&'a str, BlockTable> {
+    let (rest, caption) = opt(|i| parse_table_caption(i, options))(i)?;
+    let (mut rest, mut table) = alt((
+        |i| parse_grid_table(i, options),
+        |i| parse_multiline_table(i, true, options),
+        |i| parse_multiline_table(i, false, options),
+    ))(rest)?;
+    table.caption = caption;
+    if table.caption.is_none() {
+        if let Ok((next, text)) = parse_table_caption(rest, options) {
+            table.caption = Some(text);
+            rest = next;
+        }
+    }
+    Ok((rest, table))
+}
+
+// a line without its line ending and trailing whitespace, by char for column offsets
+fn table_line(line: &str) -> Vec<char> {
+    line.trim_end().chars().collect()
+}
+
+// The blocks of a table cell from its lines, without the spaces they all start with.
+fn parse_cell_blocks<'a>(
+    i: &'a str,
+    lines: &[String],
+    options: &ParserOptions,
+) -> Result<Vec<Markdown>, nom::Err<(&'a str, ErrorKind)>> {
+    let indent = lines
+        .iter()
+        .filter(|line| !is_blank(line))
+        .map(|line| line.len() - line.trim_start_matches(' ').len())
+        .min()
+        .unwrap_or(0);
+    let mut content = String::new();
+    for line in lines {
+        content.push_str(line.get(indent..).unwrap_or("").trim_end());
+        content.push('\n');
+    }
+    parse_nested(i, &content, 2, options).map(|(blocks, _)| blocks)
+}
+
+// the offsets of the `+` of a +---+---+ line, `fill` is `-` or `=`
+fn grid_border(line: &[char], fill: char) -> Option<Vec<usize>> {
+    if line.len() < 3 || line[0] != '+' || line[line.len() - 1] != '+' {
+        return None;
+    }
+    if !line.iter().all(|&c| c == '+' || c == ':' || c == fill) {
+        return None;
+    }
+    let columns: Vec<usize> = (0..line.len()).filter(|&n| line[n] == '+').collect();
+    // every column is at least one character wide
+    if columns.windows(2).any(|pair| pair[1] - pair[0] < 2) {
+        return None;
+    }
+    Some(columns)
+}
+
+// the alignment of every column from the colons of a border line
+fn grid_alignments(line: &[char], columns: &[usize]) -> Option<Vec<Alignment>> {
+    columns
+        .windows(2)
+        .map(|pair| {
+            let cell: String = line[pair[0] + 1..pair[1]]
+                .iter()
+                .map(|&c| if c == '=' { '-' } else { c })
+                .collect();
+            parse_table_alignment(&cell)
+        })
+        .collect()
+}
+
+// whether `line` could be the first line of a grid or multiline table
+fn is_block_table_start(line: &str) -> bool {
+    let line = table_line(line);
+    let grid = grid_border(&line, '-').and_then(|columns| grid_alignments(&line, &columns));
+    grid.is_some() || dash_columns(&line).is_some()
+}
+
+// +-------+-------+
+// | a     | b     |
+// +=======+=======+
+// | - c   | d     |
+// | - e   |       |
+// +-------+-------+
+// Rows are separated by border lines, the `=` one ends the header. Without it the
+// table has no header and the first line gives the alignments.
+fn parse_grid_table<'a>(i: &'a str, options: &ParserOptions) -> IResult<&'a str, BlockTable> {
+    let (mut rest, first) = parse_line(i)?;
+    let first = table_line(first);
+    let columns = grid_border(&first, '-').ok_or(Error((i, ErrorKind::Verify)))?;
+    let mut alignments = grid_alignments(&first, &columns).ok_or(Error((i, ErrorKind::Verify)))?;
+
+    let (mut header, mut rows) = (None, Vec::new());
+    let mut row: Vec<Vec<String>> = vec![Vec::new(); columns.len() - 1];
+    while let Ok((next, line)) = parse_line(rest) {
+        let line = table_line(line);
+        let border = |fill| grid_border(&line, fill).is_some_and(|found| found == columns);
+        if line.len() == first.len() && columns.iter().all(|&n| line[n] == '|') {
+            for (cell, pair) in row.iter_mut().zip(columns.windows(2)) {
+                cell.push(line[pair[0] + 1..pair[1]].iter().collect());
+            }
+        } else if row[0].is_empty() {
+            break;
+        } else if border('-') {
+            let cells = row.iter().map(|cell| parse_cell_blocks(i, cell, options));
+            rows.push(cells.collect::<Result<Vec<_>, _>>()?);
+            row = vec![Vec::new(); columns.len() - 1];
+        } else if border('=') && header.is_none() && rows.is_empty() {
+            let cells = row.iter().map(|cell| parse_cell_blocks(i, cell, options));
+            header = Some(cells.collect::<Result<Vec<_>, _>>()?);
+            alignments = grid_alignments(&line, &columns).ok_or(Error((i, ErrorKind::Verify)))?;
+            row = vec![Vec::new(); columns.len() - 1];
+        } else {
+            break;
+        }
+        rest = next;
+    }
+    // the last row needs a border under it
+    if !row[0].is_empty() || (header.is_none() && rows.is_empty()) {
+        return Err(Error((i, ErrorKind::Verify)));
+    }
+    Ok((rest, BlockTable { alignments, header, rows, caption: None }))
+}
+
+// the (start, end) offsets of the runs of 3 or more dashes of a `---- ----` line
+fn dash_columns(line: &[char]) -> Option<Vec<(usize, usize)>> {
+    if line.first() != Some(&'-') || !line.iter().all(|&c| c == '-' || c == ' ') {
+        return None;
+    }
+    let mut columns = Vec::new();
+    let mut start = 0;
+    for (n, &c) in line.iter().chain(std::iter::once(&' ')).enumerate() {
+        match c {
+            ' ' if n > 0 && line[n - 1] == '-' => columns.push((start, n)),
+            '-' if n == 0 || line[n - 1] == ' ' => start = n,
+            _ => {}
+        }
+    }
+    columns.iter().all(|(start, end)| end - start >= 3).then_some(columns)
+}
+
+// How the text of `line` sits in a column: flush left with its dashes is Left, flush
+// right Right, neither Center and both, like an empty column, None.
+fn multiline_alignment(line: &[char], (start, end): (usize, usize), region: usize) -> Alignment {
+    let text = |n: &usize| line[*n] != ' ';
+    let region = start.min(line.len())..region.min(line.len());
+    let first = region.clone().find(text);
+    let last = region.rev().find(text);
+    match (first == Some(start), last.is_some_and(|last| last + 1 == end)) {
+        _ if first.is_none() => Alignment::None,
+        (true, true) => Alignment::None,
+        (true, false) => Alignment::Left,
+        (false, true) => Alignment::Right,
+        (false, false) => Alignment::Center,
+    }
+}
+
+// ----------------------
+// Header     Other
+//            header
+// ---------- -----------
+// a          text over
+//            two lines
+//
+// b          c
+// ----------------------
+// A Pandoc multiline table, the `headed` one starts with a line of dashes and its
+// header ends at the line that gives the columns, at least 2 of them. A table without
+// a header starts with that line. Rows are separated by blank lines and a column holds
+// the text from its dashes up to the next column's.
+fn parse_multiline_table<'a>(
+    i: &'a str,
+    headed: bool,
+    options: &ParserOptions,
+) -> IResult<&'a str, BlockTable> {
+    let (mut rest, first) = parse_line(i)?;
+    let mut columns = dash_columns(&table_line(first)).ok_or(Error((i, ErrorKind::Verify)))?;
+    let mut header_lines = Vec::new();
+    if headed {
+        if columns.len() != 1 {
+            return Err(Error((i, ErrorKind::Verify)));
+        }
+        loop {
+            let (next, line) = parse_line(rest)?;
+            let line = table_line(line);
+            rest = next;
+            match dash_columns(&line) {
+                Some(found) if !header_lines.is_empty() => {
+                    columns = found;
+                    break;
+                }
+                _ if line.is_empty() => return Err(Error((i, ErrorKind::Verify))),
+                _ => header_lines.push(line),
+            }
+        }
+    }
+    if columns.len() < 2 {
+        return Err(Error((i, ErrorKind::Verify)));
+    }
+
+    let mut body = vec![Vec::new()];
+    loop {
+        let (next, line) = parse_line(rest)?;
+        let line = table_line(line);
+        rest = next;
+        if dash_columns(&line).is_some() {
+            break;
+        } else if line.is_empty() {
+            body.push(Vec::new());
+        } else if let Some(row) = body.last_mut() {
+            row.push(line);
+        }
+    }
+    body.retain(|row| !row.is_empty());
+    let first_line = header_lines.first().or_else(|| body.first().and_then(|row| row.first()));
+    let first_line = first_line.ok_or(Error((i, ErrorKind::Verify)))?;
+
+    let regions: Vec<_> = columns
+        .iter()
+        .enumerate()
+        .map(|(n, column)| (*column, columns.get(n + 1).map_or(usize::MAX, |next| next.0)))
+        .collect();
+    let alignments = regions
+        .iter()
+        .map(|&(column, region)| multiline_alignment(first_line, column, region))
+        .collect();
+    let cells = |lines: &[Vec<char>]| {
+        regions
+            .iter()
+            .map(|&((start, _), region)| {
+                // lines are aligned within the column, their indentation means nothing
+                let lines: Vec<String> = lines
+                    .iter()
+                    .map(|line| line.iter().take(region).skip(start).collect::<String>())
+                    .map(|line| line.trim().to_string())
+                    .collect();
+                parse_cell_blocks(i, &lines, options)
+            })
+            .collect::<Result<Vec<_>, _>>()
+    };
+    let header = match headed {
+        true => Some(cells(&header_lines)?),
+        false => None,
+    };
+    let rows = body.iter().map(|row| cells(row)).collect::<Result<Vec<_>, _>>()?;
+    Ok((rest, BlockTable { alignments, header, rows, caption: None }))
+}
+
+// Whether `line` ends in a hard line break, two spaces or a backslash that is not
+// escaped itself. The marker is removed from the end of `text`, the parsed line.
+fn take_hard_break(line: &str, text: &mut MarkdownText) -> bool {
+    let line = line.trim_end_matches(['\n', '\r']);
+    let spaces = line.ends_with("  ");
+    let backslash = (line.len() - line.trim_end_matches('\\').len()) % 2 == 1;
+    if let (true, Some(MarkdownInLine::Plain(last))) = (spaces || backslash, text.last_mut()) {
+        match backslash {
+            true => last.truncate(last.len() - 1),
+            false => last.truncate(last.trim_end_matches(' ').len()),
+        }
+        if last.is_empty() {
+            text.pop();
+        }
+    }
+    spaces || backslash
+}
+
+// A paragraph: a line of text and the lines after it that don't start another block,
+// up to a blank line. Lines are joined by a SoftBreak, or a HardBreak with hard_breaks or
+// after a line ending in two spaces or a backslash. The lines after the first one lose
+// their indentation.
+fn parse_paragraph_with<'a>(i: &'a str, options: &ParserOptions) -> IResult<&'a str, MarkdownText> {
+    let (_, mut line) = parse_line(i)?;
+    if is_blank(line) {
+        return Err(Error((i, ErrorKind::Verify)));
+    }
+    let (mut rest, mut text) = parse_markdown_text_with(i, options)?;
+    while is_paragraph_continuation(rest, options) {
+        let indent = rest.len() - rest.trim_start_matches([' ', '\t']).len();
+        let (next, next_text) = match parse_markdown_text_with(&rest[indent..], options) {
+            Ok(result) => result,
+            Err(_) => break,
+        };
+        let separator = match take_hard_break(line, &mut text) || options.hard_breaks {
+            true => MarkdownInLine::HardBreak,
+            false => MarkdownInLine::SoftBreak,
+        };
+        push_inline(&mut text, separator);
+        next_text.into_iter().for_each(|inline| push_inline(&mut text, inline));
+        line = &rest[..rest.len() - next.len()];
+        rest = next;
+    }
+    Ok((rest, text))
+}
+
+// whether the line at `i` is more text of the paragraph before it
+fn is_paragraph_continuation(i: &str, options: &ParserOptions) -> bool {
+    let blank = parse_line(i).map_or(true, |(_, line)| is_blank(line));
+    // indented code can't interrupt a paragraph
+    !blank && (is_code_indent(i) || !starts_block(i, options))
+}
+
+// Whether a block could start at `i`, from the markers of its first line and for tables,
+// setext headings and definition lists the next one or two. Unlike trying the block
+// parsers this never looks into the block, which made every line of a paragraph parse
+// all the blocks nested after it.
+fn starts_block(i: &str, options: &ParserOptions) -> bool {
+    let (rest, line) = match parse_line(i) {
+        Ok(found) => found,
+        Err(_) => return false,
+    };
+    let (rest, next) = parse_line(rest).unwrap_or((rest, ""));
+    let after = parse_line(rest).map_or("", |(_, line)| line);
+    // only a list item with some text, and for ordered lists numbered 1, interrupts a
+    // paragraph, so a year like `2019.` at the start of a line stays text
+    let list = |i| {
+        let (rest, _) = parse_list_indent(i).unwrap_or((i, ""));
+        let (rest, first) = match parse_ordered_list_tag(rest) {
+            Ok((rest, (number, _))) => (rest, number.trim_start_matches('0') == "1"),
+            Err(_) => match parse_unordered_list_tag(rest) {
+                Ok((rest, _)) => (rest, true),
+                Err(_) => return false,
+            },
+        };
+        first && parse_line(rest).is_ok_and(|(_, line)| !is_blank(line))
+    };
+    let rule = |i| parse_thematic_break(i).is_ok();
+    let container = || split_container_fence(line).is_some_and(|(_, info)| !info.is_empty());
+    let directive = || {
+        let colons = line.bytes().take_while(|&b| b == b':').count();
+        colons >= 2 && split_directive(&line[colons..]).is_some()
+    };
+    let opens = match i.as_bytes().first() {
+        Some(b'#') => parse_header_tag(i).is_ok_and(|(_, level)| {
+            level <= 6 || options.heading_overflow != HeadingOverflow::Text
+        }),
+        Some(b'0'..=b'9') => list(i),
+        Some(b'-') | Some(b'+') => is_block_table_start(line) || rule(i) || list(i),
+        Some(b'*') => rule(i) || list(i),
+        Some(b'_') => rule(i),
+        Some(b'>') => parse_quote_prefix(i).is_ok(),
+        Some(b'<') => is_html_block_start(line, true),
+        Some(b'$') => line.starts_with("$$"),
+        Some(b':') => container() || options.directives && directive(),
+        Some(b'[') => parse_toc_marker(i).is_ok() || parse_footnote_label(i).is_ok(),
+        Some(b' ') | Some(b'\t') => list(i) || rule(i) || parse_opening_fence(i).is_ok(),
+        Some(b'`') | Some(b'~') => parse_opening_fence(i).is_ok(),
+        Some(b'!') | Some(b'{') if options.includes => parse_include(i).is_ok(),
+        _ => false,
+    };
+    let table = |line, delimiter| split_table_header(line, delimiter).is_some();
+    let captioned = split_table_caption(line).is_some();
+    let definition = !line.starts_with(':') && pair(tag(":"), parse_marker_space)(next).is_ok();
+    opens
+        || table(line, next)
+        || captioned && (table(next, after) || is_block_table_start(next))
+        || setext_level(line, next).is_some()
+        || !is_blank(line) && definition
+}
+
+// (blank lines, rest) of `i`, blank lines separate blocks but aren't one
+pub fn split_blank_lines(i: &str) -> (&str, &str) {
+    let mut rest = i;
+    while let Ok((next, line)) = parse_line(rest) {
+        if !is_blank(line) {
+            break;
+        }
+        rest = next;
+    }
+    (&i[..i.len() - rest.len()], rest)
+}
+
+pub fn parse_markdown_block(i: &str) -> IResult<&str, Markdown> {
+    parse_markdown_block_with(i, &ParserOptions::default())
+}
+
+// dispatch on the first byte of the line, anything that does not open
+// a block (or fails to) is parsed as a paragraph
+pub fn parse_markdown_block_with<'a>(
+    i: &'a str,
+    options: &ParserOptions,
+) -> IResult<&'a str, Markdown> {
+    match parse_block_start_with(i, options) {
+        // a nested block went over the depth limit, falling back to text would hide that
+        Err(Failure(e)) => Err(Failure(e)),
+        // a table's header, a setext heading's text or a definition's term would parse as
+        // a paragraph too, so they are tried first
+        Err(_) => alt((
+            map(|i| parse_block_table_with(i, options), Markdown::BlockTable),
+            map(|i| parse_table_with(i, options), Markdown::Table),
+            map(|i| parse_setext_heading_with(i, options), |e| Markdown::Heading(e.0, e.1)),
+            map(|i| parse_definition_list_with(i, options), Markdown::DefinitionList),
+            map(|i| parse_paragraph_with(i, options), Markdown::Paragraph),
+        ))(i),
+        ok => ok,
+    }
+}
+
+// the block the line at `i` opens by its first byte, an Error if there is none
+fn parse_block_start_with<'a>(i: &'a str, options: &ParserOptions) -> IResult<&'a str, Markdown> {
+    let rule = |i| map(parse_thematic_break, |_| Markdown::Rule)(i);
+    let ordered = |i| {
+        map(
+            |i| parse_ordered_list_with(i, options),
+            |(items, info)| Markdown::OrderedList(items, info),
+        )(i)
+    };
+    let container = |i| {
+        map(
+            |i| parse_container_with(i, options),
+            |(name, attributes, blocks)| Markdown::Container(name, attributes, blocks),
+        )(i)
+    };
+    let block_table = |i| map(|i| parse_block_table_with(i, options), Markdown::BlockTable)(i);
+    // - - - is a rule, not a list of lists
+    let unordered = |i| {
+        alt((
+            rule,
+            map(
+                |i| parse_unordered_list_with(i, options),
+                |(items, info)| Markdown::UnorderedList(items, info),
+            ),
+        ))(i)
+    };
+    match i.as_bytes().first() {
+        Some(b'#') => {
+            map(|i| parse_header_with(i, options), |e| Markdown::Heading(e.0, e.1))(i)
+        }
+        Some(b'0'..=b'9') => ordered(i),
+        // a multiline table starts with a rule, a grid table with something like a bullet
+        Some(b'-') | Some(b'+') => alt((block_table, unordered))(i),
+        Some(b'*') => unordered(i),
+        Some(b'_') => rule(i),
+        Some(b'>') => alt((
+            map(
+                |i| parse_admonition_with(i, options),
+                |(kind, title, body)| Markdown::Admonition { kind, title, body },
+            ),
+            map(|i| parse_quote_with(i, options), |(body, cite)| Markdown::Quote { body, cite }),
+        ))(i),
+        Some(b'<') => alt((
+            map(parse_comment_block, Markdown::Comment),
+            map(parse_html_block, Markdown::Html),
+        ))(i),
+        Some(b'$') => map(parse_math_block, Markdown::MathBlock)(i),
+        Some(b':') if options.directives => alt((|i| parse_directive_with(i, options), container))(i),
+        Some(b':') => container(i),
+        Some(b'[') => alt((
+            map(parse_toc_marker, |_| Markdown::TocMarker),
+            map(
+                |i| parse_footnote_definition_with(i, options),
+                |(label, blocks)| Markdown::FootnoteDefinition(label, blocks),
+            ),
+        ))(i),
+        Some(b' ') | Some(b'\t') => match i.trim_start_matches(' ').as_bytes().first() {
+            _ if is_code_indent(i) => map(parse_indented_code_block, |code| {
+                Markdown::CodeBlock(code, FenceInfo::default())
+            })(i),
+            Some(b'0'..=b'9') => ordered(i),
+            Some(b'-') | Some(b'*') | Some(b'+') => unordered(i),
+            Some(b'_') => rule(i),
+            _ => parse_code_block_markdown(i),
+        },
+        Some(b'`') | Some(b'~') => parse_code_block_markdown(i),
+        Some(b'!') | Some(b'{') if options.includes => map(parse_include, Markdown::Include)(i),
+        _ => Err(Error((i, ErrorKind::Alt))),
+    }
+}
+
+// the blocks at `i` with the blank lines around them
+fn parse_blocks_with<'a>(i: &'a str, options: &ParserOptions) -> IResult<&'a str, Blocks> {
+    let (_, next) = split_blank_lines(i);
+    let (mut rest, first) = parse_markdown_block_with(next, options)?;
+    let mut nodes = first.node_count();
+    check_node_count(i, nodes, options)?;
+    let mut blocks = vec![first];
+    let mut separated = false;
+    loop {
+        let (blank, next) = split_blank_lines(rest);
+        rest = next;
+        match parse_markdown_block_with(next, options) {
+            Ok((after, block)) => {
+                nodes += block.node_count();
+                check_node_count(i, nodes, options)?;
+                separated |= !blank.is_empty();
+                blocks.push(block);
+                rest = after;
+            }
+            Err(Failure(e)) => return Err(Failure(e)),
+            Err(_) => break,
+        }
+    }
+    Ok((rest, (blocks, separated)))
+}
+
+pub fn parse_markdown(i: &str) -> IResult<&str, Vec<Markdown>> {
+    parse_markdown_with(i, &ParserOptions::default())
+}
+
+pub fn parse_markdown_with<'a>(
+    i: &'a str,
+    options: &ParserOptions,
+) -> IResult<&'a str, Vec<Markdown>> {
+    map(|i| parse_blocks_with(i, options), |(blocks, _)| blocks)(i)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseError {
+    // (size, limit) in bytes
+    InputTooLarge(usize, usize),
+    // limit
+    TooManyNodes(usize),
+    // limit
+    TooDeep(usize),
+    // byte offset of the first line no block parser accepted
+    Unparsed(usize),
+    // ParserOptions::cancel was set
+    Cancelled,
+    // byte offset of the first invalid sequence, only with InvalidUtf8::Error
+    InvalidUtf8(usize),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::InputTooLarge(size, limit) => {
+                write!(f, "input is {} bytes, limit is {}", size, limit)
+            }
+            ParseError::TooManyNodes(limit) => write!(f, "document has more than {} nodes", limit),
+            ParseError::TooDeep(limit) => write!(f, "document is nested deeper than {}", limit),
+            ParseError::Unparsed(offset) => write!(f, "could not parse input at byte {}", offset),
+            ParseError::Cancelled => write!(f, "parse was cancelled"),
+            ParseError::InvalidUtf8(offset) => write!(f, "invalid UTF-8 at byte {}", offset),
+        }
+    }
 }
 
-pub fn parse_markdown(i: &str) -> IResult<&str, Vec<Markdown>> {
-    many1(alt((
-        map(parse_header, |e| Markdown::Heading(e.0, e.1)),
-        map(parse_ordered_list, |e| Markdown::OrderedList(e)),
-        map(parse_unordered_list, |e| Markdown::UnorderedList(e)),
-        map(parse_quote, |e| Markdown::Quote(e)),
-        map(parse_code_block, |(language, code)| {
-            let mut lang = None;
-            let language = language.trim();
-            if language != "" {
-                lang = Some(String::from(language));
-            }
-            Markdown::CodeBlock(code.to_string(), lang)
-        }),
-        map(parse_markdown_text, |e| Markdown::Text(e)),
-    )))(i)
-}
+impl error::Error for ParseError {}
+
+impl Markdown {
+    // number of nodes in this block, the block itself included
+    pub fn node_count(&self) -> usize {
+        let blocks = |blocks: &[Markdown]| blocks.iter().map(Markdown::node_count).sum::<usize>();
+        1 + match self {
+            Markdown::Heading(_, text) | Markdown::Paragraph(text) => text_node_count(text),
+            Markdown::OrderedList(items, _) | Markdown::UnorderedList(items, _) => {
+                items.iter().map(|item| 1 + blocks(&item.blocks)).sum()
+            }
+            Markdown::Quote { body, cite } => blocks(body) + cite.as_deref().map_or(0, text_node_count),
+            Markdown::FootnoteDefinition(_, children) | Markdown::Container(_, _, children) => {
+                blocks(children)
+            }
+            Markdown::Admonition { title, body, .. } => text_node_count(title) + blocks(body),
+            Markdown::Directive { label, body, .. } => {
+                text_node_count(label) + body.as_deref().map_or(0, blocks)
+            }
+            Markdown::Figure { caption, .. } => 1 + text_node_count(caption),
+            Markdown::CodeBlock(_, _)
+            | Markdown::Rule
+            | Markdown::TocMarker
+            | Markdown::Include(_)
+            | Markdown::Html(_)
+            | Markdown::Comment(_)
+            | Markdown::MathBlock(_) => 0,
+            Markdown::Table(table) => {
+                table.cells().map(|cell| 1 + text_node_count(cell)).sum::<usize>()
+                    + table.caption.as_deref().map_or(0, text_node_count)
+            }
+            Markdown::BlockTable(table) => {
+                table.cells().map(|cell| 1 + blocks(cell)).sum::<usize>()
+                    + table.caption.as_deref().map_or(0, text_node_count)
+            }
+            Markdown::DefinitionList(entries) => {
+                definition_texts(entries).map(|text| 1 + text_node_count(text)).sum()
+            }
+        }
+    }
+
+    // levels of nodes below and including this block
+    fn depth(&self) -> usize {
+        let blocks = |blocks: &[Markdown]| blocks.iter().map(Markdown::depth).max().unwrap_or(0);
+        1 + match self {
+            Markdown::Heading(_, text) | Markdown::Paragraph(text) => text_depth(text),
+            Markdown::OrderedList(items, _) | Markdown::UnorderedList(items, _) => {
+                items.iter().map(|item| 1 + blocks(&item.blocks)).max().unwrap_or(0)
+            }
+            Markdown::Quote { body, cite } => {
+                blocks(body).max(cite.as_deref().map_or(0, text_depth))
+            }
+            Markdown::FootnoteDefinition(_, children) | Markdown::Container(_, _, children) => {
+                blocks(children)
+            }
+            Markdown::Admonition { title, body, .. } => {
+                blocks(body).max(text_depth(title))
+            }
+            Markdown::Directive { label, body, .. } => {
+                body.as_deref().map_or(0, blocks).max(text_depth(label))
+            }
+            Markdown::Figure { caption, .. } => text_depth(caption).max(1),
+            Markdown::CodeBlock(_, _)
+            | Markdown::Rule
+            | Markdown::TocMarker
+            | Markdown::Include(_)
+            | Markdown::Html(_)
+            | Markdown::Comment(_)
+            | Markdown::MathBlock(_) => 0,
+            Markdown::Table(table) => table
+                .cells()
+                .map(|cell| 1 + text_depth(cell))
+                .max()
+                .unwrap_or(0)
+                .max(table.caption.as_deref().map_or(0, text_depth)),
+            Markdown::BlockTable(table) => table
+                .cells()
+                .map(|cell| 1 + blocks(cell))
+                .max()
+                .unwrap_or(0)
+                .max(table.caption.as_deref().map_or(0, text_depth)),
+            Markdown::DefinitionList(entries) => definition_texts(entries)
+                .map(|text| 1 + text_depth(text))
+                .max()
+                .unwrap_or(0),
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Markdown::Heading(_, _) => "Heading",
+            Markdown::OrderedList(_, _) => "OrderedList",
+            Markdown::UnorderedList(_, _) => "UnorderedList",
+            Markdown::Quote { .. } => "Quote",
+            Markdown::CodeBlock(_, _) => "CodeBlock",
+            Markdown::Table(_) => "Table",
+            Markdown::BlockTable(_) => "BlockTable",
+            Markdown::Rule => "Rule",
+            Markdown::TocMarker => "TocMarker",
+            Markdown::Include(_) => "Include",
+            Markdown::Html(_) => "Html",
+            Markdown::Comment(_) => "Comment",
+            Markdown::MathBlock(_) => "MathBlock",
+            Markdown::DefinitionList(_) => "DefinitionList",
+            Markdown::FootnoteDefinition(_, _) => "FootnoteDefinition",
+            Markdown::Admonition { .. } => "Admonition",
+            Markdown::Figure { .. } => "Figure",
+            Markdown::Container(_, _, _) => "Container",
+            Markdown::Directive { .. } => "Directive",
+            Markdown::Paragraph(_) => "Paragraph",
+        }
+    }
+
+    // adds this block and everything in it to `counts`, keyed by kind()
+    fn count_kinds(&self, counts: &mut BTreeMap<&'static str, usize>) {
+        match self {
+            Markdown::Heading(_, text) | Markdown::Paragraph(text) => {
+                count_inline_kinds(text, counts);
+            }
+            Markdown::OrderedList(items, _) | Markdown::UnorderedList(items, _) => {
+                for block in items.iter().flat_map(|item| &item.blocks) {
+                    block.count_kinds(counts);
+                }
+                *counts.entry("ListItem").or_insert(0) += items.len();
+            }
+            Markdown::Quote { body, cite } => {
+                for block in body {
+                    block.count_kinds(counts);
+                }
+                count_inline_kinds(cite.iter().flatten(), counts);
+            }
+            Markdown::FootnoteDefinition(_, children) | Markdown::Container(_, _, children) => {
+                for block in children {
+                    block.count_kinds(counts);
+                }
+            }
+            Markdown::Admonition { title, body, .. } => {
+                count_inline_kinds(title, counts);
+                for block in body {
+                    block.count_kinds(counts);
+                }
+            }
+            Markdown::Directive { label, body, .. } => {
+                count_inline_kinds(label, counts);
+                for block in body.iter().flatten() {
+                    block.count_kinds(counts);
+                }
+            }
+            Markdown::Figure { image, caption } => {
+                count_inline_kinds(std::iter::once(image).chain(caption), counts);
+            }
+            Markdown::CodeBlock(_, _)
+            | Markdown::Rule
+            | Markdown::TocMarker
+            | Markdown::Include(_)
+            | Markdown::Html(_)
+            | Markdown::Comment(_)
+            | Markdown::MathBlock(_) => {}
+            Markdown::Table(table) => {
+                for cell in table.cells() {
+                    count_inline_kinds(cell, counts);
+                    *counts.entry("TableCell").or_insert(0) += 1;
+                }
+                count_inline_kinds(table.caption.iter().flatten(), counts);
+            }
+            Markdown::BlockTable(table) => {
+                for cell in table.cells() {
+                    for block in cell {
+                        block.count_kinds(counts);
+                    }
+                    *counts.entry("TableCell").or_insert(0) += 1;
+                }
+                count_inline_kinds(table.caption.iter().flatten(), counts);
+            }
+            Markdown::DefinitionList(entries) => {
+                count_inline_kinds(definition_texts(entries).flatten(), counts);
+            }
+        }
+        *counts.entry(self.kind()).or_insert(0) += 1;
+    }
+
+    // every inline element in this block and the blocks inside it, in document order,
+    // spans before the elements inside them
+    pub fn inlines(&self) -> Vec<&MarkdownInLine> {
+        self.outer_inlines().into_iter().flat_map(MarkdownInLine::nested).collect()
+    }
+
+    // like inlines(), without the elements inside spans
+    fn outer_inlines(&self) -> Vec<&MarkdownInLine> {
+        match self {
+            Markdown::Heading(_, text) | Markdown::Paragraph(text) => text.iter().collect(),
+            Markdown::OrderedList(items, _) | Markdown::UnorderedList(items, _) => {
+                items.iter().flat_map(|item| &item.blocks).flat_map(Markdown::outer_inlines).collect()
+            }
+            Markdown::Quote { body, cite } => {
+                body.iter().flat_map(Markdown::outer_inlines).chain(cite.iter().flatten()).collect()
+            }
+            Markdown::FootnoteDefinition(_, children) | Markdown::Container(_, _, children) => {
+                children.iter().flat_map(Markdown::outer_inlines).collect()
+            }
+            Markdown::Admonition { title, body, .. } => {
+                title.iter().chain(body.iter().flat_map(Markdown::outer_inlines)).collect()
+            }
+            Markdown::Directive { label, body, .. } => {
+                label.iter().chain(body.iter().flatten().flat_map(Markdown::outer_inlines)).collect()
+            }
+            Markdown::Figure { image, caption } => std::iter::once(image).chain(caption).collect(),
+            Markdown::CodeBlock(_, _)
+            | Markdown::Rule
+            | Markdown::TocMarker
+            | Markdown::Include(_)
+            | Markdown::Html(_)
+            | Markdown::Comment(_)
+            | Markdown::MathBlock(_) => vec![],
+            Markdown::Table(table) => {
+                table.cells().flatten().chain(table.caption.iter().flatten()).collect()
+            }
+            Markdown::BlockTable(table) => table
+                .cells()
+                .flatten()
+                .flat_map(Markdown::outer_inlines)
+                .chain(table.caption.iter().flatten())
+                .collect(),
+            Markdown::DefinitionList(entries) => definition_texts(entries).flatten().collect(),
+        }
+    }
+
+    // calls `f` with every inline element like inlines(), but mutably
+    pub fn for_each_inline_mut(&mut self, f: &mut dyn FnMut(&mut MarkdownInLine)) {
+        self.for_each_outer_inline_mut(&mut |inline| inline.for_each_mut(f));
+    }
+
+    fn for_each_outer_inline_mut(&mut self, f: &mut dyn FnMut(&mut MarkdownInLine)) {
+        if let Markdown::Figure { image, .. } = self {
+            f(image);
+        }
+        self.for_each_text_mut(&mut |text| text.iter_mut().for_each(&mut *f));
+    }
+
+    // calls `f` with every run of text in this block and the blocks inside it, each of
+    // them a heading, paragraph, table cell, caption, title or label
+    pub fn for_each_text_mut(&mut self, f: &mut dyn FnMut(&mut MarkdownText)) {
+        match self {
+            Markdown::Heading(_, text) | Markdown::Paragraph(text) => f(text),
+            Markdown::OrderedList(items, _) | Markdown::UnorderedList(items, _) => {
+                for block in items.iter_mut().flat_map(|item| &mut item.blocks) {
+                    block.for_each_text_mut(f);
+                }
+            }
+            Markdown::Quote { body, cite } => {
+                for block in body {
+                    block.for_each_text_mut(f);
+                }
+                cite.iter_mut().for_each(f);
+            }
+            Markdown::FootnoteDefinition(_, children) | Markdown::Container(_, _, children) => {
+                for block in children {
+                    block.for_each_text_mut(f);
+                }
+            }
+            Markdown::Admonition { title, body, .. } => {
+                f(title);
+                for block in body {
+                    block.for_each_text_mut(f);
+                }
+            }
+            Markdown::Directive { label, body, .. } => {
+                f(label);
+                for block in body.iter_mut().flatten() {
+                    block.for_each_text_mut(f);
+                }
+            }
+            Markdown::Figure { caption, .. } => f(caption),
+            Markdown::CodeBlock(_, _)
+            | Markdown::Rule
+            | Markdown::TocMarker
+            | Markdown::Include(_)
+            | Markdown::Html(_)
+            | Markdown::Comment(_)
+            | Markdown::MathBlock(_) => {}
+            Markdown::Table(table) => table
+                .header
+                .iter_mut()
+                .chain(table.rows.iter_mut().flatten())
+                .chain(table.caption.iter_mut())
+                .for_each(f),
+            Markdown::BlockTable(table) => {
+                for block in table.cells_mut().flatten() {
+                    block.for_each_text_mut(f);
+                }
+                table.caption.iter_mut().for_each(f);
+            }
+            Markdown::DefinitionList(entries) => {
+                for (term, definitions) in entries {
+                    std::iter::once(term).chain(definitions).for_each(&mut *f);
+                }
+            }
+        }
+    }
+
+    // calls `f` with this block and every block inside it, each before its children
+    pub fn for_each_block_mut(&mut self, f: &mut dyn FnMut(&mut Markdown)) {
+        f(self);
+        match self {
+            Markdown::OrderedList(items, _) | Markdown::UnorderedList(items, _) => {
+                for block in items.iter_mut().flat_map(|item| &mut item.blocks) {
+                    block.for_each_block_mut(f);
+                }
+            }
+            Markdown::Quote { body: children, .. }
+            | Markdown::FootnoteDefinition(_, children)
+            | Markdown::Container(_, _, children)
+            | Markdown::Admonition { body: children, .. }
+            | Markdown::Directive { body: Some(children), .. } => {
+                for block in children {
+                    block.for_each_block_mut(f);
+                }
+            }
+            Markdown::BlockTable(table) => {
+                for block in table.cells_mut().flatten() {
+                    block.for_each_block_mut(f);
+                }
+            }
+            Markdown::Heading(_, _)
+            | Markdown::Paragraph(_)
+            | Markdown::Figure { .. }
+            | Markdown::CodeBlock(_, _)
+            | Markdown::Rule
+            | Markdown::TocMarker
+            | Markdown::Include(_)
+            | Markdown::Html(_)
+            | Markdown::Comment(_)
+            | Markdown::MathBlock(_)
+            | Markdown::Table(_)
+            | Markdown::DefinitionList(_)
+            | Markdown::Directive { body: None, .. } => {}
+        }
+    }
+}
+
+// inline nodes in `text`, the ones inside spans included
+fn text_node_count(text: &[MarkdownInLine]) -> usize {
+    text.iter().map(|inline| 1 + text_node_count(inline.children())).sum()
+}
+
+// levels of inline nodes in `text`, 0 if it is empty
+fn text_depth(text: &[MarkdownInLine]) -> usize {
+    text.iter().map(|inline| 1 + text_depth(inline.children())).max().unwrap_or(0)
+}
+
+fn count_inline_kinds<'a>(
+    text: impl IntoIterator<Item = &'a MarkdownInLine>,
+    counts: &mut BTreeMap<&'static str, usize>,
+) {
+    for inline in text.into_iter().flat_map(MarkdownInLine::nested) {
+        *counts.entry(inline.kind()).or_insert(0) += 1;
+    }
+}
+
+// every term followed by its definitions
+fn definition_texts(
+    entries: &[(MarkdownText, Vec<MarkdownText>)],
+) -> impl Iterator<Item = &MarkdownText> {
+    entries.iter().flat_map(|(term, definitions)| std::iter::once(term).chain(definitions))
+}
+
+impl MarkdownInLine {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            MarkdownInLine::Link(_, _, _) => "Link",
+            MarkdownInLine::Image(_, _, _) => "Image",
+            MarkdownInLine::InlineCode(_, _) => "InlineCode",
+            MarkdownInLine::Bold(_) => "Bold",
+            MarkdownInLine::Italic(_) => "Italic",
+            MarkdownInLine::Strikethrough(_) => "Strikethrough",
+            MarkdownInLine::Highlight(_) => "Highlight",
+            MarkdownInLine::Subscript(_) => "Subscript",
+            MarkdownInLine::Superscript(_) => "Superscript",
+            MarkdownInLine::Plain(_) => "Plain",
+            MarkdownInLine::HardBreak => "HardBreak",
+            MarkdownInLine::SoftBreak => "SoftBreak",
+            MarkdownInLine::FootnoteRef(_) => "FootnoteRef",
+            MarkdownInLine::InlineFootnote(_) => "InlineFootnote",
+            MarkdownInLine::Html(_) => "Html",
+            MarkdownInLine::Emoji(_) => "Emoji",
+            MarkdownInLine::Math(_) => "Math",
+            MarkdownInLine::WikiLink { .. } => "WikiLink",
+        }
+    }
+
+    pub fn inline_kind(&self) -> InlineKind {
+        match self {
+            MarkdownInLine::Link(_, _, _) => InlineKind::Link,
+            MarkdownInLine::Image(_, _, _) => InlineKind::Image,
+            MarkdownInLine::InlineCode(_, _) => InlineKind::InlineCode,
+            MarkdownInLine::Bold(_) => InlineKind::Bold,
+            MarkdownInLine::Italic(_) => InlineKind::Italic,
+            MarkdownInLine::Strikethrough(_) => InlineKind::Strikethrough,
+            MarkdownInLine::Highlight(_) => InlineKind::Highlight,
+            MarkdownInLine::Subscript(_) => InlineKind::Subscript,
+            MarkdownInLine::Superscript(_) => InlineKind::Superscript,
+            MarkdownInLine::Plain(_) => InlineKind::Plain,
+            MarkdownInLine::HardBreak => InlineKind::HardBreak,
+            MarkdownInLine::SoftBreak => InlineKind::SoftBreak,
+            MarkdownInLine::FootnoteRef(_) => InlineKind::FootnoteRef,
+            MarkdownInLine::InlineFootnote(_) => InlineKind::InlineFootnote,
+            MarkdownInLine::Html(_) => InlineKind::Html,
+            MarkdownInLine::Emoji(_) => InlineKind::Emoji,
+            MarkdownInLine::Math(_) => InlineKind::Math,
+            MarkdownInLine::WikiLink { .. } => InlineKind::WikiLink,
+        }
+    }
+
+    // the elements inside a span or a link's text, empty for everything else
+    pub fn children(&self) -> &[MarkdownInLine] {
+        match self {
+            MarkdownInLine::Bold(text)
+            | MarkdownInLine::Italic(text)
+            | MarkdownInLine::Strikethrough(text)
+            | MarkdownInLine::Highlight(text)
+            | MarkdownInLine::Subscript(text)
+            | MarkdownInLine::Superscript(text)
+            | MarkdownInLine::Link(text, _, _)
+            | MarkdownInLine::InlineFootnote(text) => text,
+            MarkdownInLine::Image(_, _, _)
+            | MarkdownInLine::InlineCode(_, _)
+            | MarkdownInLine::Plain(_)
+            | MarkdownInLine::HardBreak
+            | MarkdownInLine::SoftBreak
+            | MarkdownInLine::FootnoteRef(_)
+            | MarkdownInLine::Html(_)
+            | MarkdownInLine::Emoji(_)
+            | MarkdownInLine::Math(_)
+            | MarkdownInLine::WikiLink { .. } => &[],
+        }
+    }
+
+    pub fn children_mut(&mut self) -> &mut [MarkdownInLine] {
+        match self {
+            MarkdownInLine::Bold(text)
+            | MarkdownInLine::Italic(text)
+            | MarkdownInLine::Strikethrough(text)
+            | MarkdownInLine::Highlight(text)
+            | MarkdownInLine::Subscript(text)
+            | MarkdownInLine::Superscript(text)
+            | MarkdownInLine::Link(text, _, _)
+            | MarkdownInLine::InlineFootnote(text) => text,
+            MarkdownInLine::Image(_, _, _)
+            | MarkdownInLine::InlineCode(_, _)
+            | MarkdownInLine::Plain(_)
+            | MarkdownInLine::HardBreak
+            | MarkdownInLine::SoftBreak
+            | MarkdownInLine::FootnoteRef(_)
+            | MarkdownInLine::Html(_)
+            | MarkdownInLine::Emoji(_)
+            | MarkdownInLine::Math(_)
+            | MarkdownInLine::WikiLink { .. } => &mut [],
+        }
+    }
+
+    // this element and every element inside it, each before its children
+    pub fn nested(&self) -> Vec<&MarkdownInLine> {
+        std::iter::once(self).chain(self.children().iter().flat_map(MarkdownInLine::nested)).collect()
+    }
+
+    // calls `f` with this element and then with every element inside it,
+    // `f` may replace the element it is given
+    pub fn for_each_mut(&mut self, f: &mut dyn FnMut(&mut MarkdownInLine)) {
+        f(self);
+        for child in self.children_mut() {
+            child.for_each_mut(f);
+        }
+    }
+}
+
+// the text of `text` without any markup, e.g. for comparing headings
+pub fn plain_text(text: &MarkdownText) -> String {
+    let mut plain = String::new();
+    for inline in text {
+        match inline {
+            MarkdownInLine::Image(s, _, _)
+            | MarkdownInLine::InlineCode(s, _)
+            | MarkdownInLine::Math(s)
+            | MarkdownInLine::Plain(s) => plain.push_str(s),
+            MarkdownInLine::Link(text, _, _)
+            | MarkdownInLine::Bold(text)
+            | MarkdownInLine::Italic(text)
+            | MarkdownInLine::Strikethrough(text)
+            | MarkdownInLine::Highlight(text)
+            | MarkdownInLine::Subscript(text)
+            | MarkdownInLine::Superscript(text) => plain.push_str(&plain_text(text)),
+            MarkdownInLine::HardBreak | MarkdownInLine::SoftBreak => plain.push('\n'),
+            MarkdownInLine::WikiLink { target, alias } => {
+                plain.push_str(alias.as_ref().unwrap_or(target))
+            }
+            MarkdownInLine::Emoji(name) => match emoji(name) {
+                Some(emoji) => plain.push_str(emoji),
+                None => plain.push_str(&format!(":{}:", name)),
+            },
+            // notes are not part of the text they are attached to, tags are markup
+            MarkdownInLine::FootnoteRef(_)
+            | MarkdownInLine::InlineFootnote(_)
+            | MarkdownInLine::Html(_) => {}
+        }
+    }
+    plain
+}
+
+// turns \r\n and lone \r into \n, only allocating if there are any
+pub fn normalize_line_endings(i: &str) -> Cow<'_, str> {
+    if !i.contains('\r') {
+        return Cow::Borrowed(i);
+    }
+    Cow::Owned(i.replace("\r\n", "\n").replace('\r', "\n"))
+}
+
+// collected by parse_with_stats
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParseStats {
+    // Markdown::kind() / MarkdownInLine::kind() plus "ListItem" and "TableCell" -> count
+    pub nodes: BTreeMap<&'static str, usize>,
+    pub max_depth: usize,
+    pub bytes: usize,
+    // checking limits and preparing the input
+    pub prepare_time: Duration,
+    pub block_time: Duration,
+}
+
+// Parses a whole document block by block, enforcing `options.limits`
+// and checking `options.cancel` before every block.
+// Unlike parse_markdown the last line does not need a trailing newline.
+pub fn parse(i: &str, options: &ParserOptions) -> Result<Vec<Markdown>, ParseError> {
+    #[cfg(feature = "tracing")]
+    let span = tracing::debug_span!(
+        "parse",
+        bytes = i.len(),
+        blocks = tracing::field::Empty,
+        nodes = tracing::field::Empty,
+    );
+    #[cfg(feature = "tracing")]
+    let _enter = span.enter();
+
+    let result = parse_document(i, options, None);
+
+    #[cfg(feature = "tracing")]
+    match &result {
+        Ok(blocks) => {
+            span.record("blocks", blocks.len());
+            span.record("nodes", blocks.iter().map(Markdown::node_count).sum::<usize>());
+        }
+        Err(error) => tracing::debug!(%error, "parse failed"),
+    }
+
+    result
+}
+
+// Same as parse, but also returns statistics about the document and the time each phase took
+pub fn parse_with_stats(
+    i: &str,
+    options: &ParserOptions,
+) -> Result<(Vec<Markdown>, ParseStats), ParseError> {
+    let mut stats = ParseStats::default();
+    let blocks = parse_document(i, options, Some(&mut stats))?;
+    Ok((blocks, stats))
+}
+
+// Same as parse, for input that is not known to be UTF-8 (read from a file for example).
+// A leading byte order mark is skipped and invalid sequences are handled according to
+// `options.invalid_utf8`.
+pub fn parse_bytes(i: &[u8], options: &ParserOptions) -> Result<Vec<Markdown>, ParseError> {
+    check_input_size(i.len(), options)?;
+
+    const BOM: &[u8] = b"\xEF\xBB\xBF";
+    let (offset, bytes) = match i.strip_prefix(BOM) {
+        Some(bytes) => (BOM.len(), bytes),
+        None => (0, i),
+    };
+    let text = match options.invalid_utf8 {
+        InvalidUtf8::Replace => String::from_utf8_lossy(bytes),
+        InvalidUtf8::Error => match str::from_utf8(bytes) {
+            Ok(text) => Cow::Borrowed(text),
+            Err(e) => return Err(ParseError::InvalidUtf8(offset + e.valid_up_to())),
+        },
+    };
+    parse(&text, options)
+}
+
+fn check_input_size(len: usize, options: &ParserOptions) -> Result<(), ParseError> {
+    match options.limits.max_input_size {
+        Some(limit) if len > limit => Err(ParseError::InputTooLarge(len, limit)),
+        _ => Ok(()),
+    }
+}
+
+fn parse_document(
+    i: &str,
+    options: &ParserOptions,
+    mut stats: Option<&mut ParseStats>,
+) -> Result<Vec<Markdown>, ParseError> {
+    let start = Instant::now();
+    let limits = &options.limits;
+    check_input_size(i.len(), options)?;
+
+    let i = i.strip_prefix('\u{FEFF}').unwrap_or(i);
+    let mut input = normalize_line_endings(i);
+    if !input.is_empty() && !input.ends_with('\n') {
+        input.to_mut().push('\n');
+    }
+    let prepared = Instant::now();
+
+    let mut blocks = Vec::new();
+    let mut nodes = 0;
+    let mut rest: &str = &input;
+    loop {
+        rest = split_blank_lines(rest).1;
+        if rest.is_empty() {
+            break;
+        }
+        if options.is_cancelled() {
+            return Err(ParseError::Cancelled);
+        }
+
+        let (next, block) = match parse_markdown_block_with(rest, options) {
+            Ok(result) => result,
+            // a parser only fails instead of erroring when it runs out of nodes or depth
+            Err(Failure((_, ErrorKind::Count))) => {
+                return Err(ParseError::TooManyNodes(limits.max_nodes.unwrap_or(0)))
+            }
+            Err(Failure(_)) => return Err(ParseError::TooDeep(limits.max_depth.unwrap_or(0))),
+            Err(_) => return Err(ParseError::Unparsed(input.len() - rest.len())),
+        };
+
+        nodes += block.node_count();
+        if let Some(limit) = limits.max_nodes {
+            if nodes > limit {
+                return Err(ParseError::TooManyNodes(limit));
+            }
+        }
+        let depth = block.depth();
+        if let Some(limit) = limits.max_depth {
+            if depth > limit {
+                return Err(ParseError::TooDeep(limit));
+            }
+        }
+
+        if let Some(stats) = stats.as_mut() {
+            block.count_kinds(&mut stats.nodes);
+            stats.max_depth = stats.max_depth.max(depth);
+        }
+        blocks.push(block);
+        rest = next;
+    }
+
+    if let Some(stats) = stats {
+        stats.bytes = i.len();
+        stats.prepare_time = prepared - start;
+        stats.block_time = prepared.elapsed();
+    }
+    Ok(blocks)
+}
+
+// Credit:
+// Most of tests are copied and edited from HGHimself/prose
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::options::Limits;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use nom::Err::Error;
+
+    // the lines of a paragraph, joined by SoftBreaks
+    fn lines(s: &str) -> MarkdownText {
+        let mut text = Vec::new();
+        for (n, line) in s.split('\n').enumerate() {
+            if n > 0 {
+                text.push(MarkdownInLine::SoftBreak);
+            }
+            text.push(MarkdownInLine::Plain(String::from(line)));
+        }
+        text
+    }
+
+    #[test]
+    fn test_parse_bold() {
+        assert_eq!(parse_bold("**bold text**"), Ok(("", "bold text")));
+        assert_eq!(parse_bold("**not bold"), Err(Error(("", ErrorKind::Tag))));
+        assert_eq!(
+            parse_bold("not bold**"),
+            Err(Error(("not bold**", ErrorKind::Tag)))
+        );
+        assert_eq!(
+            parse_bold("another not bold"),
+            Err(Error(("another not bold", ErrorKind::Tag)))
+        );
+        assert_eq!(parse_bold("**a*b** c"), Ok((" c", "a*b")));
+        assert_eq!(parse_bold("**2 * 3 = *6*** c"), Ok(("* c", "2 * 3 = *6")));
+        assert_eq!(parse_bold("**a*b*"), Err(Error(("", ErrorKind::Tag))));
+        assert_eq!(parse_bold("****"), Err(Error(("**", ErrorKind::IsNot))));
+        assert_eq!(parse_bold("**"), Err(Error(("", ErrorKind::IsNot))));
+        assert_eq!(parse_bold("*"), Err(Error(("*", ErrorKind::Tag))));
+        assert_eq!(parse_bold(""), Err(Error(("", ErrorKind::Tag))));
+        assert_eq!(
+            parse_bold("*this is italic*"),
+            Err(Error(("*this is italic*", ErrorKind::Tag)))
+        );
+    }
+
+    #[test]
+    fn test_parse_italics() {
+        assert_eq!(
+            parse_italic("*italic text*"),
+            Ok(("", "italic text"))
+        );
+        assert_eq!(
+            parse_italic("*not italic"),
+            Err(Error(("", ErrorKind::Tag)))
+        );
+        assert_eq!(
+            parse_italic("not italic*"),
+            Err(Error(("not italic*", ErrorKind::Tag)))
+        );
+        assert_eq!(
+            parse_italic("another not italic"),
+            Err(Error(("another not italic", ErrorKind::Tag)))
+        );
+        assert_eq!(parse_italic("*"), Err(Error(("", ErrorKind::IsNot))));
+        assert_eq!(parse_italic("**"), Err(Error(("*", ErrorKind::IsNot))));
+        assert_eq!(parse_italic(""), Err(Error(("", ErrorKind::Tag))));
+        assert_eq!(
+            parse_italic("**this is bold**"),
+            Err(Error(("*this is bold**", ErrorKind::IsNot)))
+        );
+    }
+
+    #[test]
+    fn test_parse_strikethrough() {
+        assert_eq!(parse_strikethrough("~~gone~~ text"), Ok((" text", "gone")));
+        assert_eq!(parse_strikethrough("~~a ~ b~~"), Ok(("", "a ~ b")));
+        assert_eq!(parse_strikethrough("~~ gone~~"), Err(Error((" gone~~", ErrorKind::IsNot))));
+        assert_eq!(
+            parse_markdown_text("~/bin and ~~old~~ ~~~\n"),
+            Ok((
+                "",
+                vec![
+                    MarkdownInLine::Plain(String::from("~/bin and ")),
+                    MarkdownInLine::Strikethrough(vec![MarkdownInLine::Plain(String::from("old"))]),
+                    MarkdownInLine::Plain(String::from(" ~~~")),
+                ]
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_highlight() {
+        assert_eq!(parse_highlight("==marked== text"), Ok((" text", "marked")));
+        assert_eq!(parse_highlight("== a =="), Err(Error((" a ==", ErrorKind::IsNot))));
+        let options = ParserOptions { highlight: true, ..ParserOptions::default() };
+        let plain = |s: &str| MarkdownInLine::Plain(String::from(s));
+        assert_eq!(
+            parse_markdown_text_with("a == b, ==**key** point== ===\n", &options),
+            Ok(("", vec![
+                plain("a == b, "),
+                MarkdownInLine::Highlight(vec![
+                    MarkdownInLine::Bold(vec![plain("key")]),
+                    plain(" point"),
+                ]),
+                plain(" ==="),
+            ]))
+        );
+        assert_eq!(parse_markdown_text("==a==\n"), Ok(("", vec![plain("==a==")])));
+    }
+
+    #[test]
+    fn test_parse_sub_superscript() {
+        assert_eq!(parse_subscript("~2~O"), Ok(("O", "2")));
+        assert_eq!(parse_superscript("^a b^"), Err(Error((" b^", ErrorKind::Tag))));
+        assert_eq!(parse_subscript("~~a~~"), Err(Error(("~a~~", ErrorKind::Tag))));
+        let options = ParserOptions { sub_superscript: true, ..ParserOptions::default() };
+        let plain = |s: &str| MarkdownInLine::Plain(String::from(s));
+        assert_eq!(
+            parse_markdown_text_with("~~H~2~O~~ x^*n*^ ~/a ~/b 2^ ^[note]\n", &options),
+            Ok(("", vec![
+                MarkdownInLine::Strikethrough(vec![
+                    plain("H"),
+                    MarkdownInLine::Subscript(vec![plain("2")]),
+                    plain("O"),
+                ]),
+                plain(" x"),
+                MarkdownInLine::Superscript(vec![MarkdownInLine::Italic(vec![plain("n")])]),
+                plain(" ~/a ~/b 2^ "),
+                MarkdownInLine::InlineFootnote(vec![plain("note")]),
+            ]))
+        );
+        assert_eq!(parse_markdown_text("H~2~O\n"), Ok(("", vec![plain("H~2~O")])));
+    }
+
+    #[test]
+    fn test_parse_inline_code() {
+        assert_eq!(
+            parse_inline("`inline text`"),
+            Ok(("", ("inline text", None)))
+        );
+        assert_eq!(
+            parse_inline("`inline text`rust"),
+            Ok(("", ("inline text", Some("rust"))))
+        );
+        assert_eq!(
+            parse_inline("`inline text`rust\n"),
+            Ok(("\n", ("inline text", Some("rust"))))
+        );
+        assert_eq!(
+            parse_inline("`inline text`rust "),
+            Ok((" ", ("inline text", Some("rust"))))
+        );
+        assert_eq!(
+            parse_inline("`not inline"),
+            Err(Error(("", ErrorKind::Tag)))
+        );
+        assert_eq!(
+            parse_inline("not inline`"),
+            Err(Error(("not inline`", ErrorKind::Tag)))
+        );
+        assert_eq!(parse_inline("``"), Err(Error(("", ErrorKind::IsNot))));
+        assert_eq!(parse_inline("`"), Err(Error(("", ErrorKind::IsNot))));
+        assert_eq!(parse_inline(""), Err(Error(("", ErrorKind::Tag))));
+    }
+
+    #[test]
+    fn test_parse_emphasis_unicode() {
+        assert_eq!(
+            parse_markdown_text("**テスト。**テスト\n"),
+            Ok(("", vec![
+                MarkdownInLine::Bold(vec![MarkdownInLine::Plain(String::from("テスト。"))]),
+                MarkdownInLine::Plain(String::from("テスト")),
+            ]))
+        );
+        assert_eq!(
+            parse_markdown_text("これは*強調*です\n"),
+            Ok(("", vec![
+                MarkdownInLine::Plain(String::from("これは")),
+                MarkdownInLine::Italic(vec![MarkdownInLine::Plain(String::from("強調"))]),
+                MarkdownInLine::Plain(String::from("です")),
+            ]))
+        );
+        assert_eq!(
+            parse_markdown_text("*über* *ß*\n"),
+            Ok(("", vec![
+                MarkdownInLine::Italic(vec![MarkdownInLine::Plain(String::from("über"))]),
+                MarkdownInLine::Plain(String::from(" ")),
+                MarkdownInLine::Italic(vec![MarkdownInLine::Plain(String::from("ß"))]),
+            ]))
+        );
+        // U+3000 is whitespace, so neither delimiter is flanking
+        assert_eq!(
+            parse_markdown_text("*\u{3000}a\u{3000}*\n"),
+            Ok(("", vec![MarkdownInLine::Plain(String::from("*\u{3000}a\u{3000}*"))]))
+        );
+        assert_eq!(
+            parse_markdown_text("*(a)*b\n"),
+            Ok(("", vec![MarkdownInLine::Plain(String::from("*(a)*b"))]))
+        );
+        assert_eq!(
+            parse_markdown_text("*(a)*, b\n"),
+            Ok(("", vec![
+                MarkdownInLine::Italic(vec![MarkdownInLine::Plain(String::from("(a)"))]),
+                MarkdownInLine::Plain(String::from(", b")),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_code_span_runs() {
+        assert_eq!(parse_inline_code("``code with ` backtick`` x"), Ok((" x", "code with ` backtick")));
+        assert_eq!(parse_inline_code("`` `a` ``"), Ok(("", "`a`")));
+        assert_eq!(parse_inline_code("` `"), Ok(("", " ")));
+        assert_eq!(parse_inline_code("`a``b`"), Ok(("", "a``b")));
+        assert_eq!(parse_inline_code("``a`"), Err(Error(("", ErrorKind::Tag))));
+        assert_eq!(
+            parse_markdown_text("use ``a ` b`` here\n"),
+            Ok(("", vec![
+                MarkdownInLine::Plain(String::from("use ")),
+                MarkdownInLine::InlineCode(String::from("a ` b"), None),
+                MarkdownInLine::Plain(String::from(" here")),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_inline_code_suffix() {
+        assert_eq!(parse_inline_code("`foo`.bar"), Ok((".bar", "foo")));
+        assert_eq!(
+            parse_markdown_text("use `foo`.bar\n"),
+            Ok(("", vec![
+                MarkdownInLine::Plain(String::from("use ")),
+                MarkdownInLine::InlineCode(String::from("foo"), None),
+                MarkdownInLine::Plain(String::from(".bar")),
+            ]))
+        );
+        let options = ParserOptions {
+            inline_code_language: true,
+            ..ParserOptions::default()
+        };
+        assert_eq!(
+            parse_markdown_text_with("run `cargo`sh now\n", &options),
+            Ok(("", vec![
+                MarkdownInLine::Plain(String::from("run ")),
+                MarkdownInLine::InlineCode(String::from("cargo"), Some(String::from("sh"))),
+                MarkdownInLine::Plain(String::from(" now")),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_link_text() {
+        let plain = |s: &str| MarkdownInLine::Plain(String::from(s));
+        let options = ParserOptions { autolinks: true, ..ParserOptions::default() };
+        assert_eq!(
+            parse_markdown_text_with("[**bold** `link`](url) [see https://a.b <c@d.e>](f)\n", &options),
+            Ok(("", vec![
+                MarkdownInLine::Link(
+                    vec![
+                        MarkdownInLine::Bold(vec![plain("bold")]),
+                        plain(" "),
+                        MarkdownInLine::InlineCode(String::from("link"), None),
+                    ],
+                    String::from("url"),
+                    None
+                ),
+                plain(" "),
+                MarkdownInLine::Link(vec![plain("see https://a.b c@d.e")], String::from("f"), None),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_linked_image() {
+        let image = MarkdownInLine::Image(String::from("alt"), String::from("img.png"), None);
+        assert_eq!(
+            parse_markdown_text("[![alt](img.png)](https://target) [a \\] [b]](c)\n"),
+            Ok(("", vec![
+                MarkdownInLine::Link(vec![image.clone()], String::from("https://target"), None),
+                MarkdownInLine::Plain(String::from(" ")),
+                MarkdownInLine::Link(
+                    vec![MarkdownInLine::Plain(String::from("a ] [b]"))],
+                    String::from("c"),
+                    None
+                ),
+            ]))
+        );
+        let paragraph = parse_markdown("[![alt](img.png) logo](/)\n").unwrap().1;
+        assert_eq!(paragraph[0].inlines()[1], &image);
+    }
+
+    #[test]
+    fn test_parse_footnote_refs() {
+        let plain = |s: &str| MarkdownInLine::Plain(String::from(s));
+        assert_eq!(parse_footnote_ref("[^1] a"), Ok((" a", "1")));
+        assert_eq!(parse_footnote_ref("[^a b]"), Err(Error((" b]", ErrorKind::Tag))));
+        assert_eq!(
+            parse_markdown_text("x[^note] 2^3 y^[see *[a](b)*] ^\n"),
+            Ok(("", vec![
+                plain("x"),
+                MarkdownInLine::FootnoteRef(String::from("note")),
+                plain(" 2^3 y"),
+                MarkdownInLine::InlineFootnote(vec![
+                    plain("see "),
+                    MarkdownInLine::Italic(vec![MarkdownInLine::Link(vec![plain("a")], String::from("b"), None)]),
+                ]),
+                plain(" ^"),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_wiki_link() {
+        assert_eq!(parse_wiki_link("[[Page Name]] a"), Ok((" a", ("Page Name", None))));
+        assert_eq!(parse_wiki_link("[[Page#Part|see]]"), Ok(("", ("Page#Part", Some("see")))));
+        assert_eq!(parse_wiki_link("[[|alias]]"), Err(Error(("[[|alias]]", ErrorKind::Verify))));
+        let options = ParserOptions { wiki_links: true, ..ParserOptions::default() };
+        let wiki = |target: &str, alias: Option<&str>| MarkdownInLine::WikiLink {
+            target: String::from(target),
+            alias: alias.map(String::from),
+        };
+        let plain = |s: &str| MarkdownInLine::Plain(String::from(s));
+        assert_eq!(
+            parse_markdown_text_with("see [[Home]], [[Notes/Today|today]] and [a](b)\n", &options),
+            Ok(("", vec![
+                plain("see "),
+                wiki("Home", None),
+                plain(", "),
+                wiki("Notes/Today", Some("today")),
+                plain(" and "),
+                MarkdownInLine::Link(vec![plain("a")], String::from("b"), None),
+            ]))
+        );
+        assert_eq!(plain_text(&vec![wiki("Home", None), plain(" "), wiki("a", Some("b"))]), "Home b");
+    }
+
+    #[test]
+    fn test_parse_link() {
+        assert_eq!(
+            parse_link("[title](https://www.example.com)"),
+            Ok(("", ("title", "https://www.example.com", None)))
+        );
+        assert_eq!(parse_inline(""), Err(Error(("", ErrorKind::Tag))));
+        assert_eq!(parse_link("[](url)"), Ok(("", ("", "url", None))));
+        assert_eq!(parse_link("[text]()"), Ok(("", ("text", "", None))));
+        assert_eq!(parse_link("[]()"), Ok(("", ("", "", None))));
+        assert_eq!(parse_link("[text]"), Err(Error(("", ErrorKind::Tag))));
+        assert_eq!(parse_link("[a](url \"A title\")"), Ok(("", ("a", "url", Some("A title")))));
+        assert_eq!(parse_link("[a]( url  'it\"s' )"), Ok(("", ("a", "url", Some("it\"s")))));
+        assert_eq!(parse_link("[a](\"quoted\")"), Ok(("", ("a", "\"quoted\"", None))));
+        assert_eq!(parse_image("![a](i.png \"\")"), Ok(("", ("a", "i.png", Some("")))));
+        assert_eq!(
+            parse_link("[w](https://en.wikipedia.org/wiki/Rust_(programming_language)))"),
+            Ok((")", ("w", "https://en.wikipedia.org/wiki/Rust_(programming_language)", None)))
+        );
+        assert_eq!(parse_link("[a](b\\)c)"), Ok(("", ("a", "b\\)c", None))));
+        assert_eq!(parse_link("[a](b(c)"), Err(Error(("", ErrorKind::Tag))));
+        assert_eq!(parse_link("[a](<my file.md>)"), Ok(("", ("a", "my file.md", None))));
+        assert_eq!(parse_link("[a]( <b c> 'd')"), Ok(("", ("a", "b c", Some("d")))));
+        assert_eq!(parse_image("![a](<>)"), Ok(("", ("a", "", None))));
+        assert_eq!(
+            parse_image("![x](y.png \"My \\\"q\\\"\")"),
+            Ok(("", ("x", "y.png", Some("My \\\"q\\\""))))
+        );
+        assert_eq!(parse_link("[a](b (t))"), Ok(("", ("a", "b", Some("t")))));
+        // text after the title or a space in the url is no link
+        for not_link in ["[a](b \"t\"x)", "[a](b c)", "[a](b \"t)", "[a](<b)"] {
+            assert!(parse_link(not_link).is_err(), "{:?}", not_link);
+        }
+        assert_eq!(parse_markdown("[a](b c)\n"), Ok(("", vec![Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("[a](b c)"))])])));
+    }
+
+    #[test]
+    fn test_parse_image() {
+        assert_eq!(
+            parse_image("![alt text](image.jpg)"),
+            Ok(("", ("alt text", "image.jpg", None)))
+        );
+        assert_eq!(parse_inline(""), Err(Error(("", ErrorKind::Tag))));
+        assert_eq!(parse_image("![](image.jpg)"), Ok(("", ("", "image.jpg", None))));
+        assert_eq!(parse_image("![alt]()"), Ok(("", ("alt", "", None))));
+        assert_eq!(
+            parse_markdown_text("see [](url) and ![](img.png)\n"),
+            Ok(("", vec![
+                MarkdownInLine::Plain(String::from("see ")),
+                MarkdownInLine::Link(vec![], String::from("url"), None),
+                MarkdownInLine::Plain(String::from(" and ")),
+                MarkdownInLine::Image(String::new(), String::from("img.png"), None),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_plaintext() {
+        assert_eq!(
+            parse_plain("1234567890"),
+            Ok(("", String::from("1234567890")))
+        );
+        assert_eq!(
+            parse_plain("plaintext"),
+            Ok(("", String::from("plaintext")))
+        );
+        assert_eq!(
+            parse_plain("plaintext!"),
+            Ok(("", String::from("plaintext!")))
+        );
+        assert_eq!(
+            parse_plain("plaintext!["),
+            Ok(("![", String::from("plaintext")))
+        );
+        assert_eq!(
+            parse_plain("plaintext!*"),
+            Ok(("*", String::from("plaintext!")))
+        );
+        assert_eq!(
+            parse_plain("plaintext![image"),
+            Ok(("![image", String::from("plaintext")))
+        );
+        assert_eq!(
+            parse_plain("plaintext\n"),
+            Ok(("\n", String::from("plaintext")))
+        );
+        assert_eq!(
+            parse_plain("*bold text*"),
+            Err(Error(("*bold text*", ErrorKind::Not)))
+        );
+        assert_eq!(
+            parse_plain("[example](https://example.com)"),
+            Err(Error(("[example](https://example.com)", ErrorKind::Not)))
+        );
+        assert_eq!(
+            parse_plain("`codeblock for bums`"),
+            Err(Error(("`codeblock for bums`", ErrorKind::Not)))
+        );
+        assert_eq!(
+            parse_plain("![ but wait theres more](jk)"),
+            Err(Error(("![ but wait theres more](jk)", ErrorKind::Not)))
+        );
+        assert_eq!(
+            parse_plain("*italic*"),
+            Err(Error(("*italic*", ErrorKind::Not)))
+        );
+        assert_eq!(
+            parse_plain("**bold**"),
+            Err(Error(("**bold**", ErrorKind::Not)))
+        );
+        assert_eq!(
+            parse_plain("`inline code`"),
+            Err(Error(("`inline code`", ErrorKind::Not)))
+        );
+        assert_eq!(
+            parse_plain("[title](https://example.com)"),
+            Err(Error(("[title](https://example.com)", ErrorKind::Not)))
+        );
+        assert_eq!(
+            parse_plain("![alt text](image.jpg)"),
+            Err(Error(("![alt text](image.jpg)", ErrorKind::Not)))
+        );
+        assert_eq!(parse_plain(""), Err(Error(("", ErrorKind::Eof))));
+    }
+
+    #[test]
+    fn test_parse_markdown_inline() {
+        assert_eq!(
+            parse_markdown_inline("*italic*"),
+            Ok(("", MarkdownInLine::Italic(vec![MarkdownInLine::Plain(String::from("italic"))])))
+        );
+        assert_eq!(
+            parse_markdown_inline("**bold**"),
+            Ok(("", MarkdownInLine::Bold(vec![MarkdownInLine::Plain(String::from("bold"))])))
+        );
+        assert_eq!(
+            parse_markdown_inline("`inline code`python"),
+            Ok(("python", MarkdownInLine::InlineCode(String::from("inline code"), None)))
+        );
+        let options = ParserOptions {
+            inline_code_language: true,
+            ..ParserOptions::default()
+        };
+        assert_eq!(
+            parse_markdown_inline_with("`inline code`python", &options),
+            Ok(("", MarkdownInLine::InlineCode(String::from("inline code"), Some(String::from("python")))))
+        );
+        assert_eq!(
+            parse_markdown_inline("[title](https://www.example.com)"),
+            Ok((
+                "",
+                (MarkdownInLine::Link(
+                    vec![MarkdownInLine::Plain(String::from("title"))],
+                    String::from("https://www.example.com"),
+                    None,
+                ))
+            ))
+        );
+        assert_eq!(
+            parse_markdown_inline("![text](image.png)"),
+            Ok((
+                "",
+                (MarkdownInLine::Image(String::from("text"), String::from("image.png"), None))
+            ))
+        );
+        assert_eq!(
+            parse_markdown_inline("plaintext!"),
+            Ok((
+                "",
+                MarkdownInLine::Plain(String::from("plaintext!"))
+            ))
+        );
+        assert_eq!(
+            parse_markdown_inline("here is some plaintext *but what if we italicize?"),
+            Ok((
+                "*but what if we italicize?",
+                MarkdownInLine::Plain(String::from("here is some plaintext "))
+            ))
+        );
+        assert_eq!(
+            parse_markdown_inline("here is some plaintext \n*but what if we italicize?"),
+            Ok((
+                "\n*but what if we italicize?",
+                MarkdownInLine::Plain(String::from("here is some plaintext "))
+            ))
+        );
+        assert_eq!(
+            parse_markdown_inline("\n"),
+            Err(Error(("\n", ErrorKind::Tag)))
+        );
+        assert_eq!(parse_markdown_inline(""), Err(Error(("", ErrorKind::Tag))));
+    }
+
+    #[test]
+    fn test_parse_markdown_text() {
+        assert_eq!(parse_markdown_text("\n"), Ok(("", vec![])));
+        assert_eq!(
+            parse_markdown_text("here is some plaintext\n"),
+            Ok((
+                "",
+                vec![MarkdownInLine::Plain(String::from(
+                    "here is some plaintext"
+                ))]
+            ))
+        );
+        assert_eq!(
+            parse_markdown_text("here is some plaintext *but what if we italicize?*\n"),
+            Ok((
+                "",
+                vec![
+                    MarkdownInLine::Plain(String::from("here is some plaintext ")),
+                    MarkdownInLine::Italic(vec![MarkdownInLine::Plain(String::from("but what if we italicize?"))]),
+                ]
+            ))
+        );
+        assert_eq!(
+            parse_markdown_text("here is some plaintext *but what if we italicize?* I guess it doesnt **matter** in my `code`\n"),
+            Ok(("", vec![
+                MarkdownInLine::Plain(String::from("here is some plaintext ")),
+                MarkdownInLine::Italic(vec![MarkdownInLine::Plain(String::from("but what if we italicize?"))]),
+                MarkdownInLine::Plain(String::from(" I guess it doesnt ")),
+                MarkdownInLine::Bold(vec![MarkdownInLine::Plain(String::from("matter"))]),
+                MarkdownInLine::Plain(String::from(" in my ")),
+                MarkdownInLine::InlineCode(String::from("code"), None),
+            ]))
+        );
+        assert_eq!(
+            parse_markdown_text("here is some plaintext *but what if we italicize?*\n"),
+            Ok((
+                "",
+                vec![
+                    MarkdownInLine::Plain(String::from("here is some plaintext ")),
+                    MarkdownInLine::Italic(vec![MarkdownInLine::Plain(String::from("but what if we italicize?"))]),
+                ]
+            ))
+        );
+        assert_eq!(
+            parse_markdown_text("here is some plaintext *but what if we italicize?"),
+            Err(Error(("", ErrorKind::Tag)))
+        );
+        assert_eq!(
+            parse_markdown_text("here is some plaintext *but what if we italicize?\n"),
+            Ok(("", vec![MarkdownInLine::Plain(String::from(
+                "here is some plaintext *but what if we italicize?"
+            ))]))
+        );
+    }
+
+    #[test]
+    fn test_parse_emphasis_literals() {
+        assert_eq!(
+            parse_markdown_text("**a * b**\n"),
+            Ok(("", vec![MarkdownInLine::Bold(vec![MarkdownInLine::Plain(String::from("a * b"))])]))
+        );
+        assert_eq!(
+            parse_markdown_text("**a*b**\n"),
+            Ok(("", vec![MarkdownInLine::Bold(vec![MarkdownInLine::Plain(String::from("a*b"))])]))
+        );
+        assert_eq!(
+            parse_markdown_text("2 * 3 * 4 and 2*3\n"),
+            Ok(("", vec![MarkdownInLine::Plain(String::from("2 * 3 * 4 and 2*3"))]))
+        );
+        assert_eq!(
+            parse_markdown_text("2*3*4\n"),
+            Ok(("", vec![
+                MarkdownInLine::Plain(String::from("2")),
+                MarkdownInLine::Italic(vec![MarkdownInLine::Plain(String::from("3"))]),
+                MarkdownInLine::Plain(String::from("4")),
+            ]))
+        );
+        assert_eq!(
+            parse_markdown_text("**bold***italic*\n"),
+            Ok(("", vec![
+                MarkdownInLine::Bold(vec![MarkdownInLine::Plain(String::from("bold"))]),
+                MarkdownInLine::Italic(vec![MarkdownInLine::Plain(String::from("italic"))]),
+            ]))
+        );
+        assert_eq!(
+            parse_markdown_text("*a* *b*\n"),
+            Ok(("", vec![
+                MarkdownInLine::Italic(vec![MarkdownInLine::Plain(String::from("a"))]),
+                MarkdownInLine::Plain(String::from(" ")),
+                MarkdownInLine::Italic(vec![MarkdownInLine::Plain(String::from("b"))]),
+            ]))
+        );
+        assert_eq!(
+            parse_markdown_text("** not bold ** and *** and **unclosed\n"),
+            Ok(("", vec![MarkdownInLine::Plain(String::from(
+                "** not bold ** and *** and **unclosed"
+            ))]))
+        );
+        assert_eq!(
+            parse_markdown_text("**not\nbold**\n"),
+            Ok(("bold**\n", vec![MarkdownInLine::Plain(String::from("**not"))]))
+        );
+    }
+
+    #[test]
+    fn test_parse_nested_emphasis() {
+        let plain = |s: &str| MarkdownInLine::Plain(String::from(s));
+        assert_eq!(
+            parse_markdown_text("**bold with *italic* inside** and *~~[a](b)~~ `c`*\n"),
+            Ok(("", vec![
+                MarkdownInLine::Bold(vec![
+                    plain("bold with "),
+                    MarkdownInLine::Italic(vec![plain("italic")]),
+                    plain(" inside"),
+                ]),
+                plain(" and "),
+                MarkdownInLine::Italic(vec![
+                    MarkdownInLine::Strikethrough(vec![MarkdownInLine::Link(
+                        vec![MarkdownInLine::Plain(String::from("a"))],
+                        String::from("b"),
+                        None,
+                    )]),
+                    plain(" "),
+                    MarkdownInLine::InlineCode(String::from("c"), None),
+                ]),
+            ]))
+        );
+        assert_eq!(
+            parse_markdown_text("__a [b__\n"),
+            Ok(("", vec![MarkdownInLine::Bold(vec![plain("a [b")])]))
+        );
+        let paragraph = Markdown::Paragraph(parse_markdown_text("**a *b* c**\n").unwrap().1);
+        assert_eq!(paragraph.depth(), 4);
+        assert_eq!(paragraph.node_count(), 6);
+        assert_eq!(
+            paragraph.inlines().into_iter().map(MarkdownInLine::kind).collect::<Vec<_>>(),
+            vec!["Bold", "Plain", "Italic", "Plain", "Plain"]
+        );
+    }
+
+    #[test]
+    fn test_parse_autolink() {
+        let link = |text: &str, url: &str| MarkdownInLine::Link(vec![MarkdownInLine::Plain(String::from(text))], String::from(url), None);
+        assert_eq!(
+            parse_autolink("<https://example.com/a?b=c>."),
+            Ok((".", ("https://example.com/a?b=c", String::from("https://example.com/a?b=c"))))
+        );
+        assert_eq!(
+            parse_autolink("<user.name+x@mail.example.com>"),
+            Ok(("", ("user.name+x@mail.example.com", String::from("mailto:user.name+x@mail.example.com"))))
+        );
+        assert_eq!(parse_autolink("<a b>"), Err(Error((" b>", ErrorKind::Tag))));
+        assert_eq!(parse_autolink("<x:y>"), Err(Error(("<x:y>", ErrorKind::Verify))));
+        assert_eq!(
+            parse_markdown_text("see <https://example.com> or <a@b.c>, 1 < 2 <javascript:x>\n"),
+            Ok(("", vec![
+                MarkdownInLine::Plain(String::from("see ")),
+                link("https://example.com", "https://example.com"),
+                MarkdownInLine::Plain(String::from(" or ")),
+                link("a@b.c", "mailto:a@b.c"),
+                MarkdownInLine::Plain(String::from(", 1 < 2 javascript:x")),
+            ]))
+        );
+        assert_eq!(
+            parse_markdown("<https://example.com> is a link\n"),
+            Ok(("", vec![Markdown::Paragraph(vec![
+                link("https://example.com", "https://example.com"),
+                MarkdownInLine::Plain(String::from(" is a link")),
+            ])]))
+        );
+    }
+
+    #[test]
+    fn test_parse_bare_urls() {
+        let options = ParserOptions { autolinks: true, ..ParserOptions::default() };
+        let link = |text: &str, url: &str| MarkdownInLine::Link(vec![MarkdownInLine::Plain(String::from(text))], String::from(url), None);
+        let plain = |s: &str| MarkdownInLine::Plain(String::from(s));
+        assert_eq!(
+            parse_markdown_text_with("see https://a.b/c?d=e, (www.x.org/f_(g)) or http://.\n", &options),
+            Ok(("", vec![
+                plain("see "),
+                link("https://a.b/c?d=e", "https://a.b/c?d=e"),
+                plain(", ("),
+                link("www.x.org/f_(g)", "http://www.x.org/f_(g)"),
+                plain(") or http://."),
+            ]))
+        );
+        assert_eq!(
+            parse_markdown_text_with("ahttps://a.b **https://c.d**\n", &options),
+            Ok(("", vec![
+                plain("ahttps://a.b "),
+                MarkdownInLine::Bold(vec![link("https://c.d", "https://c.d")]),
+            ]))
+        );
+        assert_eq!(
+            parse_markdown_text("see https://a.b\n"),
+            Ok(("", vec![plain("see https://a.b")]))
+        );
+    }
+
+    #[test]
+    fn test_parse_inline_html() {
+        assert_eq!(parse_inline_html("<span class=\"a>b\">x"), Ok(("x", "<span class=\"a>b\">")));
+        assert_eq!(parse_inline_html("</kbd>"), Ok(("", "</kbd>")));
+        assert_eq!(parse_inline_html("<!-- a > b -->c"), Ok(("c", "<!-- a > b -->")));
+        assert_eq!(parse_inline_html("<1>"), Err(Error(("<1>", ErrorKind::Verify))));
+        assert_eq!(parse_inline_html("<a <b>"), Err(Error(("<a <b>", ErrorKind::Tag))));
+        let html = |s: &str| MarkdownInLine::Html(String::from(s));
+        let plain = |s: &str| MarkdownInLine::Plain(String::from(s));
+        assert_eq!(
+            parse_markdown("press <kbd>Ctrl</kbd>+<kbd>C</kbd>, a <b <br/>\n"),
+            Ok(("", vec![Markdown::Paragraph(vec![
+                plain("press "),
+                html("<kbd>"),
+                plain("Ctrl"),
+                html("</kbd>"),
+                plain("+"),
+                html("<kbd>"),
+                plain("C"),
+                html("</kbd>"),
+                plain(", a <b "),
+                html("<br/>"),
+            ])]))
+        );
+    }
+
+    #[test]
+    fn test_parse_emoji() {
+        assert_eq!(parse_emoji(":+1: ok"), Ok((" ok", "+1")));
+        assert_eq!(parse_emoji(":no emoji:"), Err(Error((" emoji:", ErrorKind::Tag))));
+        let options = ParserOptions { emoji: true, ..ParserOptions::default() };
+        let emoji = |s: &str| MarkdownInLine::Emoji(String::from(s));
+        let plain = |s: &str| MarkdownInLine::Plain(String::from(s));
+        assert_eq!(
+            parse_markdown_text_with("at 10:30: :tada::rocket: *:x:* a :: b\n", &options),
+            Ok(("", vec![
+                plain("at 10:30: "),
+                emoji("tada"),
+                emoji("rocket"),
+                plain(" "),
+                MarkdownInLine::Italic(vec![emoji("x")]),
+                plain(" a :: b"),
+            ]))
+        );
+        assert_eq!(
+            parse_markdown_text(":tada:\n"),
+            Ok(("", vec![plain(":tada:")]))
+        );
+        assert_eq!(plain_text(&vec![emoji("tada"), emoji("unknown")]), "🎉:unknown:");
+    }
+
+    #[test]
+    fn test_parse_inline_math() {
+        assert_eq!(parse_inline_math("$a_1 * b_2$ c"), Ok((" c", "a_1 * b_2")));
+        assert_eq!(parse_inline_math("$\\$5$"), Ok(("", "\\$5")));
+        assert_eq!(parse_inline_math("$ x$"), Err(Error((" x$", ErrorKind::IsNot))));
+        let math = |s: &str| MarkdownInLine::Math(String::from(s));
+        let plain = |s: &str| MarkdownInLine::Plain(String::from(s));
+        assert_eq!(
+            parse_markdown_text("*$x^*$* costs $5 and $10, $$\n"),
+            Ok(("", vec![
+                MarkdownInLine::Italic(vec![math("x^*")]),
+                plain(" costs $5 and $10, $$"),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_escape() {
+        assert_eq!(parse_escape("\\*a"), Ok(("a", '*')));
+        assert_eq!(parse_escape("\\a"), Err(Error(("a", ErrorKind::Verify))));
+        assert_eq!(
+            parse_markdown_text("\\*not italic\\* \\[not a link\\] C:\\dir\\\\\n"),
+            Ok(("", vec![MarkdownInLine::Plain(String::from("*not italic* [not a link] C:\\dir\\"))]))
+        );
+    }
+
+    #[test]
+    fn test_parse_strong_emphasis() {
+        let plain = |s: &str| MarkdownInLine::Plain(String::from(s));
+        let bold = |text| MarkdownInLine::Bold(text);
+        let italic = |text| MarkdownInLine::Italic(text);
+        assert_eq!(
+            parse_markdown_text("***really important*** and ___this___\n"),
+            Ok(("", vec![
+                italic(vec![bold(vec![plain("really important")])]),
+                plain(" and "),
+                italic(vec![bold(vec![plain("this")])]),
+            ]))
+        );
+        assert_eq!(
+            parse_markdown_text("***a** b* ***c* d**\n"),
+            Ok(("", vec![
+                italic(vec![bold(vec![plain("a")]), plain(" b")]),
+                plain(" "),
+                bold(vec![italic(vec![plain("c")]), plain(" d")]),
+            ]))
+        );
+        assert_eq!(
+            parse_markdown_text("*** a*** ***b\n"),
+            Ok(("", vec![plain("*** a*** ***b")]))
+        );
+    }
+
+    #[test]
+    fn test_parse_underscore_emphasis() {
+        assert_eq!(
+            parse_markdown_text("_a_ and __b__, not_this_one or _c_d\n"),
+            Ok(("", vec![
+                MarkdownInLine::Italic(vec![MarkdownInLine::Plain(String::from("a"))]),
+                MarkdownInLine::Plain(String::from(" and ")),
+                MarkdownInLine::Bold(vec![MarkdownInLine::Plain(String::from("b"))]),
+                MarkdownInLine::Plain(String::from(", not_this_one or _c_d")),
+            ]))
+        );
+        assert_eq!(
+            parse_markdown_text("__a_b__ _x*\n"),
+            Ok(("", vec![
+                MarkdownInLine::Bold(vec![MarkdownInLine::Plain(String::from("a_b"))]),
+                MarkdownInLine::Plain(String::from(" _x*")),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_header_tag() {
+        assert_eq!(parse_header_tag("# "), Ok(("", 1)));
+        assert_eq!(parse_header_tag("### "), Ok(("", 3)));
+        assert_eq!(parse_header_tag("# h1"), Ok(("h1", 1)));
+        assert_eq!(parse_header_tag("# h1"), Ok(("h1", 1)));
+        assert_eq!(
+            parse_header_tag(" "),
+            Err(Error((" ", ErrorKind::TakeWhile1)))
+        );
+        assert_eq!(parse_header_tag("#"), Err(Error(("", ErrorKind::Tag))));
+        assert_eq!(parse_header_tag("##\th2"), Ok(("h2", 2)));
+    }
+
+    #[test]
+    fn test_parse_hard_breaks() {
+        let options = ParserOptions {
+            hard_breaks: true,
+            ..ParserOptions::default()
+        };
+        assert_eq!(
+            parse("hi\nhow are *you*\n\nbye\n- item\nafter", &options),
+            Ok(vec![
+                Markdown::Paragraph(vec![
+                    MarkdownInLine::Plain(String::from("hi")),
+                    MarkdownInLine::HardBreak,
+                    MarkdownInLine::Plain(String::from("how are ")),
+                    MarkdownInLine::Italic(vec![MarkdownInLine::Plain(String::from("you"))]),
+                ]),
+                Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("bye"))]),
+                Markdown::UnorderedList(vec![ListItem::new(vec![Markdown::Paragraph(vec![
+                    MarkdownInLine::Plain(String::from("item")),
+                ])])], ListInfo::default()),
+                Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("after"))]),
+            ])
+        );
+        assert_eq!(
+            parse("a\nb\n", &ParserOptions::default()),
+            Ok(vec![Markdown::Paragraph(lines("a\nb"))])
+        );
+        assert_eq!(
+            parse("Roses  \nare *red*\\\nC:\\\\\nend  \n", &ParserOptions::default()),
+            Ok(vec![Markdown::Paragraph(vec![
+                MarkdownInLine::Plain(String::from("Roses")),
+                MarkdownInLine::HardBreak,
+                MarkdownInLine::Plain(String::from("are ")),
+                MarkdownInLine::Italic(vec![MarkdownInLine::Plain(String::from("red"))]),
+                MarkdownInLine::HardBreak,
+                MarkdownInLine::Plain(String::from("C:\\")),
+                MarkdownInLine::SoftBreak,
+                MarkdownInLine::Plain(String::from("end  ")),
+            ])])
+        );
+    }
+
+    #[test]
+    fn test_parse_tasks() {
+        let options = ParserOptions {
+            tasks: true,
+            ..ParserOptions::default()
+        };
+        let item = |i| parse_unordered_list_element_with(i, &options).map(|(_, item)| item);
+        let text = |s: &str| vec![Markdown::Paragraph(lines(s))];
+        assert_eq!(
+            item("- [ ] (A) water  the plants due:2024-01-01\n"),
+            Ok(ListItem {
+                checked: Some(false),
+                task: Some(Task {
+                    priority: Some('A'),
+                    due: Some(String::from("2024-01-01")),
+                }),
+                blocks: text("water  the plants"),
+            })
+        );
+        assert_eq!(
+            item("- [x] call mom 📅 2024-02-03 (B)\n"),
+            Ok(ListItem {
+                checked: Some(true),
+                task: Some(Task {
+                    priority: Some('B'),
+                    due: Some(String::from("2024-02-03")),
+                }),
+                blocks: text("call mom"),
+            })
+        );
+        // not metadata, it doesn't trail the text or isn't a date
+        assert_eq!(
+            item("- [X] due:tomorrow or (a) 📅 2024-02-03 later\n"),
+            Ok(ListItem {
+                checked: Some(true),
+                task: Some(Task::default()),
+                blocks: text("due:tomorrow or (a) 📅 2024-02-03 later"),
+            })
+        );
+        assert_eq!(item("- ordinary\n"), Ok(ListItem::new(text("ordinary"))));
+        assert_eq!(
+            parse_ordered_list_element_with("1. [ ]\n", &options),
+            Ok(("", ListItem {
+                checked: Some(false),
+                task: Some(Task::default()),
+                blocks: vec![],
+            }))
+        );
+    }
+
+    #[test]
+    fn test_parse_task_checkboxes() {
+        let text = |s: &str| vec![Markdown::Paragraph(lines(s))];
+        let checked = |checked, s| ListItem {
+            checked,
+            ..ListItem::new(text(s))
+        };
+        assert_eq!(
+            parse_unordered_list("- [ ] todo\n- [x] done\n- [X] also done due:2024-01-01\n- plain\n"),
+            Ok((
+                "",
+                (vec![
+                    checked(Some(false), "todo"),
+                    checked(Some(true), "done"),
+                    checked(Some(true), "also done due:2024-01-01"),
+                    checked(None, "plain"),
+                ], ListInfo::default())
+            ))
+        );
+        assert_eq!(
+            parse_markdown("1. [x] a\n   - [ ] b\n"),
+            Ok(("", vec![Markdown::OrderedList(vec![ListItem {
+                checked: Some(true),
+                ..ListItem::new(vec![
+                    Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("a"))]),
+                    Markdown::UnorderedList(vec![checked(Some(false), "b")], ListInfo::default()),
+                ])
+            }], ListInfo::ordered(1, '.'))]))
+        );
+    }
 
-// Credit:
-// Most of tests are copied and edited from HGHimself/prose
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_parse_url_schemes() {
+        let options = ParserOptions::default();
+        let link = |i| parse_markdown_inline_with(i, &options);
+        // a blocked link or image keeps only its text
+        assert_eq!(link("[*x*](javascript:alert(1))"), Ok(("", MarkdownInLine::Plain(String::from("x")))));
+        assert_eq!(link("![x](JaVaScRiPt:alert)"), Ok(("", MarkdownInLine::Plain(String::from("x")))));
+        // a tab splits the url, so it is no image at all
+        assert!(link("![x](JaVa\tScRiPt:alert)").is_err());
+        assert_eq!(link("[x](data:text/html,hi)"), Ok(("", MarkdownInLine::Plain(String::from("x")))));
+        assert_eq!(link("<javascript:alert(1)>"), Ok(("", MarkdownInLine::Plain(String::from("javascript:alert(1)")))));
+        // unlike an empty destination
+        assert_eq!(
+            link("[x]()"),
+            Ok(("", MarkdownInLine::Link(vec![MarkdownInLine::Plain(String::from("x"))], String::new(), None)))
+        );
+        assert_eq!(link("![x]()"), Ok(("", MarkdownInLine::Image(String::from("x"), String::new(), None))));
+        for url in &["https://example.com", "MAILTO:me@example.com", "docs/a:b", "#top", "/x"] {
+            assert!(options.is_allowed_url(url), "{}", url);
+        }
+
+        let options = ParserOptions {
+            url_schemes: None,
+            ..ParserOptions::default()
+        };
+        assert!(options.is_allowed_url("javascript:alert(1)"));
+    }
 
     #[test]
-    fn test_parse_bold() {
-        assert_eq!(parse_bold("**bold text**"), Ok(("", "bold text")));
-        assert_eq!(parse_bold("**not bold"), Err(Error(("", ErrorKind::Tag))));
+    fn test_parse_tabs() {
         assert_eq!(
-            parse_bold("not bold**"),
-            Err(Error(("not bold**", ErrorKind::Tag)))
+            parse_markdown("#\th1\n-\titem\n"),
+            Ok(("", vec![
+                Markdown::Heading(1, vec![MarkdownInLine::Plain(String::from("h1"))]),
+                Markdown::UnorderedList(vec![ListItem::new(vec![Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("item"))])])], ListInfo::default()),
+            ]))
         );
         assert_eq!(
-            parse_bold("another not bold"),
-            Err(Error(("another not bold", ErrorKind::Tag)))
+            parse_markdown("1.\tfirst\n2.\tsecond\n>\tquote\n"),
+            Ok(("", vec![
+                Markdown::OrderedList(vec![
+                    ListItem::new(vec![Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("first"))])]),
+                    ListItem::new(vec![Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("second"))])]),
+                ], ListInfo::ordered(1, '.')),
+                Markdown::Quote { body: vec![Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("quote"))])], cite: None },
+            ]))
         );
-        assert_eq!(parse_bold("****"), Err(Error(("**", ErrorKind::IsNot))));
-        assert_eq!(parse_bold("**"), Err(Error(("", ErrorKind::IsNot))));
-        assert_eq!(parse_bold("*"), Err(Error(("*", ErrorKind::Tag))));
-        assert_eq!(parse_bold(""), Err(Error(("", ErrorKind::Tag))));
         assert_eq!(
-            parse_bold("*this is italic*"),
-            Err(Error(("*this is italic*", ErrorKind::Tag)))
+            parse_markdown("```make\nall:\n\tcc main.c\n```"),
+            Ok(("", vec![
+                Markdown::CodeBlock(String::from("all:\n\tcc main.c\n"), FenceInfo::new("make")),
+            ]))
         );
     }
 
     #[test]
-    fn test_parse_italics() {
+    fn test_parse_header() {
         assert_eq!(
-            parse_italic("*italic text*"),
-            Ok(("", "italic text"))
+            parse_header("# h1\n"),
+            Ok(("", (1, vec![MarkdownInLine::Plain(String::from("h1"))])))
         );
         assert_eq!(
-            parse_italic("*not italic"),
+            parse_header("## h2\n"),
+            Ok(("", (2, vec![MarkdownInLine::Plain(String::from("h2"))])))
+        );
+        assert_eq!(
+            parse_header("###  h3\n"),
+            Ok((
+                "",
+                (3, vec![MarkdownInLine::Plain(String::from(" h3"))])
+            ))
+        );
+        assert_eq!(parse_header("###h3"), Err(Error(("h3", ErrorKind::Tag))));
+        assert_eq!(parse_header("###"), Err(Error(("", ErrorKind::Tag))));
+        assert_eq!(parse_header(""), Err(Error(("", ErrorKind::TakeWhile1))));
+        assert_eq!(parse_header("#"), Err(Error(("", ErrorKind::Tag))));
+        assert_eq!(parse_header("# \n"), Ok(("", (1, vec![]))));
+        assert_eq!(parse_header("# test"), Err(Error(("", ErrorKind::Tag))));
+        assert_eq!(
+            parse_header("###### h6\n"),
+            Ok(("", (6, vec![MarkdownInLine::Plain(String::from("h6"))])))
+        );
+        assert_eq!(
+            parse_header("####### h7\n"),
+            Err(Error(("####### h7\n", ErrorKind::TooLarge)))
+        );
+    }
+
+    #[test]
+    fn test_parse_header_closing_hashes() {
+        let plain = |s: &str| vec![MarkdownInLine::Plain(String::from(s))];
+        assert_eq!(parse_header("## Title ##\n"), Ok(("", (2, plain("Title")))));
+        assert_eq!(parse_header("# Title #####  \n"), Ok(("", (1, plain("Title")))));
+        assert_eq!(parse_header("### ###\n"), Ok(("", (3, vec![]))));
+        assert_eq!(
+            parse_header("# `a` #\n"),
+            Ok(("", (1, vec![MarkdownInLine::InlineCode(String::from("a"), None)])))
+        );
+        // not a closing run without a space before it
+        assert_eq!(parse_header("# C#\n"), Ok(("", (1, plain("C#")))));
+        assert_eq!(parse_header("# #1 on ## list\n"), Ok(("", (1, plain("#1 on ## list")))));
+    }
+
+    #[test]
+    fn test_parse_header_overflow() {
+        let h7 = "####### h7\n";
+        assert_eq!(
+            parse_markdown(h7),
+            Ok(("", vec![Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("####### h7"))])]))
+        );
+
+        let mut options = ParserOptions {
+            heading_overflow: HeadingOverflow::Clamp,
+            ..ParserOptions::default()
+        };
+        assert_eq!(
+            parse_markdown_with(h7, &options),
+            Ok(("", vec![Markdown::Heading(6, vec![MarkdownInLine::Plain(String::from("h7"))])]))
+        );
+
+        options.heading_overflow = HeadingOverflow::Keep;
+        assert_eq!(
+            parse_markdown_with(h7, &options),
+            Ok(("", vec![Markdown::Heading(7, vec![MarkdownInLine::Plain(String::from("h7"))])]))
+        );
+    }
+
+    #[test]
+    fn test_parse_unordered_list_tag() {
+        assert_eq!(parse_unordered_list_tag("- "), Ok(("", "-")));
+        assert_eq!(
+            parse_unordered_list_tag("- and some more"),
+            Ok(("and some more", "-"))
+        );
+        assert_eq!(
+            parse_unordered_list_tag("-"),
             Err(Error(("", ErrorKind::Tag)))
         );
         assert_eq!(
-            parse_italic("not italic*"),
-            Err(Error(("not italic*", ErrorKind::Tag)))
+            parse_unordered_list_tag("-and some more"),
+            Err(Error(("and some more", ErrorKind::Tag)))
         );
         assert_eq!(
-            parse_italic("another not italic"),
-            Err(Error(("another not italic", ErrorKind::Tag)))
+            parse_unordered_list_tag("--"),
+            Err(Error(("-", ErrorKind::Tag)))
+        );
+        assert_eq!(
+            parse_unordered_list_tag(""),
+            Err(Error(("", ErrorKind::Tag)))
+        );
+        assert_eq!(parse_unordered_list_tag("* a"), Ok(("a", "*")));
+        assert_eq!(parse_unordered_list_tag("+ a"), Ok(("a", "+")));
+    }
+
+    #[test]
+    fn test_parse_list_bullets() {
+        let text = |s: &str| Markdown::Paragraph(lines(s));
+        let list = |marker, items: &[&str]| {
+            Markdown::UnorderedList(
+                items.iter().map(|s| ListItem::new(vec![text(s)])).collect(),
+                ListInfo { marker, ..ListInfo::default() },
+            )
+        };
+        assert_eq!(
+            parse_markdown("* a\n* b\n+ c\n- d\n"),
+            Ok(("", vec![list('*', &["a", "b"]), list('+', &["c"]), list('-', &["d"])]))
+        );
+        assert_eq!(
+            parse_markdown("*not a list*\n* * *\n"),
+            Ok(("", vec![Markdown::Paragraph(vec![MarkdownInLine::Italic(vec![MarkdownInLine::Plain(String::from("not a list"))])]), Markdown::Rule]))
+        );
+    }
+
+    #[test]
+    fn test_parse_unordered_list_element() {
+        assert_eq!(
+            parse_unordered_list_element("- this is an element\n"),
+            Ok((
+                "",
+                ListItem::new(vec![Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("this is an element"))])])
+            ))
+        );
+        assert_eq!(
+            parse_unordered_list_element("- this is an element\n- this is another element\n"),
+            Ok((
+                "- this is another element\n",
+                ListItem::new(vec![Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("this is an element"))])])
+            ))
+        );
+        assert_eq!(
+            parse_unordered_list_element(""),
+            Err(Error(("", ErrorKind::Tag)))
+        );
+        assert_eq!(parse_unordered_list_element("- \n"), Ok(("", ListItem::new(vec![]))));
+        assert_eq!(
+            parse_unordered_list_element("- "),
+            Err(Error(("", ErrorKind::Tag)))
+        );
+        assert_eq!(
+            parse_unordered_list_element("- test"),
+            Err(Error(("", ErrorKind::Tag)))
+        );
+        assert_eq!(
+            parse_unordered_list_element("-"),
+            Err(Error(("", ErrorKind::Tag)))
+        );
+    }
+
+    #[test]
+    fn test_parse_unordered_list() {
+        assert_eq!(
+            parse_unordered_list("- this is an element"),
+            Err(Error(("", ErrorKind::Tag)))
+        );
+        assert_eq!(
+            parse_unordered_list("- this is an element\n"),
+            Ok((
+                "",
+                (vec![ListItem::new(vec![Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("this is an element"))])])], ListInfo::default())
+            ))
+        );
+        assert_eq!(
+            parse_unordered_list("- this is an element\n- here is another\n"),
+            Ok((
+                "",
+                (vec![
+                    ListItem::new(vec![Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("this is an element"))])]),
+                    ListItem::new(vec![Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("here is another"))])])
+                ], ListInfo::default())
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_nested_unordered_list() {
+        let text = |s: &str| Markdown::Paragraph(lines(s));
+        assert_eq!(
+            parse_unordered_list("- Food\n  - **bold** text\n    - deep\n  - fruit\n- hope this works\n"),
+            Ok((
+                "",
+                (vec![
+                    ListItem::new(vec![
+                        text("Food"),
+                        Markdown::UnorderedList(vec![
+                            ListItem::new(vec![
+                                Markdown::Paragraph(vec![
+                                    MarkdownInLine::Bold(vec![MarkdownInLine::Plain(String::from("bold"))]),
+                                    MarkdownInLine::Plain(String::from(" text"))
+                                ]),
+                                Markdown::UnorderedList(vec![ListItem::new(vec![text("deep")])], ListInfo::default())
+                            ]),
+                            ListItem::new(vec![text("fruit")])
+                        ], ListInfo::default())
+                    ]),
+                    ListItem::new(vec![text("hope this works")])
+                ], ListInfo::default())
+            ))
+        );
+        // a sub-list right after a blank line still belongs to the item
+        assert_eq!(
+            parse_unordered_list("- a\n\n  - b\nc\n"),
+            Ok((
+                "c\n",
+                (vec![ListItem::new(vec![
+                    text("a"),
+                    Markdown::UnorderedList(vec![ListItem::new(vec![text("b")])], ListInfo::default())
+                ])], ListInfo { loose: true, ..ListInfo::default() })
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_paragraphs() {
+        let text = |s: &str| Markdown::Paragraph(lines(s));
+        assert_eq!(
+            parse("\nfirst line\n**second** line\n\n\nnext\n# heading\nlast\n> quote\n\n", &ParserOptions::default()),
+            Ok(vec![
+                Markdown::Paragraph(vec![
+                    MarkdownInLine::Plain(String::from("first line")),
+                    MarkdownInLine::SoftBreak,
+                    MarkdownInLine::Bold(vec![MarkdownInLine::Plain(String::from("second"))]),
+                    MarkdownInLine::Plain(String::from(" line")),
+                ]),
+                text("next"),
+                Markdown::Heading(1, vec![MarkdownInLine::Plain(String::from("heading"))]),
+                text("last"),
+                Markdown::Quote { body: vec![text("quote")], cite: None },
+            ])
+        );
+        assert_eq!(parse("\n  \n", &ParserOptions::default()), Ok(vec![]));
+        // a setext heading's text isn't part of the paragraph before it
+        assert_eq!(
+            parse_markdown("a\nb\n---\n"),
+            Ok(("", vec![text("a"), Markdown::Heading(2, vec![MarkdownInLine::Plain(String::from("b"))])]))
+        );
+        // continuation lines lose their indentation
+        assert_eq!(parse_markdown("a\n  b\n\tc\n"), Ok(("", vec![text("a\nb\nc")])));
+        // only a list numbered 1 or an item with text interrupts a paragraph
+        for (source, paragraph) in [
+            ("in\n2019. it rained\n", "in\n2019. it rained"),
+            ("a\n2) b\n", "a\n2) b"),
+            ("a\n* \n", "a\n* "),
+            ("a\n  + \n", "a\n+ "),
+        ] {
+            assert_eq!(parse_markdown(source), Ok(("", vec![text(paragraph)])), "{:?}", source);
+        }
+        let item = |s: &str| ListItem::new(vec![text(s)]);
+        assert_eq!(
+            parse_markdown("a\n1. b\n"),
+            Ok(("", vec![text("a"), Markdown::OrderedList(vec![item("b")], ListInfo::ordered(1, '.'))]))
+        );
+        assert_eq!(
+            parse_markdown("a\n* b\n"),
+            Ok(("", vec![text("a"), Markdown::UnorderedList(vec![item("b")], ListInfo { marker: '*', ..ListInfo::default() })]))
+        );
+    }
+
+    #[test]
+    fn test_parse_list_continuation() {
+        let text = |s: &str| Markdown::Paragraph(lines(s));
+        assert_eq!(
+            parse_markdown("- first\n  more of first\n\n  ```\n  code\n  ```\n- second\nafter\n"),
+            Ok((
+                "",
+                vec![
+                    Markdown::UnorderedList(vec![
+                        ListItem::new(vec![
+                            text("first\nmore of first"),
+                            Markdown::CodeBlock(String::from("code\n"), FenceInfo::default()),
+                        ]),
+                        ListItem::new(vec![text("second")]),
+                    ], ListInfo { loose: true, ..ListInfo::default() }),
+                    text("after"),
+                ]
+            ))
+        );
+        assert_eq!(
+            parse_ordered_list("10. a\n    b\n"),
+            Ok(("", (vec![ListItem::new(vec![text("a\nb")])], ListInfo::ordered(10, '.'))))
+        );
+    }
+
+    #[test]
+    fn test_parse_loose_lists() {
+        let text = |s: &str| Markdown::Paragraph(lines(s));
+        let tight = ListInfo::default();
+        let loose = ListInfo { loose: true, ..ListInfo::default() };
+        assert_eq!(
+            parse_unordered_list("- a\n\n- b\n\n\nafter\n"),
+            Ok(("\n\nafter\n", (vec![ListItem::new(vec![text("a")]), ListItem::new(vec![text("b")])], loose)))
+        );
+        assert_eq!(
+            parse_ordered_list("1. a\n2. b\n"),
+            Ok(("", (vec![ListItem::new(vec![text("a")]), ListItem::new(vec![text("b")])], ListInfo::ordered(1, '.'))))
+        );
+        // every block indented under the marker belongs to the item
+        assert_eq!(
+            parse_markdown("1. first\n\n   second\n   more\n2. x\n\n   y\n\nafter\n"),
+            Ok((
+                "",
+                vec![
+                    Markdown::OrderedList(
+                        vec![
+                            ListItem::new(vec![text("first"), text("second\nmore")]),
+                            ListItem::new(vec![text("x"), text("y")]),
+                        ],
+                        ListInfo { loose: true, ..ListInfo::ordered(1, '.') }
+                    ),
+                    text("after"),
+                ]
+            ))
+        );
+        // a loose sub-list doesn't make its parent loose, or the other way around
+        assert_eq!(
+            parse_markdown("- a\n  - b\n\n  - c\n- d\n"),
+            Ok((
+                "",
+                vec![Markdown::UnorderedList(
+                    vec![
+                        ListItem::new(vec![
+                            text("a"),
+                            Markdown::UnorderedList(vec![ListItem::new(vec![text("b")]), ListItem::new(vec![text("c")])], loose)
+                        ]),
+                        ListItem::new(vec![text("d")])
+                    ],
+                    tight
+                )]
+            ))
+        );
+        // blank lines in fenced code don't count
+        assert_eq!(
+            parse_ordered_list("1. ```\n   x\n\n   ```\n"),
+            Ok(("", (vec![ListItem::new(vec![Markdown::CodeBlock(String::from("x\n\n"), FenceInfo::default())])], ListInfo::ordered(1, '.'))))
+        );
+    }
+
+    #[test]
+    fn test_parse_mixed_nested_lists() {
+        let text = |s: &str| Markdown::Paragraph(lines(s));
+        let item = |s: &str, children: Option<Markdown>| {
+            ListItem::new(std::iter::once(text(s)).chain(children).collect())
+        };
+        let expected = vec![Markdown::OrderedList(vec![
+            item("a", Some(Markdown::UnorderedList(vec![item("b", Some(Markdown::OrderedList(vec![item("c", None)], ListInfo::ordered(1, '.'))))], ListInfo::default()))),
+            item("d", None),
+        ], ListInfo::ordered(1, '.'))];
+        // 2 and 4 spaces and tabs all nest
+        for source in [
+            "1. a\n   - b\n     1. c\n2. d\n",
+            "1. a\n    - b\n        1. c\n2. d\n",
+            "1. a\n\t- b\n\t\t1. c\n2. d\n",
+        ] {
+            assert_eq!(parse_markdown(source), Ok(("", expected.clone())), "{:?}", source);
+        }
+        assert_eq!(
+            parse_markdown("- a\n  1. b\n"),
+            Ok(("", vec![Markdown::UnorderedList(vec![item("a", Some(Markdown::OrderedList(vec![item("b", None)], ListInfo::ordered(1, '.'))))], ListInfo::default())]))
+        );
+        // more than 3 spaces before a marker is indented code, not a list
+        assert_eq!(
+            parse_markdown("    - a\n"),
+            Ok(("", vec![Markdown::CodeBlock(String::from("- a\n"), FenceInfo::default())]))
+        );
+    }
+
+    #[test]
+    fn test_parse_ordered_list_tag() {
+        assert_eq!(parse_ordered_list_tag("1. "), Ok(("", ("1", '.'))));
+        assert_eq!(parse_ordered_list_tag("123456789) "), Ok(("", ("123456789", ')'))));
+        assert_eq!(
+            parse_ordered_list_tag("3. and some more"),
+            Ok(("and some more", ("3", '.')))
+        );
+        assert_eq!(
+            parse_ordered_list_tag("1234567890. "),
+            Err(Error(("0. ", ErrorKind::Tag)))
+        );
+        assert_eq!(
+            parse_ordered_list_tag("1"),
+            Err(Error(("", ErrorKind::Tag)))
+        );
+        assert_eq!(
+            parse_ordered_list_tag("1.and some more"),
+            Err(Error(("and some more", ErrorKind::Tag)))
+        );
+        assert_eq!(
+            parse_ordered_list_tag("1111."),
+            Err(Error(("", ErrorKind::Tag)))
         );
-        assert_eq!(parse_italic("*"), Err(Error(("", ErrorKind::IsNot))));
-        assert_eq!(parse_italic("**"), Err(Error(("*", ErrorKind::IsNot))));
-        assert_eq!(parse_italic(""), Err(Error(("", ErrorKind::Tag))));
         assert_eq!(
-            parse_italic("**this is bold**"),
-            Err(Error(("*this is bold**", ErrorKind::IsNot)))
+            parse_ordered_list_tag(""),
+            Err(Error(("", ErrorKind::TakeWhileMN)))
         );
     }
 
     #[test]
-    fn test_parse_inline_code() {
+    fn test_parse_ordered_list_element() {
         assert_eq!(
-            parse_inline("`inline text`"),
-            Ok(("", ("inline text", None)))
+            parse_ordered_list_element("1. this is an element\n"),
+            Ok((
+                "",
+                ListItem::new(vec![Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("this is an element"))])])
+            ))
         );
         assert_eq!(
-            parse_inline("`inline text`rust"),
-            Ok(("", ("inline text", Some("rust"))))
+            parse_ordered_list_element("1. this is an element\n1. here is another\n"),
+            Ok((
+                "1. here is another\n",
+                ListItem::new(vec![Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("this is an element"))])])
+            ))
         );
         assert_eq!(
-            parse_inline("`inline text`rust\n"),
-            Ok(("\n", ("inline text", Some("rust"))))
+            parse_ordered_list_element(""),
+            Err(Error(("", ErrorKind::TakeWhileMN)))
         );
         assert_eq!(
-            parse_inline("`inline text`rust "),
-            Ok((" ", ("inline text", Some("rust"))))
+            parse_ordered_list_element(""),
+            Err(Error(("", ErrorKind::TakeWhileMN)))
         );
+        assert_eq!(parse_ordered_list_element("1. \n"), Ok(("", ListItem::new(vec![]))));
         assert_eq!(
-            parse_inline("`not inline"),
+            parse_ordered_list_element("1. test"),
             Err(Error(("", ErrorKind::Tag)))
         );
         assert_eq!(
-            parse_inline("not inline`"),
-            Err(Error(("not inline`", ErrorKind::Tag)))
+            parse_ordered_list_element("1. "),
+            Err(Error(("", ErrorKind::Tag)))
         );
-        assert_eq!(parse_inline("``"), Err(Error(("`", ErrorKind::IsNot))));
-        assert_eq!(parse_inline("`"), Err(Error(("", ErrorKind::IsNot))));
-        assert_eq!(parse_inline(""), Err(Error(("", ErrorKind::Tag))));
-    }
-
-    #[test]
-    fn test_parse_link() {
         assert_eq!(
-            parse_link("[title](https://www.example.com)"),
-            Ok(("", ("title", "https://www.example.com")))
+            parse_ordered_list_element("1."),
+            Err(Error(("", ErrorKind::Tag)))
         );
-        assert_eq!(parse_inline(""), Err(Error(("", ErrorKind::Tag))));
     }
 
     #[test]
-    fn test_parse_image() {
+    fn test_parse_ordered_list() {
         assert_eq!(
-            parse_image("![alt text](image.jpg)"),
-            Ok(("", ("alt text", "image.jpg")))
+            parse_ordered_list("1. this is an element\n"),
+            Ok((
+                "",
+                (vec![ListItem::new(vec![Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("this is an element"))])])], ListInfo::ordered(1, '.'))
+            ))
+        );
+        assert_eq!(
+            parse_ordered_list("1. test"),
+            Err(Error(("", ErrorKind::Tag)))
+        );
+        assert_eq!(
+            parse_ordered_list("1. this is an element\n2. here is another\n"),
+            Ok((
+                "",
+                (vec![
+                    ListItem::new(vec![Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("this is an element"))])]),
+                    ListItem::new(vec![Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("here is another"))])])
+                ], ListInfo::ordered(1, '.'))
+            ))
         );
-        assert_eq!(parse_inline(""), Err(Error(("", ErrorKind::Tag))));
     }
 
     #[test]
-    fn test_parse_plaintext() {
+    fn test_parse_ordered_list_start() {
+        let item = |s: &str| ListItem::new(vec![Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from(s))])]);
         assert_eq!(
-            parse_plain("1234567890"),
-            Ok(("", String::from("1234567890")))
+            parse_ordered_list("3. first\n4. second\n"),
+            Ok(("", (vec![item("first"), item("second")], ListInfo::ordered(3, '.'))))
         );
         assert_eq!(
-            parse_plain("plaintext"),
-            Ok(("", String::from("plaintext")))
+            parse_ordered_list("007) a\n"),
+            Ok(("", (vec![item("a")], ListInfo::ordered(7, ')'))))
         );
+        // another delimiter starts another list
         assert_eq!(
-            parse_plain("plaintext!"),
-            Ok(("", String::from("plaintext!")))
+            parse_markdown("1. a\n2) b\n"),
+            Ok((
+                "",
+                vec![
+                    Markdown::OrderedList(vec![item("a")], ListInfo::ordered(1, '.')),
+                    Markdown::OrderedList(vec![item("b")], ListInfo::ordered(2, ')')),
+                ]
+            ))
         );
+    }
+
+    #[test]
+    fn test_parse_codeblock() {
         assert_eq!(
-            parse_plain("plaintext!["),
-            Ok(("![", String::from("plaintext")))
+            parse_code_block("```bash\npip install foobar\n```"),
+            Ok(("", ("bash", "pip install foobar\n")))
         );
         assert_eq!(
-            parse_plain("plaintext!*"),
-            Ok(("*", String::from("plaintext!")))
+            parse_code_block("```python\nimport foobar\n\nfoobar.pluralize('word') # returns 'words'\nfoobar.pluralize('goose') # returns 'geese'\nfoobar.singularize('phenomena') # returns 'phenomenon'\n```"),
+            Ok(("", ("python", "import foobar\n\nfoobar.pluralize('word') # returns 'words'\nfoobar.pluralize('goose') # returns 'geese'\nfoobar.singularize('phenomena') # returns 'phenomenon'\n")))
         );
+    }
+
+    #[test]
+    fn test_parse_codeblock_without_info() {
+        assert_eq!(parse_code_block("```\ncode\n```"), Ok(("", ("", "code\n"))));
+        assert_eq!(parse_code_block("```\n```"), Ok(("", ("", ""))));
+        assert_eq!(parse_code_block("```\r\ncode\r\n```"), Ok(("", ("", "code\r\n"))));
+        assert_eq!(parse_code_block("~~~\ncode\n~~~"), Ok(("", ("", "code\n"))));
         assert_eq!(
-            parse_plain("plaintext![image"),
-            Ok(("![image", String::from("plaintext")))
+            parse_markdown("```\nplain fence\n```"),
+            Ok(("", vec![Markdown::CodeBlock(String::from("plain fence\n"), FenceInfo::default())]))
         );
         assert_eq!(
-            parse_plain("plaintext\n"),
-            Ok(("\n", String::from("plaintext")))
+            parse_markdown("``` \nspace only\n```"),
+            Ok(("", vec![Markdown::CodeBlock(String::from("space only\n"), FenceInfo::default())]))
         );
         assert_eq!(
-            parse_plain("*bold text*"),
-            Err(Error(("*bold text*", ErrorKind::Not)))
+            parse_markdown("  ```rust\n  let a = 1;\n    indented\n  ```"),
+            Ok(("", vec![Markdown::CodeBlock(
+                String::from("let a = 1;\n  indented\n"),
+                FenceInfo::new("rust")
+            )]))
         );
         assert_eq!(
-            parse_plain("[example](https://example.com)"),
-            Err(Error(("[example](https://example.com)", ErrorKind::Not)))
+            parse_code_block("    ```\nnot a fence\n```"),
+            Err(Error((" ```\nnot a fence\n```", ErrorKind::TakeWhileMN)))
         );
+    }
+
+    #[test]
+    fn test_parse_codeblock_fence_length() {
         assert_eq!(
-            parse_plain("`codeblock for bums`"),
-            Err(Error(("`codeblock for bums`", ErrorKind::Not)))
+            parse_code_block("````md\n```\nnested\n```\n````\n"),
+            Ok(("\n", ("md", "```\nnested\n```\n")))
         );
         assert_eq!(
-            parse_plain("![ but wait theres more](jk)"),
-            Err(Error(("![ but wait theres more](jk)", ErrorKind::Not)))
+            parse_code_block("```\nlet s = `a` + ``b``;\n```"),
+            Ok(("", ("", "let s = `a` + ``b``;\n")))
         );
         assert_eq!(
-            parse_plain("*italic*"),
-            Err(Error(("*italic*", ErrorKind::Not)))
+            parse_code_block("````\ncode\n```\n `````  \nafter"),
+            Ok(("\nafter", ("", "code\n```\n")))
         );
         assert_eq!(
-            parse_plain("**bold**"),
-            Err(Error(("**bold**", ErrorKind::Not)))
+            parse_code_block("```\ncode\n``` not a fence\n```"),
+            Ok(("", ("", "code\n``` not a fence\n")))
         );
         assert_eq!(
-            parse_plain("`inline code`"),
-            Err(Error(("`inline code`", ErrorKind::Not)))
+            parse_code_block("~~~~~\n~~~\n~~~~\n~~~~~~\n"),
+            Ok(("\n", ("", "~~~\n~~~~\n")))
         );
+        assert_eq!(parse_code_block("```\ncode\nmore"), Ok(("", ("", "code\nmore"))));
+        assert_eq!(parse_code_block("```"), Ok(("", ("", ""))));
         assert_eq!(
-            parse_plain("[title](https://example.com)"),
-            Err(Error(("[title](https://example.com)", ErrorKind::Not)))
+            parse_code_block("``` a`b\n```"),
+            Err(Error(("\n```", ErrorKind::Verify)))
         );
+        assert_eq!(parse_code_block("``\n``"), Err(Error(("``\n``", ErrorKind::TakeWhileMN))));
         assert_eq!(
-            parse_plain("![alt text](image.jpg)"),
-            Err(Error(("![alt text](image.jpg)", ErrorKind::Not)))
+            parse_markdown("```\nunterminated"),
+            Ok(("", vec![Markdown::CodeBlock(String::from("unterminated"), FenceInfo::default())]))
         );
-        assert_eq!(parse_plain(""), Err(Error(("", ErrorKind::Eof))));
     }
 
     #[test]
-    fn test_parse_markdown_inline() {
+    fn test_parse_fence_info() {
         assert_eq!(
-            parse_markdown_inline("*italic*"),
-            Ok(("", MarkdownInLine::Italic(String::from("italic"))))
+            parse_fence_info(" rust,ignore {linenos=true, hl_lines=\"2-4\" title=\"a b\" .wide}"),
+            FenceInfo {
+                language: Some(String::from("rust")),
+                attributes: vec![
+                    (String::from("ignore"), None),
+                    (String::from("linenos"), Some(String::from("true"))),
+                    (String::from("hl_lines"), Some(String::from("2-4"))),
+                    (String::from("title"), Some(String::from("a b"))),
+                    (String::from(".wide"), None),
+                ],
+            }
         );
+        let info = parse_fence_info("{hl_lines=1}");
+        assert_eq!(info.language, None);
+        assert_eq!(info.attribute("hl_lines"), Some("1"));
+        assert!(parse_fence_info("sh no_run").has_flag("no_run"));
+        assert_eq!(parse_fence_info(""), FenceInfo::default());
         assert_eq!(
-            parse_markdown_inline("**bold**"),
-            Ok(("", MarkdownInLine::Bold(String::from("bold"))))
+            parse_markdown("```py title=x\ny\n```\n"),
+            Ok(("", vec![Markdown::CodeBlock(
+                String::from("y\n"),
+                FenceInfo {
+                    language: Some(String::from("py")),
+                    attributes: vec![(String::from("title=x"), None)],
+                }
+            )]))
         );
+    }
+
+    #[test]
+    fn test_parse_tilde_codeblock() {
         assert_eq!(
-            parse_markdown_inline("`inline code`python"),
-            Ok(("", MarkdownInLine::InlineCode(String::from("inline code"), Some(String::from("python")))))
+            parse_code_block("~~~rust\nlet a = 1;\n```\n~~\n~~~~\nafter"),
+            Ok(("\nafter", ("rust", "let a = 1;\n```\n~~\n")))
         );
+        // ``` doesn't close ~~~ and the other way around
+        assert_eq!(parse_code_block("```\na\n~~~\n```"), Ok(("", ("", "a\n~~~\n"))));
+        assert_eq!(parse_code_block("~~~ a`b\n~~~"), Ok(("", (" a`b", ""))));
         assert_eq!(
-            parse_markdown_inline("[title](https://www.example.com)"),
-            Ok((
-                "",
-                (MarkdownInLine::Link(
-                    String::from("title"),
-                    String::from("https://www.example.com"),
-                ))
-            ))
+            parse_markdown("~~~py\nx\n~~~\n"),
+            Ok(("", vec![Markdown::CodeBlock(String::from("x\n"), FenceInfo::new("py"))]))
         );
+    }
+
+    #[test]
+    fn test_parse_nested_codeblock() {
         assert_eq!(
-            parse_markdown_inline("![text](image.png)"),
-            Ok((
-                "",
-                (MarkdownInLine::Image(String::from("text"), String::from("image.png")))
-            ))
+            parse_markdown("- install:\n  ```sh\n  make\n\n  make install\n  ```\n- done\n"),
+            Ok(("", vec![Markdown::UnorderedList(vec![
+                ListItem::new(vec![
+                    Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("install:"))]),
+                    Markdown::CodeBlock(String::from("make\n\nmake install\n"), FenceInfo::new("sh")),
+                ]),
+                ListItem::new(vec![Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("done"))])]),
+            ], ListInfo::default())]))
         );
         assert_eq!(
-            parse_markdown_inline("plaintext!"),
-            Ok((
-                "",
-                MarkdownInLine::Plain(String::from("plaintext!"))
-            ))
+            parse_markdown("1.  ```\n    - not a list\n    ```\n"),
+            Ok(("", vec![Markdown::OrderedList(vec![ListItem::new(vec![
+                Markdown::CodeBlock(String::from("- not a list\n"), FenceInfo::default()),
+            ])], ListInfo::ordered(1, '.'))]))
         );
         assert_eq!(
-            parse_markdown_inline("here is some plaintext *but what if we italicize?"),
-            Ok((
-                "*but what if we italicize?",
-                MarkdownInLine::Plain(String::from("here is some plaintext "))
-            ))
+            parse_markdown("> ```rust\n> fn main() {}\n>\n> ```\n> after\n"),
+            Ok(("", vec![Markdown::Quote { body: vec![
+                Markdown::CodeBlock(String::from("fn main() {}\n\n"), FenceInfo::new("rust")),
+                Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("after"))]),
+            ], cite: None }]))
         );
         assert_eq!(
-            parse_markdown_inline("here is some plaintext \n*but what if we italicize?"),
-            Ok((
-                "\n*but what if we italicize?",
-                MarkdownInLine::Plain(String::from("here is some plaintext "))
-            ))
+            parse_markdown("> - ```\n>   code\n>   ```\n"),
+            Ok(("", vec![Markdown::Quote { body: vec![Markdown::UnorderedList(vec![ListItem::new(vec![
+                Markdown::CodeBlock(String::from("code\n"), FenceInfo::default()),
+            ])], ListInfo::default())], cite: None }]))
         );
+        // not indented enough to continue the item
         assert_eq!(
-            parse_markdown_inline("\n"),
-            Err(Error(("\n", ErrorKind::Tag)))
+            parse_markdown("- a\ntext\n"),
+            Ok(("", vec![
+                Markdown::UnorderedList(vec![ListItem::new(vec![Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("a"))])])], ListInfo::default()),
+                Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("text"))]),
+            ]))
         );
-        assert_eq!(parse_markdown_inline(""), Err(Error(("", ErrorKind::Tag))));
     }
 
     #[test]
-    fn test_parse_markdown_text() {
-        assert_eq!(parse_markdown_text("\n"), Ok(("", vec![])));
-        assert_eq!(
-            parse_markdown_text("here is some plaintext\n"),
-            Ok((
-                "",
-                vec![MarkdownInLine::Plain(String::from(
-                    "here is some plaintext"
-                ))]
-            ))
-        );
-        assert_eq!(
-            parse_markdown_text("here is some plaintext *but what if we italicize?*\n"),
-            Ok((
-                "",
-                vec![
-                    MarkdownInLine::Plain(String::from("here is some plaintext ")),
-                    MarkdownInLine::Italic(String::from("but what if we italicize?")),
-                ]
-            ))
-        );
+    fn test_parse_indented_code_block() {
         assert_eq!(
-            parse_markdown_text("here is some plaintext *but what if we italicize?* I guess it doesnt **matter** in my `code`\n"),
-            Ok(("", vec![
-                MarkdownInLine::Plain(String::from("here is some plaintext ")),
-                MarkdownInLine::Italic(String::from("but what if we italicize?")),
-                MarkdownInLine::Plain(String::from(" I guess it doesnt ")),
-                MarkdownInLine::Bold(String::from("matter")),
-                MarkdownInLine::Plain(String::from(" in my ")),
-                MarkdownInLine::InlineCode(String::from("code"), None),
-            ]))
+            parse_indented_code_block("    a\n\n\t  b\n\ntext\n"),
+            Ok(("\ntext\n", String::from("a\n\n  b\n")))
         );
+        // indented code can't interrupt a paragraph
         assert_eq!(
-            parse_markdown_text("here is some plaintext *but what if we italicize?*\n"),
-            Ok((
-                "",
-                vec![
-                    MarkdownInLine::Plain(String::from("here is some plaintext ")),
-                    MarkdownInLine::Italic(String::from("but what if we italicize?")),
-                ]
-            ))
+            parse_markdown("text\n    more\n"),
+            Ok(("", vec![Markdown::Paragraph(lines("text\nmore"))]))
         );
+        let text = |s: &str| Markdown::Paragraph(lines(s));
         assert_eq!(
-            parse_markdown_text("here is some plaintext *but what if we italicize?"),
-            Err(Error(("*but what if we italicize?", ErrorKind::Tag))) // Ok(("*but what if we italicize?", vec![MarkdownInline::Plaintext(String::from("here is some plaintext "))]))
+            parse_markdown("- run:\n\n        make\n\n  then\n- done\n"),
+            Ok(("", vec![Markdown::UnorderedList(vec![
+                ListItem::new(vec![
+                    text("run:"),
+                    Markdown::CodeBlock(String::from("  make\n"), FenceInfo::default()),
+                    text("then"),
+                ]),
+                ListItem::new(vec![text("done")]),
+            ], ListInfo { loose: true, ..ListInfo::default() })]))
         );
     }
 
     #[test]
-    fn test_parse_header_tag() {
-        assert_eq!(parse_header_tag("# "), Ok(("", 1)));
-        assert_eq!(parse_header_tag("### "), Ok(("", 3)));
-        assert_eq!(parse_header_tag("# h1"), Ok(("h1", 1)));
-        assert_eq!(parse_header_tag("# h1"), Ok(("h1", 1)));
+    fn test_parse_markdown() {
         assert_eq!(
-            parse_header_tag(" "),
-            Err(Error((" ", ErrorKind::TakeWhile1)))
-        );
-        assert_eq!(parse_header_tag("#"), Err(Error(("", ErrorKind::Tag))));
+            parse_markdown("# Foobar\n\nFoobar is a Python library for dealing with word pluralization.\n\n```bash\n#!/bin/bash\npip install foobar\n```\n## Installation\n\nUse the package manager [pip](https://pip.pypa.io/en/stable/) to install foobar.\n```python\nimport foobar\n\nfoobar.pluralize('word') # returns 'words'\nfoobar.pluralize('goose') # returns 'geese'\nfoobar.singularize('phenomena') # returns 'phenomenon'\n```"),
+            Ok(("", vec![
+                Markdown::Heading(1, vec![MarkdownInLine::Plain(String::from("Foobar"))]),
+                Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("Foobar is a Python library for dealing with word pluralization."))]),
+                Markdown::CodeBlock(String::from("#!/bin/bash\npip install foobar\n"), FenceInfo::new("bash")),
+                Markdown::Heading(2, vec![MarkdownInLine::Plain(String::from("Installation"))]),
+                Markdown::Paragraph(vec![
+                    MarkdownInLine::Plain(String::from("Use the package manager ")),
+                    MarkdownInLine::Link(vec![MarkdownInLine::Plain(String::from("pip"))], String::from("https://pip.pypa.io/en/stable/"), None),
+                    MarkdownInLine::Plain(String::from(" to install foobar.")),
+                ]),
+                Markdown::CodeBlock(String::from("import foobar\n\nfoobar.pluralize('word') # returns 'words'\nfoobar.pluralize('goose') # returns 'geese'\nfoobar.singularize('phenomena') # returns 'phenomenon'\n"), FenceInfo::new("python")),
+            ]))
+        )
     }
 
     #[test]
-    fn test_parse_header() {
-        assert_eq!(
-            parse_header("# h1\n"),
-            Ok(("", (1, vec![MarkdownInLine::Plain(String::from("h1"))])))
-        );
+    fn test_parse_markdown_block() {
         assert_eq!(
-            parse_header("## h2\n"),
-            Ok(("", (2, vec![MarkdownInLine::Plain(String::from("h2"))])))
+            parse_markdown_block("#hashtag\n"),
+            Ok(("", Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("#hashtag"))])))
         );
         assert_eq!(
-            parse_header("###  h3\n"),
-            Ok((
-                "",
-                (3, vec![MarkdownInLine::Plain(String::from(" h3"))])
-            ))
+            parse_markdown_block("-5 degrees\n"),
+            Ok(("", Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("-5 degrees"))])))
         );
-        assert_eq!(parse_header("###h3"), Err(Error(("h3", ErrorKind::Tag))));
-        assert_eq!(parse_header("###"), Err(Error(("", ErrorKind::Tag))));
-        assert_eq!(parse_header(""), Err(Error(("", ErrorKind::TakeWhile1))));
-        assert_eq!(parse_header("#"), Err(Error(("", ErrorKind::Tag))));
-        assert_eq!(parse_header("# \n"), Ok(("", (1, vec![]))));
-        assert_eq!(parse_header("# test"), Err(Error(("", ErrorKind::Tag))));
-    }
-
-    #[test]
-    fn test_parse_unordered_list_tag() {
-        assert_eq!(parse_unordered_list_tag("- "), Ok(("", "-")));
         assert_eq!(
-            parse_unordered_list_tag("- and some more"),
-            Ok(("and some more", "-"))
+            parse_markdown_block("2020 was a year\n"),
+            Ok(("", Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("2020 was a year"))])))
         );
         assert_eq!(
-            parse_unordered_list_tag("-"),
-            Err(Error(("", ErrorKind::Tag)))
+            parse_markdown_block(">no quote\n"),
+            Ok(("", Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from(">no quote"))])))
         );
         assert_eq!(
-            parse_unordered_list_tag("-and some more"),
-            Err(Error(("and some more", ErrorKind::Tag)))
+            parse_markdown_block("`code` here\n"),
+            Ok(("", Markdown::Paragraph(vec![
+                MarkdownInLine::InlineCode(String::from("code"), None),
+                MarkdownInLine::Plain(String::from(" here")),
+            ])))
         );
+        assert_eq!(parse_markdown_block(""), Err(Error(("", ErrorKind::Tag))));
         assert_eq!(
-            parse_unordered_list_tag("--"),
-            Err(Error(("-", ErrorKind::Tag)))
+            parse_markdown_block("# h1\r\n"),
+            Ok(("", Markdown::Heading(1, vec![MarkdownInLine::Plain(String::from("h1"))])))
         );
+    }
+
+    #[test]
+    fn test_parse_setext_heading() {
+        let plain = |s: &str| vec![MarkdownInLine::Plain(String::from(s))];
+        assert_eq!(parse_setext_heading("Title\n=====\nrest\n"), Ok(("rest\n", (1, plain("Title")))));
+        assert_eq!(parse_setext_heading("*Sub*\n  -  \n"), Ok(("", (2, vec![MarkdownInLine::Italic(vec![MarkdownInLine::Plain(String::from("Sub"))])]))));
+        assert_eq!(parse_setext_heading("a\n=-=\n"), Err(Error(("a\n=-=\n", ErrorKind::Verify))));
+        assert_eq!(parse_setext_heading("\n===\n"), Err(Error(("\n===\n", ErrorKind::Verify))));
         assert_eq!(
-            parse_unordered_list_tag(""),
-            Err(Error(("", ErrorKind::Tag)))
+            parse_markdown("# ATX\nSetext\n---\n---\n"),
+            Ok((
+                "",
+                vec![
+                    Markdown::Heading(1, plain("ATX")),
+                    Markdown::Heading(2, plain("Setext")),
+                    Markdown::Rule,
+                ]
+            ))
         );
     }
 
     #[test]
-    fn test_parse_unordered_list_element() {
+    fn test_parse_definition_list() {
+        let plain = |s: &str| vec![MarkdownInLine::Plain(String::from(s))];
         assert_eq!(
-            parse_unordered_list_element("- this is an element\n"),
+            parse_definition_list("Apple\n: a *fruit*\n: a company\n\nRust\n: a language\n\nnot a term\n"),
             Ok((
-                "",
-                vec![MarkdownInLine::Plain(String::from(
-                    "this is an element"
-                ))]
+                "\nnot a term\n",
+                vec![
+                    (plain("Apple"), vec![
+                        vec![MarkdownInLine::Plain(String::from("a ")), MarkdownInLine::Italic(vec![MarkdownInLine::Plain(String::from("fruit"))])],
+                        plain("a company"),
+                    ]),
+                    (plain("Rust"), vec![plain("a language")]),
+                ]
             ))
         );
+        assert_eq!(parse_definition_list("a\n:b\n"), Err(Error(("b\n", ErrorKind::Tag))));
         assert_eq!(
-            parse_unordered_list_element("- this is an element\n- this is another element\n"),
+            parse_markdown("Term\n: definition\n"),
+            Ok(("", vec![Markdown::DefinitionList(vec![(plain("Term"), vec![plain("definition")])])]))
+        );
+    }
+
+    #[test]
+    fn test_parse_footnote_definition() {
+        let text = |s: &str| Markdown::Paragraph(lines(s));
+        assert_eq!(
+            parse_footnote_definition("[^note]: first\n    second\n\n    - item\nafter\n"),
             Ok((
-                "- this is another element\n",
-                vec![MarkdownInLine::Plain(String::from(
-                    "this is an element"
-                ))]
+                "after\n",
+                (
+                    String::from("note"),
+                    vec![
+                        text("first\nsecond"),
+                        Markdown::UnorderedList(vec![ListItem::new(vec![text("item")])], ListInfo::default()),
+                    ]
+                )
             ))
         );
+        assert_eq!(parse_footnote_definition("[^1]:\n"), Ok(("", (String::from("1"), vec![]))));
+        assert_eq!(parse_footnote_label("[^a b]: x\n"), Err(Error((" b]: x\n", ErrorKind::Tag))));
         assert_eq!(
-            parse_unordered_list_element(""),
-            Err(Error(("", ErrorKind::Tag)))
+            parse_markdown("[^1]: a\n"),
+            Ok(("", vec![Markdown::FootnoteDefinition(String::from("1"), vec![text("a")])]))
         );
-        assert_eq!(parse_unordered_list_element("- \n"), Ok(("", vec![])));
+    }
+
+    #[test]
+    fn test_parse_container() {
+        let text = |s: &str| Markdown::Paragraph(lines(s));
         assert_eq!(
-            parse_unordered_list_element("- "),
-            Err(Error(("", ErrorKind::Tag)))
+            parse_container("::: warning {open=true}\n*careful*\n:::\nafter\n"),
+            Ok((
+                "after\n",
+                (
+                    String::from("warning"),
+                    vec![(String::from("open"), Some(String::from("true")))],
+                    vec![Markdown::Paragraph(vec![MarkdownInLine::Italic(vec![MarkdownInLine::Plain(String::from("careful"))])])]
+                )
+            ))
         );
         assert_eq!(
-            parse_unordered_list_element("- test"),
-            Err(Error(("", ErrorKind::Tag)))
+            parse_markdown(":::: outer\n::: inner\na\n:::\nb\n::::\n"),
+            Ok(("", vec![Markdown::Container(String::from("outer"), vec![], vec![
+                Markdown::Container(String::from("inner"), vec![], vec![text("a")]),
+                text("b"),
+            ])]))
         );
         assert_eq!(
-            parse_unordered_list_element("-"),
-            Err(Error(("", ErrorKind::Tag)))
+            parse_markdown("::: tip\nunclosed\n"),
+            Ok(("", vec![Markdown::Container(String::from("tip"), vec![], vec![text("unclosed")])]))
         );
+        assert_eq!(parse_container(":::\n"), Err(Error((":::\n", ErrorKind::Verify))));
     }
 
     #[test]
-    fn test_parse_unordered_list() {
-        assert_eq!(
-            parse_unordered_list("- this is an element"),
-            Err(Error(("", ErrorKind::Tag)))
-        );
+    fn test_parse_directive() {
+        let text = |s: &str| Markdown::Paragraph(lines(s));
         assert_eq!(
-            parse_unordered_list("- this is an element\n"),
+            parse_directive("::youtube[Our *launch* [video](v.mp4)]{#intro .wide vid=\"a b\" autoplay}\nafter\n"),
             Ok((
-                "",
-                vec![vec![MarkdownInLine::Plain(String::from(
-                    "this is an element"
-                ))]]
+                "after\n",
+                Markdown::Directive {
+                    name: String::from("youtube"),
+                    label: vec![
+                        MarkdownInLine::Plain(String::from("Our ")),
+                        MarkdownInLine::Italic(vec![MarkdownInLine::Plain(String::from("launch"))]),
+                        MarkdownInLine::Plain(String::from(" ")),
+                        MarkdownInLine::Link(vec![MarkdownInLine::Plain(String::from("video"))], String::from("v.mp4"), None),
+                    ],
+                    attrs: vec![
+                        (String::from("id"), Some(String::from("intro"))),
+                        (String::from("class"), Some(String::from("wide"))),
+                        (String::from("vid"), Some(String::from("a b"))),
+                        (String::from("autoplay"), None),
+                    ],
+                    body: None,
+                }
             ))
         );
+        let options = ParserOptions { directives: true, ..ParserOptions::default() };
         assert_eq!(
-            parse_unordered_list("- this is an element\n- here is another\n"),
+            parse_markdown_with("::::spoiler[Ending]\n:::note\na\n:::\n::::\n::: tip\nb\n:::\n", &options),
+            Ok(("", vec![
+                Markdown::Directive {
+                    name: String::from("spoiler"),
+                    label: vec![MarkdownInLine::Plain(String::from("Ending"))],
+                    attrs: vec![],
+                    body: Some(vec![Markdown::Directive {
+                        name: String::from("note"),
+                        label: vec![],
+                        attrs: vec![],
+                        body: Some(vec![text("a")]),
+                    }]),
+                },
+                Markdown::Container(String::from("tip"), vec![], vec![text("b")]),
+            ]))
+        );
+        // only with the option, and nothing may follow the attributes
+        assert_eq!(parse_markdown("::toc\n"), Ok(("", vec![text("::toc")])));
+        assert_eq!(parse_directive("::a{b} c\n"), Err(Error(("::a{b} c\n", ErrorKind::Verify))));
+    }
+
+    #[test]
+    fn test_parse_math_block() {
+        assert_eq!(
+            parse_math_block("$$\n\\frac{a_1}{b^*} *\n  x_2\n$$\nafter\n"),
+            Ok(("after\n", String::from("\\frac{a_1}{b^*} *\n  x_2")))
+        );
+        assert_eq!(parse_math_block("$$ x^2 $$\n"), Ok(("", String::from("x^2"))));
+        assert_eq!(parse_math_block("$$a\nb$$\n"), Ok(("", String::from("a\nb"))));
+        assert_eq!(parse_math_block("$$\nunclosed\n"), Err(Error(("$$\nunclosed\n", ErrorKind::TakeUntil))));
+        assert_eq!(
+            parse_markdown("$$\na_b *c*\n$$\n$5\n"),
             Ok((
                 "",
                 vec![
-                    vec![MarkdownInLine::Plain(String::from(
-                        "this is an element"
-                    ))],
-                    vec![MarkdownInLine::Plain(String::from("here is another"))]
+                    Markdown::MathBlock(String::from("a_b *c*")),
+                    Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("$5"))]),
                 ]
             ))
         );
     }
 
     #[test]
-    fn test_parse_ordered_list_tag() {
-        assert_eq!(parse_ordered_list_tag("1. "), Ok(("", "1")));
-        assert_eq!(parse_ordered_list_tag("1234567. "), Ok(("", "1234567")));
-        assert_eq!(
-            parse_ordered_list_tag("3. and some more"),
-            Ok(("and some more", "3"))
-        );
+    fn test_parse_html_block() {
         assert_eq!(
-            parse_ordered_list_tag("1"),
-            Err(Error(("", ErrorKind::Tag)))
+            parse_html_block("<div class=\"note\">\r\n*not* markdown\r\n</div>\n\nafter\n"),
+            Ok(("\nafter\n", String::from("<div class=\"note\">\n*not* markdown\n</div>\n")))
         );
+        assert_eq!(parse_html_block("<!-- comment -->"), Ok(("", String::from("<!-- comment -->"))));
+        assert_eq!(parse_html_block("</p>\n"), Ok(("", String::from("</p>\n"))));
+        let not_html = [
+            "<https://example.com>\n",
+            "<3 you\n",
+            "< div>\n",
+            "<a.b>\n",
+            // inline tags with text after them
+            "<kbd>Ctrl</kbd>+C\n",
+            "<em>a</em>\n",
+            "<br/> b\n",
+        ];
+        for not_html in not_html {
+            assert_eq!(parse_html_block(not_html), Err(Error((not_html, ErrorKind::Verify))), "{:?}", not_html);
+        }
         assert_eq!(
-            parse_ordered_list_tag("1.and some more"),
-            Err(Error(("and some more", ErrorKind::Tag)))
+            parse_markdown("<br/>\n# heading\n\n<3\n"),
+            Ok((
+                "",
+                vec![
+                    Markdown::Html(String::from("<br/>\n# heading\n")),
+                    Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("<3"))]),
+                ]
+            ))
         );
+
+        // inline tags at the start of a line are text, only block-level ones interrupt
+        let html = |s: &str| MarkdownInLine::Html(String::from(s));
+        let plain = |s: &str| MarkdownInLine::Plain(String::from(s));
         assert_eq!(
-            parse_ordered_list_tag("1111."),
-            Err(Error(("", ErrorKind::Tag)))
+            parse_markdown("<kbd>Ctrl</kbd>+C copies *this*\n\nPress\n<kbd>Ctrl</kbd> now\n<span>\n"),
+            Ok((
+                "",
+                vec![
+                    Markdown::Paragraph(vec![
+                        html("<kbd>"),
+                        plain("Ctrl"),
+                        html("</kbd>"),
+                        plain("+C copies "),
+                        MarkdownInLine::Italic(vec![plain("this")]),
+                    ]),
+                    Markdown::Paragraph(vec![
+                        plain("Press"),
+                        MarkdownInLine::SoftBreak,
+                        html("<kbd>"),
+                        plain("Ctrl"),
+                        html("</kbd>"),
+                        plain(" now"),
+                        MarkdownInLine::SoftBreak,
+                        html("<span>"),
+                    ]),
+                ]
+            ))
         );
         assert_eq!(
-            parse_ordered_list_tag(""),
-            Err(Error(("", ErrorKind::TakeWhile1)))
+            parse_markdown("a\n<div>\n"),
+            Ok(("", vec![Markdown::Paragraph(vec![plain("a")]), Markdown::Html(String::from("<div>\n"))]))
         );
     }
 
     #[test]
-    fn test_parse_ordered_list_element() {
+    fn test_parse_comment_block() {
         assert_eq!(
-            parse_ordered_list_element("1. this is an element\n"),
+            parse_markdown("<!-- todo:\r\n\r\nreword -->  \nafter\n"),
             Ok((
                 "",
-                vec![MarkdownInLine::Plain(String::from(
-                    "this is an element"
-                ))]
+                vec![
+                    Markdown::Comment(String::from(" todo:\n\nreword ")),
+                    Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("after"))]),
+                ]
             ))
         );
+        assert_eq!(parse_comment_block("<!---->"), Ok(("", String::new())));
+        assert_eq!(parse_comment_block("<!-- a"), Err(Error((" a", ErrorKind::TakeUntil))));
+        // text after the comment makes it inline html
+        assert_eq!(parse_comment_block("<!-- a --> b\n"), Err(Error((" b\n", ErrorKind::Verify))));
         assert_eq!(
-            parse_ordered_list_element("1. this is an element\n1. here is another\n"),
+            parse_markdown("<!-- a --> b\n"),
+            Ok(("", vec![Markdown::Html(String::from("<!-- a --> b\n"))]))
+        );
+    }
+
+    #[test]
+    fn test_parse_toc_marker() {
+        assert_eq!(
+            parse_markdown("[TOC]\n# a\n[[_TOC_]] \n"),
             Ok((
-                "1. here is another\n",
-                vec![MarkdownInLine::Plain(String::from(
-                    "this is an element"
-                ))]
+                "",
+                vec![
+                    Markdown::TocMarker,
+                    Markdown::Heading(1, vec![MarkdownInLine::Plain(String::from("a"))]),
+                    Markdown::TocMarker,
+                ]
             ))
         );
+        assert_eq!(parse_toc_marker("[TOC] here\n"), Err(Error(("[TOC] here\n", ErrorKind::Verify))));
         assert_eq!(
-            parse_ordered_list_element(""),
-            Err(Error(("", ErrorKind::TakeWhile1)))
+            parse_markdown("[toc](#toc)\n"),
+            Ok(("", vec![Markdown::Paragraph(vec![MarkdownInLine::Link(vec![MarkdownInLine::Plain(String::from("toc"))], String::from("#toc"), None)])]))
         );
+    }
+
+    #[test]
+    fn test_parse_include() {
+        let options = ParserOptions { includes: true, ..ParserOptions::default() };
         assert_eq!(
-            parse_ordered_list_element(""),
-            Err(Error(("", ErrorKind::TakeWhile1)))
+            parse_markdown_with("text\n!include(parts/a.md)\n {{#include b.md }}\n", &options),
+            Ok((
+                "",
+                vec![
+                    Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("text"))]),
+                    Markdown::Include(PathBuf::from("parts/a.md")),
+                    Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from(" {{#include b.md }}"))]),
+                ]
+            ))
         );
-        assert_eq!(parse_ordered_list_element("1. \n"), Ok(("", vec![])));
         assert_eq!(
-            parse_ordered_list_element("1. test"),
-            Err(Error(("", ErrorKind::Tag)))
+            parse_markdown_with("{{#include b.md }}\n", &options),
+            Ok(("", vec![Markdown::Include(PathBuf::from("b.md"))]))
         );
+        assert_eq!(parse_include("!include()\n"), Err(Error(("!include()\n", ErrorKind::Verify))));
         assert_eq!(
-            parse_ordered_list_element("1. "),
-            Err(Error(("", ErrorKind::Tag)))
+            parse_markdown("!include(a.md)\n"),
+            Ok(("", vec![Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("!include(a.md)"))])]))
         );
+    }
+
+    #[test]
+    fn test_parse_thematic_break() {
+        for rule in ["---\n", "***\n", "___\n", " - - -\n", "*\t* *  *\n", "   _____\n"] {
+            assert_eq!(parse_thematic_break(rule), Ok(("", ())), "{:?}", rule);
+        }
+        for not_rule in ["--\n", "-*-\n", "--- a\n", "    ---\n"] {
+            assert_eq!(parse_thematic_break(not_rule), Err(Error((not_rule, ErrorKind::Verify))), "{:?}", not_rule);
+        }
         assert_eq!(
-            parse_ordered_list_element("1."),
-            Err(Error(("", ErrorKind::Tag)))
+            parse_markdown("a\n* * *\n- b\n- - -\n"),
+            Ok((
+                "",
+                vec![
+                    Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("a"))]),
+                    Markdown::Rule,
+                    Markdown::UnorderedList(vec![ListItem::new(vec![Markdown::Paragraph(vec![
+                        MarkdownInLine::Plain(String::from("b"))
+                    ])])], ListInfo::default()),
+                    Markdown::Rule,
+                ]
+            ))
         );
     }
 
     #[test]
-    fn test_parse_ordered_list() {
+    fn test_parse_table() {
+        let plain = |s: &str| vec![MarkdownInLine::Plain(String::from(s))];
         assert_eq!(
-            parse_ordered_list("1. this is an element\n"),
+            parse_table("| a | **b** | c |\n|:--|:-:|--:|\n| d | e |\n| f | g | h | i |\nafter\n"),
             Ok((
-                "",
-                vec![vec![MarkdownInLine::Plain(String::from(
-                    "this is an element"
-                ))]]
+                "after\n",
+                Table {
+                    alignments: vec![Alignment::Left, Alignment::Center, Alignment::Right],
+                    header: vec![plain("a"), vec![MarkdownInLine::Bold(vec![MarkdownInLine::Plain(String::from("b"))])], plain("c")],
+                    rows: vec![vec![plain("d"), plain("e"), vec![]], vec![plain("f"), plain("g"), plain("h")]],
+                    caption: None,
+                }
             ))
         );
         assert_eq!(
-            parse_ordered_list("1. test"),
-            Err(Error(("", ErrorKind::Tag)))
+            parse_table("a | b\n--- | ---\n"),
+            Ok(("", Table { alignments: vec![Alignment::None; 2], header: vec![plain("a"), plain("b")], rows: vec![], caption: None }))
         );
+        // the delimiter row needs as many cells as the header, each with a dash
+        assert_eq!(parse_table("a | b\n---\n"), Err(Error(("a | b\n---\n", ErrorKind::Verify))));
+        assert_eq!(parse_table("a | b\n:: | --\n"), Err(Error(("a | b\n:: | --\n", ErrorKind::Verify))));
         assert_eq!(
-            parse_ordered_list("1. this is an element\n2. here is another\n"),
+            parse_markdown("1 | 2\n--|--\n"),
+            Ok(("", vec![Markdown::Table(Table {
+                alignments: vec![Alignment::None; 2],
+                header: vec![plain("1"), plain("2")],
+                rows: vec![],
+                caption: None,
+            })]))
+        );
+    }
+
+    #[test]
+    fn test_parse_grid_table() {
+        let text = |s: &str| vec![Markdown::Paragraph(lines(s))];
+        assert_eq!(
+            parse_block_table("+-------+:-----:+\n| Fruit | Price |\n+=======+=======+\n| a     | $1    |\n| - b   | more  |\n+-------+-------+\n| c     |       |\n+-------+-------+\nTable: Fruits\nafter\n"),
             Ok((
-                "",
-                vec![
-                    vec!(MarkdownInLine::Plain(String::from(
-                        "this is an element"
-                    ))),
-                    vec![MarkdownInLine::Plain(String::from("here is another"))]
-                ]
+                "after\n",
+                BlockTable {
+                    alignments: vec![Alignment::None; 2],
+                    header: Some(vec![text("Fruit"), text("Price")]),
+                    rows: vec![
+                        vec![
+                            vec![
+                                Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("a"))]),
+                                Markdown::UnorderedList(vec![ListItem::new(text("b"))], ListInfo::default()),
+                            ],
+                            text("$1\nmore"),
+                        ],
+                        vec![text("c"), vec![]],
+                    ],
+                    caption: Some(vec![MarkdownInLine::Plain(String::from("Fruits"))]),
+                }
             ))
         );
+        // without a header the first line has the alignments
+        assert_eq!(
+            parse_markdown("+:--+--:+\n| a | b |\n+---+---+\n"),
+            Ok(("", vec![Markdown::BlockTable(BlockTable {
+                alignments: vec![Alignment::Left, Alignment::Right],
+                header: None,
+                rows: vec![vec![text("a"), text("b")]],
+                caption: None,
+            })]))
+        );
+        // the last row needs a border under it
+        let open = "+---+\n| a |\n";
+        assert_eq!(parse_block_table(open), Err(Error((open, ErrorKind::Verify))));
     }
 
     #[test]
-    fn test_parse_codeblock() {
+    fn test_parse_multiline_table() {
+        let text = |s: &str| vec![Markdown::Paragraph(lines(s))];
         assert_eq!(
-            parse_code_block("```bash\npip install foobar\n```"),
-            Ok(("", ("bash", "pip install foobar\n")))
+            parse_markdown("-----------------------------\n Centered  Default      Right\n  Header   Aligned    Aligned\n---------- ------- ----------\n   First   row     text over\n                   two lines\n\n  Second   row            5.0\n-----------------------------\n"),
+            Ok(("", vec![Markdown::BlockTable(BlockTable {
+                alignments: vec![Alignment::Center, Alignment::None, Alignment::Right],
+                header: Some(vec![text("Centered\nHeader"), text("Default\nAligned"), text("Right\nAligned")]),
+                rows: vec![
+                    vec![text("First"), text("row"), text("text over\ntwo lines")],
+                    vec![text("Second"), text("row"), text("5.0")],
+                ],
+                caption: None,
+            })]))
         );
         assert_eq!(
-            parse_code_block("```python\nimport foobar\n\nfoobar.pluralize('word') # returns 'words'\nfoobar.pluralize('goose') # returns 'geese'\nfoobar.singularize('phenomena') # returns 'phenomenon'\n```"),
-            Ok(("", ("python", "import foobar\n\nfoobar.pluralize('word') # returns 'words'\nfoobar.pluralize('goose') # returns 'geese'\nfoobar.singularize('phenomena') # returns 'phenomenon'\n")))
+            parse_block_table("---- ----\na    b\n---- ----\n"),
+            Ok(("", BlockTable {
+                alignments: vec![Alignment::Left, Alignment::Left],
+                header: None,
+                rows: vec![vec![text("a"), text("b")]],
+                caption: None,
+            }))
+        );
+        // a single column is a rule, a paragraph and a rule
+        assert_eq!(
+            parse_markdown("---\ntext\n\n---\n"),
+            Ok(("", vec![Markdown::Rule, Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("text"))]), Markdown::Rule]))
         );
     }
 
     #[test]
-    fn test_parse_markdown() {
+    fn test_parse_table_caption() {
+        let plain = |s: &str| vec![MarkdownInLine::Plain(String::from(s))];
+        let table = |caption| Table {
+            alignments: vec![Alignment::None; 2],
+            header: vec![plain("a"), plain("b")],
+            rows: vec![vec![plain("1"), plain("2")]],
+            caption,
+        };
         assert_eq!(
-            parse_markdown("# Foobar\n\nFoobar is a Python library for dealing with word pluralization.\n\n```bash\n#!/bin/bash\npip install foobar\n```\n## Installation\n\nUse the package manager [pip](https://pip.pypa.io/en/stable/) to install foobar.\n```python\nimport foobar\n\nfoobar.pluralize('word') # returns 'words'\nfoobar.pluralize('goose') # returns 'geese'\nfoobar.singularize('phenomena') # returns 'phenomenon'\n```"),
-            Ok(("", vec![
-                Markdown::Heading(1, vec![MarkdownInLine::Plain(String::from("Foobar"))]),
-                Markdown::Text(vec![]),
-                Markdown::Text(vec![MarkdownInLine::Plain(String::from("Foobar is a Python library for dealing with word pluralization."))]),
-                Markdown::Text(vec![]),
-                Markdown::CodeBlock(String::from("#!/bin/bash\npip install foobar\n"), Some(String::from("bash"))),
-                Markdown::Text(vec![]),
-                Markdown::Heading(2, vec![MarkdownInLine::Plain(String::from("Installation"))]),
-                Markdown::Text(vec![]),
-                Markdown::Text(vec![
-                    MarkdownInLine::Plain(String::from("Use the package manager ")),
-                    MarkdownInLine::Link(String::from("pip"), String::from("https://pip.pypa.io/en/stable/")),
-                    MarkdownInLine::Plain(String::from(" to install foobar.")),
-                ]),
-                Markdown::CodeBlock(String::from("import foobar\n\nfoobar.pluralize('word') # returns 'words'\nfoobar.pluralize('goose') # returns 'geese'\nfoobar.singularize('phenomena') # returns 'phenomenon'\n"), Some(String::from("python"))),
-            ]))
-        )
+            parse_table("Table: *Totals* by year\na | b\n--|--\n1 | 2\n[not the caption]\n"),
+            Ok((
+                "[not the caption]\n",
+                table(Some(vec![
+                    MarkdownInLine::Italic(vec![MarkdownInLine::Plain(String::from("Totals"))]),
+                    MarkdownInLine::Plain(String::from(" by year")),
+                ]))
+            ))
+        );
+        assert_eq!(
+            parse_markdown("text\na | b\n--|--\n1 | 2\n[Totals]\n"),
+            Ok((
+                "",
+                vec![
+                    Markdown::Paragraph(plain("text")),
+                    Markdown::Table(table(Some(plain("Totals")))),
+                ]
+            ))
+        );
+        assert_eq!(
+            parse_markdown("a | b\n--|--\n1 | 2\n\nTable:\n"),
+            Ok(("", vec![Markdown::Table(table(None)), Markdown::Paragraph(plain("Table:"))]))
+        );
     }
 
     #[test]
@@ -789,16 +5623,14 @@ mod tests {
     fn test_parse_quote_text() {
         assert_eq!(
             parse_quote_line("> this is a quote\n"),
-            Ok(("", vec![
-                MarkdownInLine::Plain(String::from("this is a quote"))
-            ]))
+            Ok(("", "this is a quote\n"))
         );
         assert_eq!(
             parse_quote_line("> **this is a bold quote**\n> this is another quote\n"),
-            Ok(("> this is another quote\n", vec![
-                MarkdownInLine::Bold(String::from("this is a bold quote"))
-            ]))
+            Ok(("> this is another quote\n", "**this is a bold quote**\n"))
         );
+        assert_eq!(parse_quote_line(">\n"), Ok(("", "\n")));
+        assert_eq!(parse_quote_line(">> nested\n"), Ok(("", "> nested\n")));
         assert_eq!(
             parse_quote_line(""),
             Err(Error(("", ErrorKind::Tag)))
@@ -817,23 +5649,327 @@ mod tests {
     fn test_parse_quote() {
         assert_eq!(
             parse_quote("> this is a quote\n"),
-            Ok(("", vec![
-                vec![MarkdownInLine::Plain(String::from("this is a quote"))],
-            ]))
+            Ok(("", (vec![
+                Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("this is a quote"))]),
+            ], None)))
         );
         assert_eq!(
             parse_quote("> **this is a bold quote**\n> this is another quote\n"),
-            Ok(("", vec![
-                vec![MarkdownInLine::Bold(String::from("this is a bold quote"))],
-                vec![MarkdownInLine::Plain(String::from("this is another quote"))]
-            ]))
+            Ok(("", (vec![
+                Markdown::Paragraph(vec![
+                    MarkdownInLine::Bold(vec![MarkdownInLine::Plain(String::from("this is a bold quote"))]),
+                    MarkdownInLine::SoftBreak,
+                    MarkdownInLine::Plain(String::from("this is another quote")),
+                ]),
+            ], None)))
         );
         assert_eq!(
             parse_quote("> - this is a list inside a quote\n> - this the second list\n"),
+            Ok(("", (vec![
+                Markdown::UnorderedList(vec![
+                    ListItem::new(vec![Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("this is a list inside a quote"))])]),
+                    ListItem::new(vec![Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("this the second list"))])]),
+                ], ListInfo::default()),
+            ], None)))
+        );
+    }
+
+    #[test]
+    fn test_parse_quote_cite() {
+        let text = |s: &str| Markdown::Paragraph(lines(s));
+        assert_eq!(
+            parse_quote("> Be yourself.\n> — *Oscar* Wilde \n"),
+            Ok(("", (vec![text("Be yourself.")], Some(vec![
+                MarkdownInLine::Italic(vec![MarkdownInLine::Plain(String::from("Oscar"))]),
+                MarkdownInLine::Plain(String::from(" Wilde")),
+            ]))))
+        );
+        assert_eq!(
+            parse_markdown("> a\n> > b\n> -- Ann\n"),
+            Ok(("", vec![Markdown::Quote {
+                body: vec![text("a"), Markdown::Quote { body: vec![text("b")], cite: None }],
+                cite: Some(vec![MarkdownInLine::Plain(String::from("Ann"))]),
+            }]))
+        );
+        // only the last line
+        assert_eq!(
+            parse_quote("> -- Ann\n> a\n"),
+            Ok(("", (vec![text("-- Ann\na")], None)))
+        );
+        // and only below a body
+        assert_eq!(parse_quote("> -- only cite\n"), Ok(("", (vec![text("-- only cite")], None))));
+        assert_eq!(parse_quote(">\n> — Ann\n"), Ok(("", (vec![text("— Ann")], None))));
+    }
+
+    #[test]
+    fn test_parse_quote_blocks() {
+        assert_eq!(
+            parse_quote("> # heading\n> - item\n>   more\n> ```rust\n> > not a quote\n>\n> ```\n"),
+            Ok((
+                "",
+                (vec![
+                    Markdown::Heading(1, vec![MarkdownInLine::Plain(String::from("heading"))]),
+                    Markdown::UnorderedList(vec![ListItem::new(vec![
+                        Markdown::Paragraph(lines("item\nmore")),
+                    ])], ListInfo::default()),
+                    Markdown::CodeBlock(String::from("> not a quote\n\n"), FenceInfo::new("rust")),
+                ], None)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_admonition() {
+        let text = |s: &str| Markdown::Paragraph(lines(s));
+        assert_eq!(
+            parse_admonition("> [!WARNING]\n> Don't *run* this.\n> - really\n"),
+            Ok((
+                "",
+                (
+                    String::from("WARNING"),
+                    vec![],
+                    vec![
+                        Markdown::Paragraph(vec![
+                            MarkdownInLine::Plain(String::from("Don't ")),
+                            MarkdownInLine::Italic(vec![MarkdownInLine::Plain(String::from("run"))]),
+                            MarkdownInLine::Plain(String::from(" this.")),
+                        ]),
+                        Markdown::UnorderedList(vec![ListItem::new(vec![text("really")])], ListInfo::default()),
+                    ]
+                )
+            ))
+        );
+        assert_eq!(
+            parse_markdown("> [!tip]- Custom title\n> body\n"),
+            Ok(("", vec![Markdown::Admonition {
+                kind: String::from("TIP"),
+                title: vec![MarkdownInLine::Plain(String::from("Custom title"))],
+                body: vec![text("body")],
+            }]))
+        );
+        // anything else is a quote
+        assert_eq!(
+            parse_markdown("> !NOTE\n"),
+            Ok(("", vec![Markdown::Quote { body: vec![text("!NOTE")], cite: None }]))
+        );
+    }
+
+    #[test]
+    fn test_parse_nested_quote() {
+        let text = |s: &str| Markdown::Paragraph(lines(s));
+        assert_eq!(
+            parse_quote("> a\n> > b\n> >> c\n> d\n"),
+            Ok((
+                "",
+                (vec![
+                    text("a"),
+                    Markdown::Quote { body: vec![text("b"), Markdown::Quote { body: vec![text("c")], cite: None }], cite: None },
+                    text("d"),
+                ], None)
+            ))
+        );
+        assert_eq!(
+            parse_quote(">> a\n>\n>> b\n"),
+            Ok(("", (vec![Markdown::Quote { body: vec![text("a")], cite: None }, Markdown::Quote { body: vec![text("b")], cite: None }], None)))
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let options = ParserOptions::default();
+        assert_eq!(parse("", &options), Ok(vec![]));
+        assert_eq!(
+            parse("# h1\ntext", &options),
+            Ok(vec![
+                Markdown::Heading(1, vec![MarkdownInLine::Plain(String::from("h1"))]),
+                Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("text"))]),
+            ])
+        );
+        // an unmatched delimiter is text, not a parse error
+        for text in ["see [1", "a ` b", "![x", "[a](b", "**a", "a <b", "a^[b", "$5"] {
+            assert_eq!(
+                parse(&format!("# h1\n{}\n", text), &options),
+                Ok(vec![
+                    Markdown::Heading(1, vec![MarkdownInLine::Plain(String::from("h1"))]),
+                    Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from(text))]),
+                ])
+            );
+        }
+        assert_eq!(
+            parse("a\nsee [1 and `\n![ or [a](b\n", &options),
+            Ok(vec![Markdown::Paragraph(lines("a\nsee [1 and `\n![ or [a](b"))])
+        );
+        assert_eq!(
+            parse("# [h1\n[a\n===\n", &options),
+            Ok(vec![
+                Markdown::Heading(1, vec![MarkdownInLine::Plain(String::from("[h1"))]),
+                Markdown::Heading(1, vec![MarkdownInLine::Plain(String::from("[a"))]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_crlf() {
+        let options = ParserOptions::default();
+        assert_eq!(
+            parse("# h1\r\n- a\r- b\r\n```rust\r\nlet a = 1;\r\n```\r\ntext\r\n", &options),
+            Ok(vec![
+                Markdown::Heading(1, vec![MarkdownInLine::Plain(String::from("h1"))]),
+                Markdown::UnorderedList(vec![
+                    ListItem::new(vec![Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("a"))])]),
+                    ListItem::new(vec![Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("b"))])]),
+                ], ListInfo::default()),
+                Markdown::CodeBlock(String::from("let a = 1;\n"), FenceInfo::new("rust")),
+                Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("text"))]),
+            ])
+        );
+        assert_eq!(
+            parse_markdown("> a\r\n> b\r\n"),
             Ok(("", vec![
-                vec![MarkdownInLine::Plain(String::from("- this is a list inside a quote"))],
-                vec![MarkdownInLine::Plain(String::from("- this the second list"))]
+                Markdown::Quote { body: vec![Markdown::Paragraph(lines("a\nb"))], cite: None },
+            ]))
+        );
+        assert_eq!(
+            parse_markdown("```sh\r\nls\r\n```"),
+            Ok(("", vec![Markdown::CodeBlock(String::from("ls\n"), FenceInfo::new("sh"))]))
+        );
+        assert_eq!(
+            parse_markdown_text("a *b*\r\nc"),
+            Ok(("c", vec![
+                MarkdownInLine::Plain(String::from("a ")),
+                MarkdownInLine::Italic(vec![MarkdownInLine::Plain(String::from("b"))]),
             ]))
         );
+        assert_eq!(normalize_line_endings("a\r\nb\rc\n"), "a\nb\nc\n");
+    }
+
+    #[test]
+    fn test_parse_bytes() {
+        let options = ParserOptions::default();
+        let heading = vec![Markdown::Heading(1, vec![MarkdownInLine::Plain(String::from("h1"))])];
+        assert_eq!(parse_bytes(b"\xEF\xBB\xBF# h1\n", &options), Ok(heading.clone()));
+        assert_eq!(parse_bytes(b"# h1", &options), Ok(heading.clone()));
+        assert_eq!(parse("\u{FEFF}# h1\n", &options), Ok(heading));
+        assert_eq!(
+            parse_bytes(b"caf\xE9\n", &options),
+            Ok(vec![Markdown::Paragraph(vec![MarkdownInLine::Plain(String::from("caf\u{FFFD}"))])])
+        );
+
+        let options = ParserOptions {
+            invalid_utf8: InvalidUtf8::Error,
+            ..ParserOptions::default()
+        };
+        assert_eq!(
+            parse_bytes(b"\xEF\xBB\xBFcaf\xE9\n", &options),
+            Err(ParseError::InvalidUtf8(6))
+        );
+        assert!(parse_bytes("café\n".as_bytes(), &options).is_ok());
+
+        let mut options = ParserOptions::default();
+        options.limits.max_input_size = Some(2);
+        assert_eq!(parse_bytes(b"\xFF\xFF\xFF", &options), Err(ParseError::InputTooLarge(3, 2)));
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        let mut options = ParserOptions::default();
+        options.limits.max_input_size = Some(4);
+        assert_eq!(parse("# h1\n", &options), Err(ParseError::InputTooLarge(5, 4)));
+
+        let mut options = ParserOptions::default();
+        options.limits.max_nodes = Some(4);
+        assert_eq!(parse("- a\n- b\n", &options), Err(ParseError::TooManyNodes(4)));
+        assert!(parse("- a\n", &options).is_ok());
+
+        let mut options = ParserOptions::default();
+        options.limits.max_depth = Some(2);
+        assert_eq!(parse("> quote\n", &options), Err(ParseError::TooDeep(2)));
+        assert!(parse("text\n", &options).is_ok());
+
+        let mut options = ParserOptions::default();
+        options.limits.max_depth = Some(8);
+        assert_eq!(parse("> > > > > > > > > deep\n", &options), Err(ParseError::TooDeep(8)));
+        assert_eq!(parse(&"> ".repeat(10_000), &ParserOptions::default()), Err(ParseError::TooDeep(64)));
+
+        let options = ParserOptions {
+            limits: Limits::none(),
+            ..ParserOptions::default()
+        };
+        assert!(parse(&"- item\n".repeat(1000), &options).is_ok());
+    }
+
+    #[test]
+    fn test_inline_limits() {
+        let mut options = ParserOptions::default();
+        options.limits.max_depth = Some(4);
+        assert!(parse("*a **b** c*\n", &options).is_ok());
+        assert_eq!(parse("*a **b _c_ d** e*\n", &options), Err(ParseError::TooDeep(4)));
+        assert_eq!(parse("[*a **b** c*](u)\n", &options), Err(ParseError::TooDeep(4)));
+        assert_eq!(parse("> ^[a *b*]\n", &options), Err(ParseError::TooDeep(4)));
+
+        let deep = |open: &str, close: &str| {
+            format!("{}a{}\n", open.repeat(5000), close.repeat(5000))
+        };
+        for text in [deep("[", "](u)"), deep("^[", "]"), deep("^[*a* [", "](u)]")] {
+            assert_eq!(parse(&text, &ParserOptions::default()), Err(ParseError::TooDeep(64)));
+        }
+
+        let mut options = ParserOptions::default();
+        options.limits.max_nodes = Some(10);
+        assert_eq!(parse(&"> - a\n".repeat(20), &options), Err(ParseError::TooManyNodes(10)));
+    }
+
+    #[test]
+    fn test_nested_lists_time() {
+        // every level's paragraph used to parse all the lists below it to see where it ends
+        let nested = |depth| -> String {
+            (0..depth).map(|n| format!("{}- a\n", "  ".repeat(n))).collect()
+        };
+        let start = Instant::now();
+        assert_eq!(parse(&nested(30), &ParserOptions::default()).unwrap()[0].depth(), 62);
+        assert_eq!(parse(&nested(200), &ParserOptions::default()), Err(ParseError::TooDeep(64)));
+        let options = ParserOptions {
+            limits: Limits::none(),
+            ..ParserOptions::default()
+        };
+        assert_eq!(parse(&nested(100), &options).unwrap()[0].depth(), 202);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_parse_with_stats() {
+        let (blocks, stats) = parse_with_stats(
+            "# **h1**\n- a\n- b [link](url)\n",
+            &ParserOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(stats.bytes, 29);
+        assert_eq!(stats.max_depth, 5);
+        assert_eq!(
+            stats.nodes.into_iter().collect::<Vec<_>>(),
+            vec![
+                ("Bold", 1),
+                ("Heading", 1),
+                ("Link", 1),
+                ("ListItem", 2),
+                ("Paragraph", 2),
+                ("Plain", 4),
+                ("UnorderedList", 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_cancel() {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let options = ParserOptions {
+            cancel: Some(cancel.clone()),
+            ..ParserOptions::default()
+        };
+        assert!(parse("# h1\n", &options).is_ok());
+        cancel.store(true, Ordering::Relaxed);
+        assert_eq!(parse("# h1\n", &options), Err(ParseError::Cancelled));
+        assert_eq!(parse("", &options), Ok(vec![]));
     }
 }
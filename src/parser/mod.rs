@@ -1,2 +1,4 @@
+pub mod emoji;
 mod html;
-mod markdown;
+pub mod markdown;
+pub mod options;
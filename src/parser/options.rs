@@ -0,0 +1,161 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+// Upper bounds for a single parse, `None` disables a limit.
+// The defaults are meant to be safe for untrusted input, use Limits::none() for trusted documents.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Limits {
+    // in bytes
+    pub max_input_size: Option<usize>,
+    // blocks, list items and inline elements
+    pub max_nodes: Option<usize>,
+    // a block is depth 1, its direct children depth 2 ...
+    pub max_depth: Option<usize>,
+}
+
+impl Limits {
+    pub fn none() -> Self {
+        Limits {
+            max_input_size: None,
+            max_nodes: None,
+            max_depth: None,
+        }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_input_size: Some(4 * 1024 * 1024),
+            max_nodes: Some(1_000_000),
+            max_depth: Some(64),
+        }
+    }
+}
+
+// what to do with headings that have more than 6 `#`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum HeadingOverflow {
+    // ####### h7 becomes a level 6 heading
+    Clamp,
+    // not a heading, parsed as text like CommonMark does
+    #[default]
+    Text,
+    // Heading(7, ..)
+    Keep,
+}
+
+// what parse_bytes does with byte sequences that are not valid UTF-8
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum InvalidUtf8 {
+    // replace them with U+FFFD
+    #[default]
+    Replace,
+    // fail with ParseError::InvalidUtf8
+    Error,
+}
+
+#[derive(Clone, Debug)]
+pub struct ParserOptions {
+    pub limits: Limits,
+    // set to true from any thread to abort the parse at the next block boundary
+    pub cancel: Option<Arc<AtomicBool>>,
+    // `code`rust: take the word directly after an inline code span as its language
+    pub inline_code_language: bool,
+    pub heading_overflow: HeadingOverflow,
+    pub invalid_utf8: InvalidUtf8,
+    // chat style input: the lines of a paragraph are joined by a HardBreak instead
+    // of a newline, like GFM's `breaks` option
+    pub hard_breaks: bool,
+    // links and images with a destination scheme not in this list (compared ignoring case)
+    // are left as their plain text, so `javascript:` and the like never reach the output.
+    // Relative destinations have no scheme and are kept, None allows every scheme.
+    pub url_schemes: Option<Vec<String>>,
+    // task list items also get a priority and due date, see markdown::Task
+    pub tasks: bool,
+    // a first block of `Key: value` lines is MultiMarkdown metadata, see
+    // document::FrontMatterFormat
+    pub metadata_block: bool,
+    // !include(path) and {{#include path}} lines are Markdown::Include blocks
+    pub includes: bool,
+    // ::name[label]{key=value} and :::name blocks are Markdown::Directive, `::: name`
+    // with a space stays a Container
+    pub directives: bool,
+    // https://..., http://... and www.... in text are links like GFM's autolink extension,
+    // www. ones get an http:// destination
+    pub autolinks: bool,
+    // :smile: shortcodes are MarkdownInLine::Emoji, parser::emoji::emoji has the
+    // characters for the common ones
+    pub emoji: bool,
+    // ==text== is MarkdownInLine::Highlight like in Obsidian
+    pub highlight: bool,
+    // H~2~O and x^2^ are MarkdownInLine::Subscript and Superscript, ~~text~~ stays
+    // strikethrough
+    pub sub_superscript: bool,
+    // [[Page Name|alias]] is a MarkdownInLine::WikiLink like in Obsidian, [[a]](b) is
+    // then a wiki link followed by text
+    pub wiki_links: bool,
+}
+
+impl Default for ParserOptions {
+    fn default() -> Self {
+        ParserOptions {
+            limits: Limits::default(),
+            cancel: None,
+            inline_code_language: false,
+            heading_overflow: HeadingOverflow::default(),
+            invalid_utf8: InvalidUtf8::default(),
+            hard_breaks: false,
+            url_schemes: Some(vec![
+                String::from("http"),
+                String::from("https"),
+                String::from("mailto"),
+            ]),
+            tasks: false,
+            metadata_block: false,
+            includes: false,
+            directives: false,
+            autolinks: false,
+            emoji: false,
+            highlight: false,
+            sub_superscript: false,
+            wiki_links: false,
+        }
+    }
+}
+
+impl ParserOptions {
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+    }
+
+    // whether `url` may be used as a link or image destination
+    pub fn is_allowed_url(&self, url: &str) -> bool {
+        match (&self.url_schemes, url_scheme(url)) {
+            (Some(schemes), Some(scheme)) => {
+                schemes.iter().any(|s| s.eq_ignore_ascii_case(&scheme))
+            }
+            _ => true,
+        }
+    }
+}
+
+// The scheme of `url`, if it has one. Browsers ignore whitespace and control characters
+// in a scheme (`java\tscript:`), so they are skipped here as well.
+fn url_scheme(url: &str) -> Option<String> {
+    let mut scheme = String::new();
+    for c in url
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace() && !c.is_ascii_control())
+    {
+        match c {
+            ':' if !scheme.is_empty() => return Some(scheme),
+            'a'..='z' | 'A'..='Z' => scheme.push(c),
+            '0'..='9' | '+' | '-' | '.' if !scheme.is_empty() => scheme.push(c),
+            _ => return None,
+        }
+    }
+    None
+}
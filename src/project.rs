@@ -0,0 +1,506 @@
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::thread;
+
+use crate::document::{slugify, Document, FrontMatter, FrontMatterFormat, OutlineEntry};
+use crate::parser::markdown::MarkdownInLine;
+use crate::parser::markdown::ParseError;
+use crate::parser::options::ParserOptions;
+
+const EXTENSIONS: &[&str] = &["md", "markdown"];
+// flat `key: value` YAML, the front matter defaults of the files in its directory
+const DEFAULTS: &str = "_defaults.yaml";
+
+// one markdown file of a project
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProjectFile {
+    pub document: Document,
+    pub outline: Vec<OutlineEntry>,
+    // the other files of the project this one links to, relative to the project root
+    pub links: Vec<PathBuf>,
+}
+
+// every markdown file below a directory, keyed by its path relative to that directory
+#[derive(Clone, Debug, PartialEq)]
+pub struct Project {
+    pub root: PathBuf,
+    pub files: BTreeMap<PathBuf, ProjectFile>,
+}
+
+#[derive(Debug)]
+pub enum ProjectError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, ParseError),
+}
+
+impl fmt::Display for ProjectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProjectError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            ProjectError::Parse(path, e) => write!(f, "{}: {}", path.display(), e),
+        }
+    }
+}
+
+impl error::Error for ProjectError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ProjectError::Io(_, e) => Some(e),
+            ProjectError::Parse(_, e) => Some(e),
+        }
+    }
+}
+
+impl Project {
+    // Parses every .md and .markdown file below `root` on all cores. Hidden files and
+    // directories (starting with `.`) and symlinks are skipped.
+    // The fields of a _defaults.yaml are added to the front matter of every file below its
+    // directory. A file's own front matter wins over them, and the defaults of a directory
+    // over those of the directories it is in.
+    pub fn load(root: impl AsRef<Path>, options: &ParserOptions) -> Result<Self, ProjectError> {
+        let root = root.as_ref();
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!("load_project", root = %root.display(), files = tracing::field::Empty);
+        #[cfg(feature = "tracing")]
+        let _enter = span.enter();
+
+        let mut paths = Vec::new();
+        let mut defaults = BTreeMap::new();
+        find_markdown(root, Path::new(""), &mut paths, &mut defaults)?;
+
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk = paths.len().div_ceil(threads).max(1);
+        let documents = thread::scope(|scope| {
+            let workers: Vec<_> = paths
+                .chunks(chunk)
+                .map(|paths| {
+                    // the documents parsed on this thread belong to the project
+                    #[cfg(feature = "tracing")]
+                    let span = span.clone();
+                    scope.spawn(move || {
+                        #[cfg(feature = "tracing")]
+                        let _enter = span.enter();
+                        paths
+                            .iter()
+                            .map(|path| load_document(root, path, options))
+                            .collect::<Result<Vec<_>, _>>()
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .map(|worker| worker.join().expect("parser thread panicked"))
+                .collect::<Result<Vec<_>, _>>()
+        })?;
+
+        let mut files = BTreeMap::new();
+        for (path, mut document) in paths.iter().zip(documents.into_iter().flatten()) {
+            apply_defaults(&mut document, path, &defaults);
+            let file = ProjectFile {
+                outline: document.outline(),
+                links: Vec::new(),
+                document,
+            };
+            files.insert(path.clone(), file);
+        }
+
+        // only now every path is known, links to anything else are left out
+        let links: Vec<_> = files
+            .iter()
+            .map(|(path, file)| {
+                let mut links: Vec<_> = file
+                    .document
+                    .links()
+                    .into_iter()
+                    .filter_map(|url| resolve_link(path, url))
+                    .filter(|target| files.contains_key(target))
+                    .collect();
+                links.sort();
+                links.dedup();
+                links
+            })
+            .collect();
+        for (file, links) in files.values_mut().zip(links) {
+            file.links = links;
+        }
+
+        #[cfg(feature = "tracing")]
+        span.record("files", files.len());
+
+        Ok(Project {
+            root: root.to_path_buf(),
+            files,
+        })
+    }
+
+    // the files linking to `path`
+    pub fn backlinks(&self, path: &Path) -> Vec<&Path> {
+        self.files
+            .iter()
+            .filter(|(_, file)| file.links.iter().any(|link| link == path))
+            .map(|(source, _)| source.as_path())
+            .collect()
+    }
+
+    // the url of every file
+    pub fn permalinks(&self, permalinks: &Permalinks) -> BTreeMap<PathBuf, String> {
+        self.files
+            .iter()
+            .map(|(path, file)| (path.clone(), permalinks.permalink(path, file)))
+            .collect()
+    }
+
+    // Points every link to another file of the project at that file's permalink,
+    // keeping the fragment: [setup](setup.md#install) becomes [setup](/setup/#install).
+    pub fn rewrite_links(&mut self, permalinks: &Permalinks) {
+        let urls = self.permalinks(permalinks);
+        for (path, file) in self.files.iter_mut() {
+            for block in file.document.blocks.iter_mut() {
+                block.for_each_inline_mut(&mut |inline| {
+                    if let MarkdownInLine::Link(_, url, _) = inline {
+                        let target = resolve_link(path, url).and_then(|target| urls.get(&target));
+                        if let Some(target) = target {
+                            let fragment = url.find('#').map_or("", |n| &url[n..]);
+                            *url = format!("{}{}", target, fragment);
+                        }
+                    }
+                });
+            }
+        }
+    }
+}
+
+// Turns project paths into urls. The pattern can use
+//   :path    the path without its extension, an index file is its directory
+//   :slug    the file name without extension or date prefix, slugified
+//   :title   the first heading slugified, :slug if there is none
+//   :year, :month, :day   from a `2024-01-31-` file name prefix
+// Segments that end up empty are dropped, "/:year/:slug/" is "/post/" for "post.md".
+#[derive(Clone, Debug, PartialEq)]
+pub struct Permalinks {
+    pub pattern: String,
+}
+
+impl Default for Permalinks {
+    fn default() -> Self {
+        Permalinks {
+            pattern: String::from("/:path/"),
+        }
+    }
+}
+
+impl Permalinks {
+    pub fn permalink(&self, path: &Path, file: &ProjectFile) -> String {
+        let stem = path
+            .file_stem()
+            .map_or(String::new(), |s| s.to_string_lossy().into_owned());
+        let (date, name) = split_date(&stem);
+        let slug = slugify(name);
+        let dir = path.parent().map_or(String::new(), |dir| {
+            let parts: Vec<_> = dir
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect();
+            parts.join("/")
+        });
+
+        let mut url = String::new();
+        let mut rest = self.pattern.as_str();
+        while let Some(colon) = rest.find(':') {
+            url.push_str(&rest[..colon]);
+            let name_len = rest[colon + 1..]
+                .find(|c: char| !c.is_ascii_alphabetic())
+                .unwrap_or(rest.len() - colon - 1);
+            let value = match &rest[colon + 1..colon + 1 + name_len] {
+                "path" if stem == "index" => dir.clone(),
+                "path" if dir.is_empty() => stem.clone(),
+                "path" => format!("{}/{}", dir, stem),
+                "slug" => slug.clone(),
+                "title" => file
+                    .outline
+                    .first()
+                    .map(|heading| slugify(&heading.text))
+                    .filter(|title| !title.is_empty())
+                    .unwrap_or_else(|| slug.clone()),
+                "year" => date.map_or(String::new(), |(year, _, _)| year.to_string()),
+                "month" => date.map_or(String::new(), |(_, month, _)| month.to_string()),
+                "day" => date.map_or(String::new(), |(_, _, day)| day.to_string()),
+                // not a placeholder, keep it as it is
+                _ => rest[colon..colon + 1 + name_len].to_string(),
+            };
+            url.push_str(&value);
+            rest = &rest[colon + 1 + name_len..];
+        }
+        url.push_str(rest);
+
+        let mut collapsed = String::with_capacity(url.len());
+        for c in url.chars() {
+            if !(c == '/' && collapsed.ends_with('/')) {
+                collapsed.push(c);
+            }
+        }
+        collapsed
+    }
+}
+
+// "2024-01-31-post" -> ((2024, 01, 31), "post")
+fn split_date(stem: &str) -> (Option<(&str, &str, &str)>, &str) {
+    let digits = |s: &str, len| s.len() == len && s.bytes().all(|b| b.is_ascii_digit());
+    let parts: Vec<_> = stem.splitn(4, '-').collect();
+    match parts.as_slice() {
+        [year, month, day, name] if digits(year, 4) && digits(month, 2) && digits(day, 2) => {
+            (Some((year, month, day)), name)
+        }
+        _ => (None, stem),
+    }
+}
+
+fn find_markdown(
+    root: &Path,
+    dir: &Path,
+    paths: &mut Vec<PathBuf>,
+    defaults: &mut BTreeMap<PathBuf, FrontMatter>,
+) -> Result<(), ProjectError> {
+    let full = root.join(dir);
+    let io_error = |e| ProjectError::Io(full.clone(), e);
+    let mut entries = fs::read_dir(&full)
+        .and_then(|entries| entries.collect::<Result<Vec<_>, _>>())
+        .map_err(io_error)?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let name = entry.file_name();
+        if name.to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = dir.join(&name);
+        let file_type = entry.file_type().map_err(io_error)?;
+        if file_type.is_dir() {
+            find_markdown(root, &path, paths, defaults)?;
+        } else if file_type.is_file() && is_markdown(&path) {
+            paths.push(path);
+        } else if file_type.is_file() && name == DEFAULTS {
+            let full = root.join(&path);
+            let raw = fs::read_to_string(&full).map_err(|e| ProjectError::Io(full, e))?;
+            defaults.insert(
+                dir.to_path_buf(),
+                FrontMatter::parse(FrontMatterFormat::Yaml, &raw),
+            );
+        }
+    }
+    Ok(())
+}
+
+fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| EXTENSIONS.iter().any(|e| e.eq_ignore_ascii_case(ext)))
+}
+
+// the defaults of the directories the file at `path` is in, the nearest one first so
+// its values are the ones that stay
+fn apply_defaults(document: &mut Document, path: &Path, defaults: &BTreeMap<PathBuf, FrontMatter>) {
+    for defaults in path.ancestors().skip(1).filter_map(|dir| defaults.get(dir)) {
+        document
+            .front_matter
+            .get_or_insert_with(|| FrontMatter::parse(FrontMatterFormat::Yaml, ""))
+            .apply_defaults(defaults);
+    }
+}
+
+fn load_document(
+    root: &Path,
+    path: &Path,
+    options: &ParserOptions,
+) -> Result<Document, ProjectError> {
+    let full = root.join(path);
+    let bytes = fs::read(&full).map_err(|e| ProjectError::Io(full.clone(), e))?;
+    Document::parse_bytes(&bytes, options).map_err(|e| ProjectError::Parse(path.to_path_buf(), e))
+}
+
+// The project path a link in the file at `from` points to, if it is a relative link to
+// a markdown file. Fragments and queries are dropped, `..` can't leave the root.
+fn resolve_link(from: &Path, url: &str) -> Option<PathBuf> {
+    let url = url.split(['#', '?']).next().unwrap_or("");
+    if url.is_empty() || url.contains(':') || url.starts_with("//") {
+        return None;
+    }
+    let target = match url.strip_prefix('/') {
+        Some(absolute) => PathBuf::from(absolute),
+        None => from.parent().unwrap_or(Path::new("")).join(url),
+    };
+    if !is_markdown(&target) {
+        return None;
+    }
+
+    let mut resolved = PathBuf::new();
+    for component in target.components() {
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            Component::ParentDir if !resolved.pop() => return None,
+            _ => {}
+        }
+    }
+    Some(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_link() {
+        let from = Path::new("guide/intro.md");
+        assert_eq!(
+            resolve_link(from, "setup.md#install"),
+            Some(PathBuf::from("guide/setup.md"))
+        );
+        assert_eq!(
+            resolve_link(from, "../index.md"),
+            Some(PathBuf::from("index.md"))
+        );
+        assert_eq!(
+            resolve_link(from, "/api/./a.md"),
+            Some(PathBuf::from("api/a.md"))
+        );
+        assert_eq!(resolve_link(from, "../../escape.md"), None);
+        assert_eq!(resolve_link(from, "https://example.com/a.md"), None);
+        assert_eq!(resolve_link(from, "image.png"), None);
+        assert_eq!(resolve_link(from, "#top"), None);
+    }
+
+    #[test]
+    fn test_load() {
+        let root = std::env::temp_dir().join(format!("doubledown-project-{}", std::process::id()));
+        let write = |path: &str, text: &str| {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        };
+        write(
+            "index.md",
+            "# Home\n[guide](guide/intro.md)\n[gone](missing.md)\n",
+        );
+        write(
+            "guide/intro.md",
+            "# Intro\n## Next\n[home](../index.md) [home](/index.md#top)\n",
+        );
+        write("guide/notes.txt", "not markdown");
+        write(".hidden/secret.md", "# Secret\n");
+
+        let project = Project::load(&root, &ParserOptions::default());
+        fs::remove_dir_all(&root).unwrap();
+        let project = project.unwrap();
+
+        let paths: Vec<_> = project.files.keys().cloned().collect();
+        assert_eq!(
+            paths,
+            vec![PathBuf::from("guide/intro.md"), PathBuf::from("index.md")]
+        );
+        let intro = &project.files[Path::new("guide/intro.md")];
+        assert_eq!(intro.outline.len(), 2);
+        assert_eq!(intro.links, vec![PathBuf::from("index.md")]);
+        assert_eq!(
+            project.files[Path::new("index.md")].links,
+            vec![PathBuf::from("guide/intro.md")]
+        );
+        assert_eq!(
+            project.backlinks(Path::new("index.md")),
+            vec![Path::new("guide/intro.md")]
+        );
+
+        let mut project = project;
+        project.rewrite_links(&Permalinks::default());
+        assert_eq!(
+            project.files[Path::new("guide/intro.md")].document.links(),
+            vec!["/", "/#top"]
+        );
+        assert_eq!(
+            project.files[Path::new("index.md")].document.links(),
+            vec!["/guide/intro/", "missing.md"]
+        );
+    }
+
+    #[test]
+    fn test_defaults() {
+        let root = std::env::temp_dir().join(format!("doubledown-defaults-{}", std::process::id()));
+        let write = |path: &str, text: &str| {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        };
+        write("_defaults.yaml", "layout: page\nauthor: Ann\n");
+        write("blog/_defaults.yaml", "layout: post\n");
+        write("about.md", "# About\n");
+        write("blog/a.md", "---\nauthor: Bob\n---\n# A\n");
+        write("blog/b.md", "---\ntags:\n  - x\n---\n# B\n");
+
+        let project = Project::load(&root, &ParserOptions::default());
+        fs::remove_dir_all(&root).unwrap();
+        let project = project.unwrap();
+
+        let front_matter =
+            |path: &str| project.files[Path::new(path)].document.front_matter.clone();
+        let about = front_matter("about.md").unwrap();
+        assert_eq!(
+            (about.get("layout"), about.get("author")),
+            (Some("page"), Some("Ann"))
+        );
+        assert_eq!(about.raw, "");
+        let a = front_matter("blog/a.md").unwrap();
+        assert_eq!(
+            (a.get("layout"), a.get("author")),
+            (Some("post"), Some("Bob"))
+        );
+        // nested YAML stays raw, without fields to merge into
+        assert_eq!(front_matter("blog/b.md").unwrap().fields, None);
+    }
+
+    #[test]
+    fn test_permalink() {
+        let file = |text| ProjectFile {
+            document: Document::default(),
+            outline: vec![OutlineEntry {
+                level: 1,
+                text: String::from(text),
+            }],
+            links: vec![],
+        };
+        let pattern = |pattern: &str| Permalinks {
+            pattern: pattern.to_string(),
+        };
+        let post = Path::new("blog/2024-01-31-Hello World.md");
+        assert_eq!(
+            pattern("/:year/:month/:day/:slug/").permalink(post, &file("")),
+            "/2024/01/31/hello-world/"
+        );
+        assert_eq!(
+            pattern("/:title.html").permalink(post, &file("Hi, there")),
+            "/hi-there.html"
+        );
+        assert_eq!(
+            pattern("/:title.html").permalink(post, &file("")),
+            "/hello-world.html"
+        );
+        assert_eq!(
+            pattern("/:year/:slug/").permalink(Path::new("about.md"), &file("")),
+            "/about/"
+        );
+        assert_eq!(
+            Permalinks::default().permalink(Path::new("docs/index.md"), &file("")),
+            "/docs/"
+        );
+        assert_eq!(
+            Permalinks::default().permalink(Path::new("docs/a.md"), &file("")),
+            "/docs/a/"
+        );
+        assert_eq!(
+            pattern("/p/:unknown").permalink(Path::new("a.md"), &file("")),
+            "/p/:unknown"
+        );
+    }
+}
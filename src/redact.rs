@@ -0,0 +1,309 @@
+use std::ops::Range;
+
+use crate::document::Document;
+use crate::parser::markdown::{Markdown, MarkdownInLine};
+
+// what replaces every character of a match, one for one so lengths stay the same
+pub const MASK: char = '█';
+
+// Finds the byte ranges of `text` to mask. Closures taking a &str work too.
+pub trait Pattern {
+    fn find(&self, text: &str) -> Vec<Range<usize>>;
+}
+
+impl<F: Fn(&str) -> Vec<Range<usize>>> Pattern for F {
+    fn find(&self, text: &str) -> Vec<Range<usize>> {
+        self(text)
+    }
+}
+
+// every occurrence of a fixed string, like an API key you know about
+pub struct Literal(pub String);
+
+impl Pattern for Literal {
+    fn find(&self, text: &str) -> Vec<Range<usize>> {
+        if self.0.is_empty() {
+            return vec![];
+        }
+        text.match_indices(&self.0)
+            .map(|(n, s)| n..n + s.len())
+            .collect()
+    }
+}
+
+// name@example.com
+pub struct Email;
+
+impl Pattern for Email {
+    fn find(&self, text: &str) -> Vec<Range<usize>> {
+        let local = |c: char| c.is_ascii_alphanumeric() || "._%+-".contains(c);
+        let domain = |c: char| c.is_ascii_alphanumeric() || c == '.' || c == '-';
+        let mut found: Vec<Range<usize>> = Vec::new();
+        for (at, _) in text.match_indices('@') {
+            if found.last().is_some_and(|last| at < last.end) {
+                continue;
+            }
+            let start = text[..at].rfind(|c| !local(c)).map_or(0, |n| n + 1);
+            let len = text[at + 1..]
+                .find(|c| !domain(c))
+                .unwrap_or(text.len() - at - 1);
+            // a sentence may end right after the address
+            let host = text[at + 1..at + 1 + len].trim_end_matches(['.', '-']);
+            let valid_host = host
+                .rsplit_once('.')
+                .is_some_and(|(name, tld)| !name.is_empty() && tld.len() >= 2);
+            if start < at && valid_host {
+                found.push(start..at + 1 + host.len());
+            }
+        }
+        found
+    }
+}
+
+// Phone numbers: 7 to 15 digits, optionally after a `+` and separated by spaces,
+// dashes, dots or parentheses, like +1 (555) 123-4567.
+pub struct Phone;
+
+impl Pattern for Phone {
+    fn find(&self, text: &str) -> Vec<Range<usize>> {
+        let mut found = Vec::new();
+        let bytes = text.as_bytes();
+        let mut n = 0;
+        while n < bytes.len() {
+            let starts = bytes[n].is_ascii_digit() || (bytes[n] == b'+' || bytes[n] == b'(');
+            // inside a word or a longer number
+            let glued = n > 0 && (bytes[n - 1].is_ascii_alphanumeric());
+            if !starts || glued {
+                n += 1;
+                continue;
+            }
+            let (mut end, mut digits) = (n, 0);
+            let mut m = n;
+            while m < bytes.len() {
+                match bytes[m] {
+                    b'0'..=b'9' => {
+                        digits += 1;
+                        end = m + 1;
+                    }
+                    b'+' if m == n => {}
+                    b' ' | b'-' | b'.' | b'(' | b')' => {}
+                    _ => break,
+                }
+                m += 1;
+            }
+            let glued = bytes.get(end).is_some_and(|b| b.is_ascii_alphanumeric());
+            // 2024-01-01 has enough digits too
+            let date = end - n == 10 && bytes[n + 4] == b'-' && bytes[n + 7] == b'-';
+            if (7..=15).contains(&digits) && !glued && !date {
+                found.push(n..end);
+                n = end;
+            } else {
+                n = m.max(n + 1);
+            }
+        }
+        found
+    }
+}
+
+// `text` with everything any of `patterns` finds replaced by MASK
+pub fn redact_text(text: &str, patterns: &[&dyn Pattern]) -> String {
+    let mut ranges: Vec<Range<usize>> = patterns
+        .iter()
+        .flat_map(|pattern| pattern.find(text))
+        .collect();
+    if ranges.is_empty() {
+        return text.to_string();
+    }
+    ranges.sort_by_key(|range| range.start);
+
+    let mut redacted = String::with_capacity(text.len());
+    let mut last = 0;
+    for range in ranges {
+        let start = range.start.max(last);
+        if start >= range.end {
+            continue;
+        }
+        redacted.push_str(&text[last..start]);
+        redacted.extend(text[start..range.end].chars().map(|_| MASK));
+        last = range.end;
+    }
+    redacted.push_str(&text[last..]);
+    redacted
+}
+
+fn redact_inline(inline: &mut MarkdownInLine, patterns: &[&dyn Pattern]) {
+    let redact = |s: &mut String| *s = redact_text(s, patterns);
+    match inline {
+        // destinations too, a mailto: link would give the address away
+        MarkdownInLine::Link(_, url, title) => {
+            redact(url);
+            title.iter_mut().for_each(redact);
+        }
+        MarkdownInLine::WikiLink { target, alias } => {
+            redact(target);
+            alias.iter_mut().for_each(redact);
+        }
+        MarkdownInLine::Image(text, url, title) => {
+            redact(text);
+            redact(url);
+            title.iter_mut().for_each(redact);
+        }
+        MarkdownInLine::InlineCode(s, _)
+        | MarkdownInLine::Math(s)
+        | MarkdownInLine::Plain(s)
+        | MarkdownInLine::FootnoteRef(s)
+        | MarkdownInLine::Html(s) => redact(s),
+        // the elements inside are visited on their own
+        MarkdownInLine::Bold(_)
+        | MarkdownInLine::Italic(_)
+        | MarkdownInLine::Strikethrough(_)
+        | MarkdownInLine::Highlight(_)
+        | MarkdownInLine::Subscript(_)
+        | MarkdownInLine::Superscript(_)
+        | MarkdownInLine::InlineFootnote(_)
+        | MarkdownInLine::HardBreak
+        | MarkdownInLine::SoftBreak
+        | MarkdownInLine::Emoji(_) => {}
+    }
+}
+
+// redacts every element of `text` and the elements inside them
+fn redact_inlines<'a>(
+    text: impl IntoIterator<Item = &'a mut MarkdownInLine>,
+    patterns: &[&dyn Pattern],
+) {
+    for inline in text {
+        inline.for_each_mut(&mut |inline| redact_inline(inline, patterns));
+    }
+}
+
+fn redact_block(block: &mut Markdown, patterns: &[&dyn Pattern]) {
+    match block {
+        Markdown::CodeBlock(code, _) | Markdown::Html(code) | Markdown::Comment(code) => {
+            *code = redact_text(code, patterns)
+        }
+        Markdown::OrderedList(items, _) | Markdown::UnorderedList(items, _) => {
+            for block in items.iter_mut().flat_map(|item| &mut item.blocks) {
+                redact_block(block, patterns);
+            }
+        }
+        Markdown::Quote {
+            body: children,
+            cite,
+        } => {
+            redact_inlines(cite.iter_mut().flatten(), patterns);
+            for block in children {
+                redact_block(block, patterns);
+            }
+        }
+        Markdown::FootnoteDefinition(_, children) | Markdown::Container(_, _, children) => {
+            for block in children {
+                redact_block(block, patterns);
+            }
+        }
+        Markdown::BlockTable(table) => {
+            for block in table.cells_mut().flatten() {
+                redact_block(block, patterns);
+            }
+            redact_inlines(table.caption.iter_mut().flatten(), patterns);
+        }
+        Markdown::Admonition { title, body, .. } => {
+            redact_inlines(title, patterns);
+            for block in body {
+                redact_block(block, patterns);
+            }
+        }
+        Markdown::Directive { label, body, .. } => {
+            redact_inlines(label, patterns);
+            for block in body.iter_mut().flatten() {
+                redact_block(block, patterns);
+            }
+        }
+        Markdown::Heading(_, _)
+        | Markdown::Figure { .. }
+        | Markdown::Table(_)
+        | Markdown::DefinitionList(_)
+        | Markdown::Paragraph(_) => {
+            block.for_each_inline_mut(&mut |inline| redact_inline(inline, patterns))
+        }
+        Markdown::Rule | Markdown::TocMarker | Markdown::Include(_) => {}
+        Markdown::MathBlock(math) => *math = redact_text(math, patterns),
+    }
+}
+
+impl Document {
+    // Masks everything `patterns` find in text, link and image destinations and code,
+    // keeping every node in place and every masked string as long in characters.
+    pub fn redact(&mut self, patterns: &[&dyn Pattern]) {
+        for block in &mut self.blocks {
+            redact_block(block, patterns);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::markdown::{FenceInfo, ListInfo};
+    use crate::parser::options::ParserOptions;
+
+    #[test]
+    fn test_patterns() {
+        let text =
+            "mail ann.b+x@mail.example.org. or call +1 (555) 123-4567, not 2024-01-01 or v1.2.3";
+        assert_eq!(
+            redact_text(text, &[&Email]),
+            "mail ████████████████████████. or call +1 (555) 123-4567, not 2024-01-01 or v1.2.3"
+        );
+        assert_eq!(
+            redact_text(text, &[&Phone]),
+            "mail ann.b+x@mail.example.org. or call █████████████████, not 2024-01-01 or v1.2.3"
+        );
+        assert_eq!(
+            redact_text("a@b c@d.e x@y.co", &[&Email]),
+            "a@b c@d.e ██████"
+        );
+        let even = |text: &str| -> Vec<Range<usize>> {
+            text.match_indices('2').map(|(n, _)| n..n + 1).collect()
+        };
+        assert_eq!(
+            redact_text("123 sk-1", &[&even, &Literal(String::from("sk-1"))]),
+            "1█3 ████"
+        );
+    }
+
+    #[test]
+    fn test_redact_document() {
+        let mut document = Document::parse(
+            "# Contact bob@example.com\n- [mail](mailto:bob@example.com) `bob@example.com`\n```\nkey = \"hunter22\"\n```\n",
+            &ParserOptions::default(),
+        )
+        .unwrap();
+        document.redact(&[&Email, &Literal(String::from("hunter22"))]);
+        let masked = |n| MASK.to_string().repeat(n);
+        assert_eq!(
+            document.blocks,
+            vec![
+                Markdown::Heading(
+                    1,
+                    vec![MarkdownInLine::Plain(format!("Contact {}", masked(15)))]
+                ),
+                Markdown::UnorderedList(
+                    vec![crate::parser::markdown::ListItem::new(vec![
+                        Markdown::Paragraph(vec![
+                            MarkdownInLine::Link(
+                                vec![MarkdownInLine::Plain(String::from("mail"))],
+                                format!("mailto:{}", masked(15)),
+                                None,
+                            ),
+                            MarkdownInLine::Plain(String::from(" ")),
+                            MarkdownInLine::InlineCode(masked(15), None),
+                        ])
+                    ])],
+                    ListInfo::default()
+                ),
+                Markdown::CodeBlock(format!("key = \"{}\"\n", masked(8)), FenceInfo::default()),
+            ]
+        );
+    }
+}
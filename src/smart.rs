@@ -0,0 +1,128 @@
+use crate::document::Document;
+use crate::parser::markdown::{MarkdownInLine, MarkdownText};
+
+// whether a quote after `prev` opens, at the start of a text, after a space or after an
+// opening bracket, quote or dash
+fn is_opening(prev: Option<char>) -> bool {
+    match prev {
+        None => true,
+        Some(c) => c.is_whitespace() || "([{<“‘—–-/".contains(c),
+    }
+}
+
+// `text` with curly quotes, dashes and ellipses in place of their ASCII stand-ins:
+// "a" and 'a' become “a” and ‘a’, it's it’s, -- an en dash –, --- an em dash — and ...
+// an ellipsis …. `prev` is the character before `text` and is left at its last one.
+pub fn smarten(text: &str, prev: &mut Option<char>) -> String {
+    let mut smart = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let (replacement, len) = match c {
+            '"' if is_opening(*prev) => ('“', 1),
+            '"' => ('”', 1),
+            '\'' if is_opening(*prev) => ('‘', 1),
+            '\'' => ('’', 1),
+            '.' if rest.starts_with("...") => ('…', 3),
+            '-' => match rest.bytes().take_while(|&b| b == b'-').count() {
+                3 => ('—', 3),
+                2 => ('–', 2),
+                // a longer run is probably a rule drawn in text, left as it is
+                run => {
+                    smart.push_str(&rest[..run]);
+                    rest = &rest[run..];
+                    *prev = Some('-');
+                    continue;
+                }
+            },
+            c => (c, c.len_utf8()),
+        };
+        smart.push(replacement);
+        *prev = Some(replacement);
+        rest = &rest[len..];
+    }
+    smart
+}
+
+// smarten()s the plain text in `text`, the quotes around a span or link are paired up
+// across it. Code and math are left alone.
+fn smarten_text(text: &mut MarkdownText) {
+    let mut prev = None;
+    for inline in text {
+        inline.for_each_mut(&mut |inline| match inline {
+            MarkdownInLine::Plain(s) => *s = smarten(s, &mut prev),
+            MarkdownInLine::HardBreak | MarkdownInLine::SoftBreak => prev = Some('\n'),
+            // like a word in the text, `code`'s gets an apostrophe
+            MarkdownInLine::InlineCode(_, _)
+            | MarkdownInLine::Math(_)
+            | MarkdownInLine::Image(_, _, _)
+            | MarkdownInLine::Emoji(_)
+            | MarkdownInLine::WikiLink { .. }
+            | MarkdownInLine::FootnoteRef(_) => prev = Some('\u{FFFC}'),
+            // spans are followed by what is in them, tags don't show
+            MarkdownInLine::Link(_, _, _)
+            | MarkdownInLine::Bold(_)
+            | MarkdownInLine::Italic(_)
+            | MarkdownInLine::Strikethrough(_)
+            | MarkdownInLine::Highlight(_)
+            | MarkdownInLine::Subscript(_)
+            | MarkdownInLine::Superscript(_)
+            | MarkdownInLine::InlineFootnote(_)
+            | MarkdownInLine::Html(_) => {}
+        });
+    }
+}
+
+impl Document {
+    // Typographic punctuation in all text, see smarten(). Documents are parsed with the
+    // quotes and dashes as typed, this is the pass to run before rendering for print.
+    pub fn smart_punctuation(&mut self) {
+        for block in &mut self.blocks {
+            block.for_each_text_mut(&mut smarten_text);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::markdown::Markdown;
+    use crate::parser::options::ParserOptions;
+
+    #[test]
+    fn test_smarten() {
+        assert_eq!(
+            smarten("\"It's 9--5,\" she said---'or so'... x-ray", &mut None),
+            "“It’s 9–5,” she said—‘or so’… x-ray"
+        );
+        let mut prev = Some('a');
+        assert_eq!(smarten("\" ----", &mut prev), "” ----");
+        assert_eq!(prev, Some('-'));
+    }
+
+    #[test]
+    fn test_smart_punctuation() {
+        let mut document = Document::parse(
+            "\"*Quoted*\" and `\"code\"`'s\n\n> 'a'\n",
+            &ParserOptions::default(),
+        )
+        .unwrap();
+        document.smart_punctuation();
+        let plain = |s: &str| MarkdownInLine::Plain(String::from(s));
+        assert_eq!(
+            document.blocks,
+            vec![
+                Markdown::Paragraph(vec![
+                    plain("“"),
+                    MarkdownInLine::Italic(vec![plain("Quoted")]),
+                    plain("” and "),
+                    MarkdownInLine::InlineCode(String::from("\"code\""), None),
+                    plain("’s"),
+                ]),
+                Markdown::Quote {
+                    body: vec![Markdown::Paragraph(vec![plain("‘a’")])],
+                    cite: None
+                },
+            ]
+        );
+    }
+}